@@ -1,10 +1,13 @@
+use crate::entity::Component;
+
 #[derive(Clone, PartialEq, Debug)]
 pub struct RGBA {
     rgb: glam::U8Vec3,
     alpha: f32,
 }
 
-#[derive(Clone, PartialEq, Debug)]
+#[derive(Clone, PartialEq, Debug, Component)]
+#[component(storage = "table")]
 pub enum Color {
     Uniform(RGBA),
     PerVertex(Vec<f32>),
@@ -1,5 +1,6 @@
 use crate::components::color::Color;
 
+#[derive(Clone, PartialEq, Debug)]
 pub struct Material {
     pub shader_id: u32,
     pub color: Color,
@@ -1,6 +1,9 @@
 use glam::Vec3;
 
-#[derive(Clone, PartialEq, Debug)]
+use crate::entity::Component;
+
+#[derive(Clone, PartialEq, Debug, Component)]
+#[component(storage = "table")]
 pub struct Shape {
     vertices: Vec<Vec3>,
 }
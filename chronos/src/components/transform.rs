@@ -1,9 +1,21 @@
 use glam::{Mat4, Quat, Vec3};
 
+use crate::entity::Component;
+
+#[derive(Clone, Copy, Debug, PartialEq, Component)]
+#[component(storage = "table")]
 pub struct Transform {
     matrix: Mat4,
 }
 
+/// World-space transform of an entity: its `Transform` multiplied by the
+/// transforms of all its ancestors. Written by
+/// [`EntityManager::propagate_transforms`](crate::entity::EntityManager::propagate_transforms).
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct GlobalTransform {
+    matrix: Mat4,
+}
+
 pub struct TransformBuilder {
     translation: Vec3,
     rotation: Vec3,
@@ -53,6 +65,11 @@ impl Transform {
         Self { matrix }
     }
 
+    #[must_use]
+    pub fn from_matrix(matrix: Mat4) -> Self {
+        Self { matrix }
+    }
+
     #[must_use]
     pub fn from_translation(translation: Vec3) -> Self {
         Self {
@@ -115,6 +132,34 @@ impl Transform {
     }
 }
 
+impl GlobalTransform {
+    #[must_use]
+    pub fn from_matrix(matrix: Mat4) -> Self {
+        Self { matrix }
+    }
+
+    #[must_use]
+    pub fn matrix(&self) -> Mat4 {
+        self.matrix
+    }
+}
+
+impl Default for GlobalTransform {
+    fn default() -> Self {
+        Self {
+            matrix: Mat4::IDENTITY,
+        }
+    }
+}
+
+impl From<Transform> for GlobalTransform {
+    fn from(transform: Transform) -> Self {
+        Self {
+            matrix: transform.matrix,
+        }
+    }
+}
+
 impl Default for Transform {
     fn default() -> Self {
         Self::identity()
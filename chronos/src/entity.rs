@@ -1,50 +1,408 @@
+pub mod commands;
 mod component_storage;
-use crate::entity::component_storage::ComponentStorage;
+pub mod dynamic;
+pub mod events;
+pub mod group;
+pub mod hierarchy;
+pub mod name;
+pub mod query;
+pub mod reflect;
+pub mod resources;
+pub mod snapshot;
+pub mod stats;
+mod transfer;
 
+use std::any::TypeId;
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicIsize, Ordering};
+
+pub use chronos_macros::{Bundle, Component};
+#[doc(hidden)]
+pub use component_storage::ComponentStorage;
+pub use component_storage::{ComponentHook, ComponentHooks, StorageConfig, StorageKind, Tick};
+
+use crate::entity::commands::{CommandQueue, Commands};
+use crate::entity::events::Events;
+use crate::entity::query::{QueryData, QueryFilter, QueryIter, TickRange};
+use crate::entity::reflect::TypeRegistry;
+use crate::entity::resources::Resources;
+use crate::scene::SceneRegistry;
+
+/// Handle to an entity living in an [`EntityManager`].
+///
+/// The index of a removed entity is reused, but its generation is bumped, so
+/// handles kept around after `remove_entity` no longer resolve to anything.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct Entity {
+    index: usize,
+    generation: u32,
+}
+
+/// Set of components added to an entity together. Implemented for tuples of
+/// up to 16 components and derived for structs with `#[derive(Bundle)]`.
 pub trait ComponentBundle {
-    fn add_to_entity(self, entity_id: usize, storage: &mut ComponentStorage);
+    fn add_to_entity(self, entity: Entity, storage: &mut ComponentStorage);
+
+    /// Adds each bundle to its entity. Implementations insert every component
+    /// type in one go with [`ComponentStorage::extend_components`].
+    fn add_to_entities(batch: Vec<(Entity, Self)>, storage: &mut ComponentStorage)
+    where
+        Self: Sized,
+    {
+        for (entity, bundle) in batch {
+            bundle.add_to_entity(entity, storage);
+        }
+    }
+}
+
+/// Component type with its storage configuration, usually derived with
+/// `#[derive(Component)]` and applied with [`EntityManager::register_component`].
+/// Types without it can still be used as components, with the default storage.
+pub trait Component: Send + Sync + 'static {
+    const STORAGE: StorageConfig = StorageConfig::DEFAULT;
 }
 
-#[derive(Default)]
 pub struct EntityManager {
     next_id: usize,
     free_ids: Vec<usize>,
+    /// Number of `free_ids` not handed out by `reserve_entity` yet. Negative
+    /// values count reserved IDs past `next_id`.
+    free_cursor: AtomicIsize,
+    generations: Vec<u32>,
+    /// Whether each index is held by a live entity, as a freed index keeps
+    /// its bumped generation until it is reused.
+    alive: Vec<bool>,
+    /// Generation to free the listed live indices with at the least, as a
+    /// restored snapshot may have rolled back a newer generation whose
+    /// handles are still around.
+    generation_floors: HashMap<usize, u32>,
     components: ComponentStorage,
+    resources: Resources,
+    event_updaters: Vec<fn(&mut Resources)>,
+    pub(crate) scene_registry: SceneRegistry,
+    pub(crate) type_registry: TypeRegistry,
+}
+
+impl Entity {
+    pub(crate) fn new(index: usize, generation: u32) -> Self {
+        Self { index, generation }
+    }
+
+    #[must_use]
+    pub fn index(self) -> usize {
+        self.index
+    }
+
+    #[must_use]
+    pub fn generation(self) -> u32 {
+        self.generation
+    }
 }
 
 impl EntityManager {
     #[must_use]
     pub fn new(storage_capacity: usize) -> Self {
+        let mut components = ComponentStorage::new(storage_capacity);
+        snapshot::register_builtins(&mut components);
         Self {
             next_id: 0,
             free_ids: Vec::new(),
-            components: ComponentStorage::new(storage_capacity),
+            free_cursor: AtomicIsize::new(0),
+            generations: Vec::new(),
+            alive: Vec::new(),
+            generation_floors: HashMap::new(),
+            components,
+            resources: Resources::default(),
+            event_updaters: Vec::new(),
+            scene_registry: SceneRegistry::default(),
+            type_registry: TypeRegistry::default(),
+        }
+    }
+
+    pub fn create_entity<T: ComponentBundle>(&mut self, component_bundle: T) -> Entity {
+        let entity = self.create_entity_id();
+        self.insert_bundle(entity, component_bundle);
+        entity
+    }
+
+    /// Creates one entity per bundle, handing out all their IDs at once and
+    /// inserting each component type in one go, which is much faster than
+    /// calling [`EntityManager::create_entity`] in a loop for large batches.
+    /// Hooks run once every bundle is inserted.
+    pub fn spawn_batch<I>(&mut self, bundles: I) -> Vec<Entity>
+    where
+        I: IntoIterator,
+        I::Item: ComponentBundle,
+    {
+        let bundles: Vec<I::Item> = bundles.into_iter().collect();
+        let entities = self.create_entity_ids(bundles.len());
+        let batch = entities.iter().copied().zip(bundles).collect();
+        I::Item::add_to_entities(batch, &mut self.components);
+        self.run_triggered_hooks();
+        entities
+    }
+
+    /// Hands out an entity ID without needing `&mut self`, e.g. for
+    /// [`Commands`](commands::Commands). The entity becomes alive, without
+    /// components, on the next structural change of the manager.
+    pub fn reserve_entity(&self) -> Entity {
+        let cursor = self.free_cursor.fetch_sub(1, Ordering::Relaxed);
+        if cursor > 0 {
+            let free_id = self.free_ids[cursor.unsigned_abs() - 1];
+            Entity::new(free_id, self.generations[free_id])
+        } else {
+            Entity::new(self.next_id + cursor.unsigned_abs(), 0)
         }
     }
 
-    pub fn create_entity<T: ComponentBundle>(&mut self, component_bundle: T) -> usize {
-        let entity_id = self.create_entity_id();
-        component_bundle.add_to_entity(entity_id, &mut self.components);
-        entity_id
+    /// Turns every ID handed out by [`EntityManager::reserve_entity`] into a live entity.
+    pub fn flush_reserved(&mut self) {
+        let cursor = *self.free_cursor.get_mut();
+        let reused = if cursor < 0 { 0 } else { cursor.unsigned_abs() };
+        for &free_id in &self.free_ids[reused..] {
+            self.alive[free_id] = true;
+        }
+        self.free_ids.truncate(reused);
+        if cursor < 0 {
+            self.next_id += cursor.unsigned_abs();
+            self.generations.resize(self.next_id, 0);
+            self.alive.resize(self.next_id, true);
+        }
+        self.sync_free_cursor();
     }
 
     #[must_use]
-    pub fn get_component<T: 'static>(&self, entity_id: usize) -> Option<&T> {
-        self.components.get_component::<T>(entity_id)
+    pub fn get_component<T: Send + Sync + 'static>(&self, entity: Entity) -> Option<&T> {
+        if !self.entity_exists(entity) {
+            return None;
+        }
+        self.components.get_component::<T>(entity)
     }
 
-    pub fn add_component<T: 'static>(&mut self, entity_id: usize, component: T) {
-        self.components.add_component(entity_id, component);
+    #[must_use]
+    pub fn get_component_mut<T: Send + Sync + 'static>(
+        &mut self,
+        entity: Entity,
+    ) -> Option<&mut T> {
+        if !self.entity_exists(entity) {
+            return None;
+        }
+        self.components.get_component_mut::<T>(entity)
+    }
+
+    /// Adds a component to the entity, returning the previous value if the
+    /// entity already had a component of this type.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `T` is [`Parent`](hierarchy::Parent) or
+    /// [`Children`](hierarchy::Children), which only
+    /// [`EntityManager::set_parent`] may add.
+    pub fn add_component<T: Send + Sync + 'static>(
+        &mut self,
+        entity: Entity,
+        component: T,
+    ) -> Option<T> {
+        assert_not_hierarchy::<T>();
+        self.add_component_unchecked(entity, component)
+    }
+
+    /// # Panics
+    ///
+    /// Panics if `T` is [`Parent`](hierarchy::Parent) or
+    /// [`Children`](hierarchy::Children), which only
+    /// [`EntityManager::remove_parent`] and despawns may remove.
+    pub fn remove_component<T: Send + Sync + 'static>(&mut self, entity: Entity) -> Option<T> {
+        assert_not_hierarchy::<T>();
+        self.remove_component_unchecked(entity)
+    }
+
+    /// [`EntityManager::add_component`] for the hierarchy components too.
+    fn add_component_unchecked<T: Send + Sync + 'static>(
+        &mut self,
+        entity: Entity,
+        component: T,
+    ) -> Option<T> {
+        if !self.entity_exists(entity) {
+            return None;
+        }
+        let previous = self.components.add_component(entity, component);
+        self.run_triggered_hooks();
+        previous
+    }
+
+    /// [`EntityManager::remove_component`] for the hierarchy components too.
+    fn remove_component_unchecked<T: Send + Sync + 'static>(
+        &mut self,
+        entity: Entity,
+    ) -> Option<T> {
+        if !self.entity_exists(entity) {
+            return None;
+        }
+        let Some(hook) = self.components.remove_hook::<T>(entity) else {
+            return self.components.remove_component::<T>(entity);
+        };
+        let mut queue = self.run_hooks(&[(hook, entity)]);
+        let component = self.components.remove_component::<T>(entity);
+        queue.apply(self);
+        component
+    }
+
+    /// Sizes the storage of `T` according to [`Component::STORAGE`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if the storage kind of `T` changes while components of `T` are stored.
+    pub fn register_component<T: Component>(&mut self) {
+        self.register_component_with::<T>(T::STORAGE);
+    }
+
+    /// Sizes the storage of `T` according to `config`, for types that do not
+    /// implement [`Component`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if the storage kind of `T` changes while components of `T` are stored.
+    pub fn register_component_with<T: Send + Sync + 'static>(&mut self, config: StorageConfig) {
+        self.components.register_component_type_with::<T>(config);
+    }
+
+    /// Replaces the lifecycle hooks of `T`.
+    pub fn register_component_hooks<T: Send + Sync + 'static>(&mut self, hooks: ComponentHooks) {
+        self.components.set_hooks::<T>(hooks);
     }
 
     #[must_use]
-    pub fn has_component<T: 'static>(&self, entity_id: usize) -> bool {
-        self.components.has_component::<T>(entity_id)
+    pub fn has_component<T: Send + Sync + 'static>(&self, entity: Entity) -> bool {
+        self.entity_exists(entity) && self.components.has_component::<T>(entity)
+    }
+
+    /// Removes the entity with all its components, and recursively every
+    /// descendant attached with [`EntityManager::set_parent`]. The `on_remove`
+    /// hooks of all removed components run before any of them is dropped.
+    pub fn remove_entity(&mut self, entity: Entity) {
+        self.remove_batch(&[entity]);
+    }
+
+    /// Same as [`EntityManager::remove_entity`] for every entity in
+    /// `entities`, visiting each component storage once for the whole batch.
+    /// Entities that do not exist are skipped.
+    pub fn remove_batch(&mut self, entities: &[Entity]) {
+        if let Some(mut queue) = self.remove_trees(entities, ComponentStorage::remove_entities) {
+            queue.apply(self);
+        }
     }
 
-    pub fn remove_entity(&mut self, entity_id: usize) {
-        self.components.remove_all_components(entity_id);
-        self.free_ids.push(entity_id);
+    /// Iterates every entity that has all components requested by `Q`, e.g.
+    /// `query::<(&Shape, &mut Transform, Option<&Color>)>()`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `Q` accesses a component mutably more than once, such as
+    /// `(&mut Transform, &Transform)`, or writes a [`Name`](name::Name).
+    pub fn query<Q: QueryData>(&mut self) -> QueryIter<'_, Q> {
+        self.query_filtered::<Q, ()>()
+    }
+
+    /// Same as [`EntityManager::query`], but only visits entities that pass
+    /// the filter `F`, e.g. `query_filtered::<&Shape, Without<Material>>()`.
+    /// [`Added`](query::Added) and [`Changed`](query::Changed) match
+    /// components touched since the last [`EntityManager::advance_tick`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if `Q` accesses a component mutably more than once, or writes a
+    /// [`Name`](name::Name).
+    pub fn query_filtered<Q: QueryData, F: QueryFilter>(&mut self) -> QueryIter<'_, Q, F> {
+        let ticks = TickRange::new(
+            self.components.last_change_tick(),
+            self.components.change_tick(),
+        );
+        // SAFETY: the iterator keeps `self` mutably borrowed.
+        unsafe { QueryIter::new(self, ticks) }
+    }
+
+    /// Current world tick, stamped on every added or mutably accessed component.
+    #[must_use]
+    pub fn change_tick(&self) -> Tick {
+        self.components.change_tick()
+    }
+
+    /// Starts a new tick. Called once per frame by the engine.
+    pub fn advance_tick(&mut self) -> Tick {
+        self.components.advance_tick()
+    }
+
+    pub(crate) fn increment_change_tick(&self) -> Tick {
+        self.components.increment_change_tick()
+    }
+
+    /// Inserts a resource, returning the previous one of the same type.
+    pub fn insert_resource<T: Send + Sync + 'static>(&mut self, resource: T) -> Option<T> {
+        self.resources.insert(resource)
+    }
+
+    pub fn remove_resource<T: Send + Sync + 'static>(&mut self) -> Option<T> {
+        self.resources.remove::<T>()
+    }
+
+    #[must_use]
+    pub fn resource<T: Send + Sync + 'static>(&self) -> Option<&T> {
+        self.resources.get::<T>()
+    }
+
+    #[must_use]
+    pub fn resource_mut<T: Send + Sync + 'static>(&mut self) -> Option<&mut T> {
+        self.resources.get_mut::<T>()
+    }
+
+    #[must_use]
+    pub fn has_resource<T: Send + Sync + 'static>(&self) -> bool {
+        self.resources.contains::<T>()
+    }
+
+    /// Registers the event type `T`, storing its [`Events`] queue as a
+    /// resource that [`EntityManager::update_events`] keeps rotating.
+    pub fn add_event<T: Send + Sync + 'static>(&mut self) {
+        if self.resources.contains::<Events<T>>() {
+            return;
+        }
+        self.resources.insert(Events::<T>::default());
+        self.event_updaters.push(|resources| {
+            if let Some(events) = resources.get_mut::<Events<T>>() {
+                events.update();
+            }
+        });
+    }
+
+    /// Sends an event, registering its type first if needed.
+    pub fn send_event<T: Send + Sync + 'static>(&mut self, event: T) {
+        self.add_event::<T>();
+        if let Some(events) = self.resources.get_mut::<Events<T>>() {
+            events.send(event);
+        }
+    }
+
+    /// Advances every registered event queue by one frame. Called once per
+    /// frame by the engine; events are dropped after two calls.
+    pub fn update_events(&mut self) {
+        for update in &self.event_updaters {
+            update(&mut self.resources);
+        }
+    }
+
+    pub(crate) fn resources(&self) -> &Resources {
+        &self.resources
+    }
+
+    /// Iterates all live entities in index order.
+    pub fn entities(&self) -> impl Iterator<Item = Entity> + '_ {
+        self.generations
+            .iter()
+            .zip(&self.alive)
+            .enumerate()
+            .filter(|(_, (_, alive))| **alive)
+            .map(|(index, (generation, _))| Entity::new(index, *generation))
     }
 
     #[must_use]
@@ -53,46 +411,225 @@ impl EntityManager {
     }
 
     #[must_use]
-    pub fn entity_exists(&self, entity_id: usize) -> bool {
-        entity_id < self.next_id && !self.free_ids.contains(&entity_id)
+    pub fn entity_exists(&self, entity: Entity) -> bool {
+        self.generations.get(entity.index) == Some(&entity.generation) && self.alive[entity.index]
     }
 
-    fn create_entity_id(&mut self) -> usize {
-        if let Some(free_id) = self.free_ids.pop() {
-            free_id
+    fn create_entity_id(&mut self) -> Entity {
+        self.flush_reserved();
+        let entity = if let Some(free_id) = self.free_ids.pop() {
+            self.alive[free_id] = true;
+            Entity::new(free_id, self.generations[free_id])
         } else {
             let id = self.next_id;
             self.next_id += 1;
-            id
+            self.generations.push(0);
+            self.alive.push(true);
+            Entity::new(id, 0)
+        };
+        self.sync_free_cursor();
+        entity
+    }
+
+    /// Hands out `count` IDs, reusing free ones in the order
+    /// [`EntityManager::create_entity`] would.
+    fn create_entity_ids(&mut self, count: usize) -> Vec<Entity> {
+        self.flush_reserved();
+        let reused = count.min(self.free_ids.len());
+        let first_reused = self.free_ids.len() - reused;
+        let mut entities: Vec<Entity> = self
+            .free_ids
+            .drain(first_reused..)
+            .rev()
+            .map(|free_id| {
+                self.alive[free_id] = true;
+                Entity::new(free_id, self.generations[free_id])
+            })
+            .collect();
+        let first_new = self.next_id;
+        self.next_id += count - reused;
+        self.generations.resize(self.next_id, 0);
+        self.alive.resize(self.next_id, true);
+        entities.extend((first_new..self.next_id).map(|id| Entity::new(id, 0)));
+        self.sync_free_cursor();
+        entities
+    }
+
+    pub(crate) fn insert_bundle<T: ComponentBundle>(
+        &mut self,
+        entity: Entity,
+        component_bundle: T,
+    ) {
+        component_bundle.add_to_entity(entity, &mut self.components);
+        self.run_triggered_hooks();
+    }
+
+    /// Runs `hooks` and returns the structural changes they recorded.
+    fn run_hooks(&self, hooks: &[(ComponentHook, Entity)]) -> CommandQueue {
+        let mut queue = CommandQueue::default();
+        let mut commands = Commands::new(&mut queue, self);
+        for &(hook, entity) in hooks {
+            hook(self, entity, &mut commands);
+        }
+        queue
+    }
+
+    fn run_triggered_hooks(&mut self) {
+        let hooks = self.components.take_triggered_hooks();
+        if !hooks.is_empty() {
+            self.run_hooks(&hooks).apply(self);
+        }
+    }
+
+    /// Detaches the roots from their parents, runs the `on_remove` hooks of
+    /// the roots and their descendants, and frees all their IDs, letting
+    /// `clear` take their components out first. Each root comes right after
+    /// its descendants in the list passed to `clear`. Returns the commands
+    /// recorded by the hooks, or `None` if none of the roots exists.
+    fn remove_trees(
+        &mut self,
+        roots: &[Entity],
+        clear: impl FnOnce(&mut ComponentStorage, &[Entity]),
+    ) -> Option<CommandQueue> {
+        self.flush_reserved();
+        let mut removed = Vec::new();
+        let mut seen = HashSet::new();
+        for &root in roots {
+            // Roots already removed as descendants of an earlier root are skipped.
+            if !self.entity_exists(root) || seen.contains(&root) {
+                continue;
+            }
+            self.detach_from_parent(root);
+            for entity in self.descendants(root).into_iter().chain([root]) {
+                if seen.insert(entity) {
+                    removed.push(entity);
+                }
+            }
+        }
+        if removed.is_empty() {
+            return None;
         }
+        let hooks: Vec<_> = removed
+            .iter()
+            .flat_map(|&removed| {
+                self.components
+                    .remove_hooks(removed)
+                    .map(move |hook| (hook, removed))
+            })
+            .collect();
+        let queue = self.run_hooks(&hooks);
+        // Entities reserved by the hooks must not be handed out again once
+        // the removed IDs are freed.
+        self.flush_reserved();
+
+        clear(&mut self.components, &removed);
+        for removed in removed {
+            let generation = removed.generation.wrapping_add(1);
+            self.generations[removed.index] = self
+                .generation_floors
+                .remove(&removed.index)
+                .map_or(generation, |floor| floor.max(generation));
+            self.alive[removed.index] = false;
+            self.free_ids.push(removed.index);
+        }
+        self.sync_free_cursor();
+        Some(queue)
+    }
+
+    fn sync_free_cursor(&mut self) {
+        *self.free_cursor.get_mut() =
+            isize::try_from(self.free_ids.len()).expect("free entity IDs exceed isize::MAX");
+    }
+}
+
+/// Keeps [`Parent`](hierarchy::Parent) and [`Children`](hierarchy::Children)
+/// out of the generic component API, which would let them disagree.
+fn assert_not_hierarchy<T: 'static>() {
+    let type_id = TypeId::of::<T>();
+    assert!(
+        type_id != TypeId::of::<hierarchy::Parent>()
+            && type_id != TypeId::of::<hierarchy::Children>(),
+        "{} is maintained by the hierarchy; use EntityManager::set_parent or remove_parent",
+        std::any::type_name::<T>()
+    );
+}
+
+impl Default for EntityManager {
+    fn default() -> Self {
+        Self::new(component_storage::DEFAULT_CAPACITY)
     }
 }
 
+impl ComponentBundle for () {
+    fn add_to_entity(self, _entity: Entity, _storage: &mut ComponentStorage) {}
+}
+
 macro_rules! impl_component_bundle_for_tuple {
-    ($($T:ident),+) => {
-        impl<$($T: 'static),+> ComponentBundle for ($($T,)+) {
-            fn add_to_entity(self, entity_id: usize, storage: &mut ComponentStorage) {
+    ($(($T:ident, $column:ident)),+) => {
+        impl<$($T: Send + Sync + 'static),+> ComponentBundle for ($($T,)+) {
+            fn add_to_entity(self, entity: Entity, storage: &mut ComponentStorage) {
                 #[allow(non_snake_case)]
                 let ($($T,)+) = self;
-                $(storage.add_component(entity_id, $T);)+
+                $(storage.add_component(entity, $T);)+
+            }
+
+            #[allow(non_snake_case)]
+            fn add_to_entities(batch: Vec<(Entity, Self)>, storage: &mut ComponentStorage) {
+                let mut entities = Vec::with_capacity(batch.len());
+                $(let mut $column = Vec::with_capacity(batch.len());)+
+                for (entity, ($($T,)+)) in batch {
+                    entities.push(entity);
+                    $($column.push($T);)+
+                }
+                $(storage.extend_components(&entities, $column);)+
             }
         }
     };
 }
 
-impl_component_bundle_for_tuple!(T1);
-impl_component_bundle_for_tuple!(T1, T2);
-impl_component_bundle_for_tuple!(T1, T2, T3);
-impl_component_bundle_for_tuple!(T1, T2, T3, T4);
+macro_rules! impl_component_bundle_for_tuples {
+    ($T:tt) => {
+        impl_component_bundle_for_tuple!($T);
+    };
+    ($T:tt, $($rest:tt),+) => {
+        impl_component_bundle_for_tuple!($T, $($rest),+);
+        impl_component_bundle_for_tuples!($($rest),+);
+    };
+}
+
+impl_component_bundle_for_tuples!(
+    (T1, c1),
+    (T2, c2),
+    (T3, c3),
+    (T4, c4),
+    (T5, c5),
+    (T6, c6),
+    (T7, c7),
+    (T8, c8),
+    (T9, c9),
+    (T10, c10),
+    (T11, c11),
+    (T12, c12),
+    (T13, c13),
+    (T14, c14),
+    (T15, c15),
+    (T16, c16)
+);
 
 #[cfg(test)]
 mod tests {
     use glam::Vec3;
 
-    use super::EntityManager;
+    use super::{
+        Bundle, Component, ComponentHooks, Entity, EntityManager, StorageConfig, StorageKind,
+        commands::{CommandQueue, Commands},
+        events::Events,
+        hierarchy::ReparentMode,
+    };
     use crate::components::{
         color::{Color, RGBA},
         shape::Shape,
+        transform::Transform,
     };
 
     #[test]
@@ -200,6 +737,370 @@ mod tests {
         );
         let id = entity_manager.create_entity((shape.clone(),));
         assert!(entity_manager.entity_exists(id));
-        assert!(!entity_manager.entity_exists(999));
+        assert!(!entity_manager.entity_exists(Entity::new(999, 0)));
+
+        entity_manager.remove_entity(id);
+        let bumped = Entity::new(id.index(), id.generation() + 1);
+        assert!(!entity_manager.entity_exists(bumped));
+        assert_eq!(entity_manager.entities().count(), 0);
+        assert_eq!(entity_manager.reserve_entity(), bumped);
+        assert!(!entity_manager.entity_exists(bumped));
+        entity_manager.flush_reserved();
+        assert!(entity_manager.entity_exists(bumped));
+    }
+
+    #[test]
+    fn test_entity_manager_stale_handle_after_reuse() {
+        let mut entity_manager = EntityManager::new(10);
+        let color = Color::Uniform(RGBA::new(10, 20, 30, 1.0));
+
+        let stale = entity_manager.create_entity((color,));
+        entity_manager.remove_entity(stale);
+
+        let shape = Shape::new_triangle(
+            Vec3::new(0.0, 0.0, 0.0),
+            Vec3::new(1.0, 0.0, 0.0),
+            Vec3::new(0.0, 1.0, 0.0),
+        );
+        let reused = entity_manager.create_entity((shape,));
+
+        assert_eq!(reused.index(), stale.index());
+        assert_ne!(reused.generation(), stale.generation());
+        assert!(!entity_manager.entity_exists(stale));
+        assert!(entity_manager.entity_exists(reused));
+        assert!(entity_manager.get_component::<Shape>(stale).is_none());
+        assert!(!entity_manager.has_component::<Shape>(stale));
+
+        entity_manager.add_component(stale, Color::default());
+        assert!(!entity_manager.has_component::<Color>(reused));
+
+        entity_manager.remove_entity(stale);
+        assert!(entity_manager.entity_exists(reused));
+        assert_eq!(entity_manager.get_entity_count(), 1);
+    }
+
+    #[test]
+    fn test_entity_manager_get_component_mut() {
+        let mut entity_manager = EntityManager::new(10);
+        let id = entity_manager.create_entity((Color::Uniform(RGBA::default()),));
+
+        *entity_manager.get_component_mut::<Color>(id).unwrap() =
+            Color::Uniform(RGBA::new(1, 2, 3, 1.0));
+
+        assert_eq!(
+            entity_manager.get_component::<Color>(id).unwrap(),
+            &Color::Uniform(RGBA::new(1, 2, 3, 1.0))
+        );
+        assert!(entity_manager.get_component_mut::<Shape>(id).is_none());
+    }
+
+    #[test]
+    fn test_entity_manager_add_component_replaces_previous() {
+        let mut entity_manager = EntityManager::new(10);
+        let first = Color::Uniform(RGBA::new(10, 10, 10, 1.0));
+        let second = Color::Uniform(RGBA::new(20, 20, 20, 1.0));
+        let id = entity_manager.create_entity((first.clone(),));
+
+        let previous = entity_manager.add_component(id, second.clone());
+
+        assert_eq!(previous, Some(first));
+        assert_eq!(entity_manager.get_component::<Color>(id).unwrap(), &second);
+    }
+
+    #[test]
+    fn test_entity_manager_remove_component() {
+        let mut entity_manager = EntityManager::new(10);
+        let shape = Shape::new_triangle(
+            Vec3::new(0.0, 0.0, 0.0),
+            Vec3::new(1.0, 0.0, 0.0),
+            Vec3::new(0.0, 1.0, 0.0),
+        );
+        let color = Color::Uniform(RGBA::default());
+        let id = entity_manager.create_entity((shape.clone(), color.clone()));
+
+        assert_eq!(entity_manager.remove_component::<Color>(id), Some(color));
+        assert!(!entity_manager.has_component::<Color>(id));
+        assert_eq!(entity_manager.get_component::<Shape>(id).unwrap(), &shape);
+        assert!(entity_manager.remove_component::<Color>(id).is_none());
+    }
+
+    #[test]
+    fn test_entity_manager_resources() {
+        let mut entity_manager = EntityManager::new(10);
+        entity_manager.insert_resource(0.5_f32);
+
+        *entity_manager.resource_mut::<f32>().unwrap() += 1.0;
+
+        assert!(entity_manager.has_resource::<f32>());
+        assert_eq!(entity_manager.resource::<f32>(), Some(&1.5));
+        assert_eq!(entity_manager.get_entity_count(), 0);
+        assert_eq!(entity_manager.remove_resource::<f32>(), Some(1.5));
+        assert!(entity_manager.resource::<f32>().is_none());
+    }
+
+    #[test]
+    fn test_entity_manager_events() {
+        let mut entity_manager = EntityManager::new(10);
+        entity_manager.send_event(1_u32);
+
+        entity_manager.update_events();
+        entity_manager.send_event(2_u32);
+        let events = entity_manager.resource::<Events<u32>>().unwrap();
+        assert_eq!(events.iter().copied().collect::<Vec<_>>(), vec![1, 2]);
+
+        entity_manager.update_events();
+        entity_manager.update_events();
+        assert!(entity_manager.resource::<Events<u32>>().unwrap().is_empty());
+    }
+
+    fn log(commands: &mut Commands, message: String) {
+        commands.add(move |entity_manager| {
+            entity_manager
+                .resource_mut::<Vec<String>>()
+                .unwrap()
+                .push(message);
+        });
+    }
+
+    #[test]
+    fn test_entity_manager_component_hooks() {
+        let mut entity_manager = EntityManager::new(10);
+        entity_manager.insert_resource(Vec::<String>::new());
+        entity_manager.register_component_hooks::<u32>(
+            ComponentHooks::new()
+                .on_add(|entity_manager, entity, commands| {
+                    let value = entity_manager.get_component::<u32>(entity).unwrap();
+                    log(commands, format!("add {value}"));
+                })
+                .on_insert(|entity_manager, entity, commands| {
+                    let value = entity_manager.get_component::<u32>(entity).unwrap();
+                    log(commands, format!("insert {value}"));
+                })
+                .on_remove(|entity_manager, entity, commands| {
+                    let value = entity_manager.get_component::<u32>(entity).unwrap();
+                    log(commands, format!("remove {value}"));
+                }),
+        );
+
+        let entity = entity_manager.create_entity((1_u32, 'a'));
+        entity_manager.add_component(entity, 2_u32);
+        entity_manager.remove_component::<u32>(entity);
+        entity_manager.remove_component::<u32>(entity);
+        entity_manager.add_component(entity, 3_u32);
+        entity_manager.remove_entity(entity);
+
+        assert_eq!(
+            entity_manager.resource::<Vec<String>>().unwrap(),
+            &[
+                "add 1", "insert 1", "insert 2", "remove 2", "add 3", "insert 3", "remove 3"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_entity_manager_component_hooks_commands() {
+        let mut entity_manager = EntityManager::new(10);
+        entity_manager.register_component_hooks::<char>(
+            ComponentHooks::new()
+                .on_add(|_, entity, commands| commands.add_component(entity, 0_u8))
+                .on_remove(|entity_manager, entity, commands| {
+                    let value = *entity_manager.get_component::<char>(entity).unwrap();
+                    commands.create_entity((value.to_string(),));
+                }),
+        );
+        let parent = entity_manager.create_entity((1_u32,));
+        let child = entity_manager.create_entity(('c',));
+        let mut queue = CommandQueue::default();
+        Commands::new(&mut queue, &entity_manager).create_entity(('d',));
+        queue.apply(&mut entity_manager);
+        entity_manager.set_parent(child, parent, ReparentMode::KeepLocal);
+
+        assert_eq!(entity_manager.get_component::<u8>(child), Some(&0));
+        assert_eq!(entity_manager.query::<&u8>().count(), 2);
+
+        entity_manager.remove_entity(parent);
+
+        let mut names: Vec<String> = entity_manager.query::<&String>().cloned().collect();
+        names.sort_unstable();
+        assert_eq!(names, vec!["c".to_string()]);
+        assert_eq!(entity_manager.get_entity_count(), 2);
+        let mut ids: Vec<usize> = entity_manager.entities().map(Entity::index).collect();
+        ids.sort_unstable();
+        ids.dedup();
+        assert_eq!(ids.len(), 2);
+    }
+
+    #[derive(Bundle)]
+    struct Placement {
+        transform: Transform,
+        name: String,
+    }
+
+    #[derive(Bundle)]
+    struct Sprite<T: Send + Sync + 'static> {
+        shape: Shape,
+        tag: T,
+        #[bundle]
+        placement: Placement,
+        #[bundle]
+        extra: (u8, u16),
+    }
+
+    #[derive(Bundle)]
+    struct Pair(u32, #[bundle] (char,));
+
+    #[derive(Component, Debug, PartialEq)]
+    #[component(sparse_capacity = 4096, dense_capacity = 8)]
+    struct Velocity(f32);
+
+    #[derive(Component)]
+    struct Marker;
+
+    #[test]
+    fn test_entity_manager_derived_bundles() {
+        let mut entity_manager = EntityManager::new(10);
+
+        let sprite = entity_manager.create_entity(Sprite {
+            shape: Shape::new_circle(Vec3::ZERO, 1.0, 4),
+            tag: 7_i64,
+            placement: Placement {
+                transform: Transform::identity(),
+                name: "sprite".to_string(),
+            },
+            extra: (1, 2),
+        });
+        let pair = entity_manager.create_entity(Pair(3, ('p',)));
+
+        assert!(entity_manager.has_component::<Shape>(sprite));
+        assert_eq!(entity_manager.get_component::<i64>(sprite), Some(&7));
+        assert!(entity_manager.has_component::<Transform>(sprite));
+        assert_eq!(
+            entity_manager
+                .get_component::<String>(sprite)
+                .map(String::as_str),
+            Some("sprite")
+        );
+        assert_eq!(entity_manager.get_component::<u8>(sprite), Some(&1));
+        assert_eq!(entity_manager.get_component::<u16>(sprite), Some(&2));
+        assert_eq!(entity_manager.get_component::<u32>(pair), Some(&3));
+        assert_eq!(entity_manager.get_component::<char>(pair), Some(&'p'));
+    }
+
+    #[test]
+    fn test_entity_manager_sixteen_component_tuple() {
+        let mut entity_manager = EntityManager::new(10);
+
+        let entity = entity_manager.create_entity((
+            1_u8, 2_u16, 3_u32, 4_u64, 5_u128, 6_usize, 7_i8, 8_i16, 9_i32, 10_i64, 11_i128,
+            12_isize, 13_f32, 14_f64, 'c', true,
+        ));
+
+        assert_eq!(entity_manager.get_component::<u8>(entity), Some(&1));
+        assert_eq!(entity_manager.get_component::<isize>(entity), Some(&12));
+        assert_eq!(entity_manager.get_component::<bool>(entity), Some(&true));
+    }
+
+    #[test]
+    fn test_entity_manager_register_component() {
+        let mut entity_manager = EntityManager::new(2);
+        entity_manager.register_component::<Velocity>();
+        entity_manager.register_component::<Marker>();
+        let entities: Vec<Entity> = (0..5)
+            .map(|index| entity_manager.create_entity((Velocity(index as f32),)))
+            .collect();
+        entity_manager.add_component(entities[4], Marker);
+
+        assert_eq!(
+            <Velocity as Component>::STORAGE,
+            StorageConfig {
+                sparse_capacity: Some(4096),
+                dense_capacity: 8,
+                ..StorageConfig::DEFAULT
+            }
+        );
+        assert_eq!(<Marker as Component>::STORAGE, StorageConfig::default());
+        assert_eq!(<Transform as Component>::STORAGE.kind, StorageKind::Table);
+        assert_eq!(
+            entity_manager.get_component::<Velocity>(entities[4]),
+            Some(&Velocity(4.0))
+        );
+        assert!(entity_manager.has_component::<Marker>(entities[4]));
+    }
+
+    #[test]
+    fn test_entity_manager_spawn_batch() {
+        let mut entity_manager = EntityManager::new(10);
+        entity_manager.register_component::<Transform>();
+        entity_manager.register_component_hooks::<u32>(
+            ComponentHooks::new().on_add(|_, entity, commands| commands.add_component(entity, 'a')),
+        );
+        let first = entity_manager.create_entity((0_u32,));
+        let second = entity_manager.create_entity((0_u32,));
+        entity_manager.remove_entity(first);
+        entity_manager.remove_entity(second);
+
+        let entities = entity_manager.spawn_batch(
+            (0..10_000_u32).map(|index| (index, Transform::from_translation(Vec3::X))),
+        );
+
+        assert_eq!(entities.len(), 10_000);
+        assert_eq!(entities[0].index(), second.index());
+        assert_eq!(entities[1].index(), first.index());
+        assert!(!entity_manager.entity_exists(first));
+        assert_eq!(entity_manager.get_entity_count(), 10_000);
+        assert_eq!(
+            entity_manager.get_component::<u32>(entities[9_999]),
+            Some(&9_999)
+        );
+        assert_eq!(
+            entity_manager.query::<(&u32, &Transform, &char)>().count(),
+            10_000
+        );
+        assert_eq!(entity_manager.create_entity(()).index(), 10_000);
+    }
+
+    #[test]
+    fn test_entity_manager_spawn_batch_derived_bundles() {
+        let mut entity_manager = EntityManager::new(10);
+
+        let entities = entity_manager.spawn_batch((0..3_u8).map(|index| Sprite {
+            shape: Shape::new_circle(Vec3::ZERO, 1.0, 4),
+            tag: index,
+            placement: Placement {
+                transform: Transform::identity(),
+                name: format!("sprite {index}"),
+            },
+            extra: (index, u16::from(index)),
+        }));
+
+        assert_eq!(
+            entity_manager
+                .get_component::<String>(entities[2])
+                .map(String::as_str),
+            Some("sprite 2")
+        );
+        assert_eq!(entity_manager.get_component::<u16>(entities[1]), Some(&1));
+        assert_eq!(
+            entity_manager.query::<(&Shape, &Transform, &u8)>().count(),
+            3
+        );
+    }
+
+    #[test]
+    fn test_entity_manager_remove_batch() {
+        let mut entity_manager = EntityManager::new(10);
+        let entities = entity_manager.spawn_batch((0..6_u32).map(|index| (index,)));
+        entity_manager.set_parent(entities[1], entities[0], ReparentMode::KeepLocal);
+        entity_manager.set_parent(entities[2], entities[1], ReparentMode::KeepLocal);
+        let stale = entities[5];
+        entity_manager.remove_entity(stale);
+
+        entity_manager.remove_batch(&[entities[1], entities[3], entities[2], entities[1], stale]);
+
+        let mut remaining: Vec<u32> = entity_manager.query::<&u32>().copied().collect();
+        remaining.sort_unstable();
+        assert_eq!(remaining, vec![0, 4]);
+        assert_eq!(entity_manager.get_entity_count(), 2);
+        assert!(entity_manager.children(entities[0]).is_empty());
     }
 }
@@ -0,0 +1,206 @@
+use crate::entity::{ComponentBundle, Entity, EntityManager, hierarchy::ReparentMode};
+
+type Command = Box<dyn FnOnce(&mut EntityManager) + Send>;
+
+/// Structural changes recorded by [`Commands`], applied in recording order by
+/// [`CommandQueue::apply`].
+#[derive(Default)]
+pub struct CommandQueue {
+    commands: Vec<Command>,
+}
+
+/// Records entity creation and removal and component changes without
+/// touching the storages, so it can be used while iterating a query.
+///
+/// As a system parameter, the recorded commands are applied at the end of the
+/// system's stage, after every system of the stage has run.
+pub struct Commands<'w> {
+    queue: &'w mut CommandQueue,
+    entity_manager: &'w EntityManager,
+}
+
+impl CommandQueue {
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.commands.is_empty()
+    }
+
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.commands.len()
+    }
+
+    /// Applies and clears every recorded command.
+    pub fn apply(&mut self, entity_manager: &mut EntityManager) {
+        entity_manager.flush_reserved();
+        for command in self.commands.drain(..) {
+            command(entity_manager);
+        }
+    }
+
+    fn push(&mut self, command: impl FnOnce(&mut EntityManager) + Send + 'static) {
+        self.commands.push(Box::new(command));
+    }
+}
+
+impl<'w> Commands<'w> {
+    pub fn new(queue: &'w mut CommandQueue, entity_manager: &'w EntityManager) -> Self {
+        Self {
+            queue,
+            entity_manager,
+        }
+    }
+
+    /// Reserves an entity ID right away and records adding `component_bundle` to it.
+    pub fn create_entity<T: ComponentBundle + Send + 'static>(
+        &mut self,
+        component_bundle: T,
+    ) -> Entity {
+        let entity = self.entity_manager.reserve_entity();
+        self.queue.push(move |entity_manager| {
+            if entity_manager.entity_exists(entity) {
+                entity_manager.insert_bundle(entity, component_bundle);
+            }
+        });
+        entity
+    }
+
+    pub fn remove_entity(&mut self, entity: Entity) {
+        self.queue
+            .push(move |entity_manager| entity_manager.remove_entity(entity));
+    }
+
+    pub fn add_component<T: Send + Sync + 'static>(&mut self, entity: Entity, component: T) {
+        self.queue.push(move |entity_manager| {
+            entity_manager.add_component(entity, component);
+        });
+    }
+
+    pub fn remove_component<T: Send + Sync + 'static>(&mut self, entity: Entity) {
+        self.queue.push(move |entity_manager| {
+            entity_manager.remove_component::<T>(entity);
+        });
+    }
+
+    /// See [`EntityManager::set_parent`].
+    pub fn set_parent(&mut self, child: Entity, parent: Entity, mode: ReparentMode) {
+        self.queue.push(move |entity_manager| {
+            entity_manager.set_parent(child, parent, mode);
+        });
+    }
+
+    /// Records an arbitrary change to the entity manager.
+    pub fn add(&mut self, command: impl FnOnce(&mut EntityManager) + Send + 'static) {
+        self.queue.push(command);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CommandQueue, Commands};
+    use crate::entity::{Entity, EntityManager, hierarchy::ReparentMode, query::Query};
+    use crate::schedule::{Schedule, Stage, SystemConfig};
+
+    #[test]
+    fn test_commands_deferred_until_apply() {
+        let mut entity_manager = EntityManager::new(10);
+        let existing = entity_manager.create_entity((1_u32,));
+        let mut queue = CommandQueue::default();
+
+        let mut commands = Commands::new(&mut queue, &entity_manager);
+        let created = commands.create_entity((2_u32, 'a'));
+        commands.remove_entity(existing);
+        assert_eq!(queue.len(), 2);
+        assert_eq!(entity_manager.get_entity_count(), 1);
+        assert_eq!(entity_manager.get_component::<u32>(existing), Some(&1));
+
+        queue.apply(&mut entity_manager);
+
+        assert!(queue.is_empty());
+        assert!(!entity_manager.entity_exists(existing));
+        assert_eq!(entity_manager.get_component::<u32>(created), Some(&2));
+        assert_eq!(entity_manager.get_component::<char>(created), Some(&'a'));
+        assert_eq!(entity_manager.get_entity_count(), 1);
+    }
+
+    #[test]
+    fn test_commands_reserved_ids_are_unique() {
+        let mut entity_manager = EntityManager::new(10);
+        let removed = entity_manager.create_entity((0_u32,));
+        entity_manager.create_entity((1_u32,));
+        entity_manager.remove_entity(removed);
+        let mut queue = CommandQueue::default();
+
+        let mut commands = Commands::new(&mut queue, &entity_manager);
+        let first = commands.create_entity((2_u32,));
+        let second = commands.create_entity((3_u32,));
+        queue.apply(&mut entity_manager);
+        let third = entity_manager.create_entity((4_u32,));
+
+        assert_eq!(first, Entity::new(0, 1));
+        assert_eq!(second, Entity::new(2, 0));
+        assert_eq!(third, Entity::new(3, 0));
+        let mut values: Vec<u32> = entity_manager.query::<&u32>().copied().collect();
+        values.sort_unstable();
+        assert_eq!(values, vec![1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn test_commands_reference_reserved_entity() {
+        let mut entity_manager = EntityManager::new(10);
+        let mut queue = CommandQueue::default();
+
+        let mut commands = Commands::new(&mut queue, &entity_manager);
+        let entity = commands.create_entity((1_u32,));
+        commands.add_component(entity, 'b');
+        commands.remove_component::<u32>(entity);
+        queue.apply(&mut entity_manager);
+
+        assert!(!entity_manager.has_component::<u32>(entity));
+        assert_eq!(entity_manager.get_component::<char>(entity), Some(&'b'));
+    }
+
+    #[test]
+    fn test_commands_set_parent_on_reserved_entities() {
+        let mut entity_manager = EntityManager::new(10);
+        let mut queue = CommandQueue::default();
+
+        let mut commands = Commands::new(&mut queue, &entity_manager);
+        let parent = commands.create_entity((1_u32,));
+        let child = commands.create_entity((2_u32,));
+        commands.set_parent(child, parent, ReparentMode::KeepLocal);
+        queue.apply(&mut entity_manager);
+
+        assert_eq!(entity_manager.children(parent), &[child]);
+    }
+
+    #[test]
+    fn test_commands_while_iterating() {
+        // Spawns and despawns recorded while a query walks the dense arrays,
+        // applied once the stage has finished.
+        fn split(mut query: Query<(Entity, &mut u32)>, mut commands: Commands) {
+            for (entity, value) in query.iter_mut() {
+                if *value % 2 == 0 {
+                    commands.remove_entity(entity);
+                } else {
+                    *value += 1;
+                    commands.create_entity((*value * 10,));
+                }
+            }
+        }
+
+        let mut entity_manager = EntityManager::new(10);
+        for value in 0..6_u32 {
+            entity_manager.create_entity((value,));
+        }
+        let mut schedule = Schedule::new();
+        schedule.add_system(Stage::Update, SystemConfig::new("split", split));
+
+        schedule.run(&mut entity_manager).unwrap();
+
+        let mut values: Vec<u32> = entity_manager.query::<&u32>().copied().collect();
+        values.sort_unstable();
+        assert_eq!(values, vec![2, 4, 6, 20, 40, 60]);
+        assert_eq!(entity_manager.get_entity_count(), 6);
+    }
+}
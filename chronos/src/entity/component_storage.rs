@@ -1,28 +1,365 @@
 use std::{
-    any::{Any, TypeId},
+    any::{Any, TypeId, type_name},
+    cell::UnsafeCell,
     collections::HashMap,
+    marker::PhantomData,
+    sync::atomic::{AtomicU32, Ordering},
 };
 
-trait Component {
-    fn remove_component(&mut self, entity_id: usize);
+mod dynamic;
+mod group;
+mod names;
+mod snapshot;
+mod sparse_array;
+mod stats;
+mod table;
+
+use crate::entity::commands::Commands;
+use crate::entity::hierarchy::{Children, Parent};
+use crate::entity::name::Name;
+use crate::entity::{Entity, EntityManager};
+use dynamic::DynamicStorage;
+pub use dynamic::{
+    DynamicComponentId, DynamicField, DynamicFieldType, DynamicLayout, DynamicMut, DynamicRef,
+    FieldKind, RawDynamicSet,
+};
+use group::Group;
+pub(crate) use snapshot::SnapshotClone;
+pub use snapshot::StorageSnapshot;
+use snapshot::{Cloner, SharedCopy};
+use sparse_array::SparseArray;
+pub use stats::ComponentStats;
+use table::{TableRow, Tables, table_row};
+
+trait Component: Send + Sync {
+    fn type_name(&self) -> &'static str;
+    fn len(&self) -> usize;
+    fn contains(&self, entity: Entity) -> bool;
+    fn remove_component(&mut self, entity: Entity);
+    fn take_component(&mut self, entity: Entity) -> Option<ErasedComponent>;
+    fn get_any(&self, entity: Entity) -> Option<&dyn Any>;
+    fn entities(&self) -> &[Entity];
+    /// Dense index of the entity's component.
+    fn row(&self, entity: Entity) -> Option<usize>;
+    fn swap_rows(&mut self, a: usize, b: usize);
+    /// Reorders the rows from `start` on so that row `start + i` holds what
+    /// row `start + order[i]` held.
+    fn permute(&mut self, start: usize, order: &[usize]);
+    fn stats(&self) -> ComponentStats;
+    fn shrink_to_fit(&mut self);
+    /// Returns the component mutably and marks it as changed at `tick`.
+    fn get_any_mut(&mut self, entity: Entity, tick: Tick) -> Option<&mut dyn Any>;
+    fn clear(&mut self);
     fn as_any_mut(&mut self) -> &mut dyn Any;
     fn as_any(&self) -> &dyn Any;
 }
 
+/// Entity capacity of default-constructed storages.
+pub(crate) const DEFAULT_CAPACITY: usize = 1000;
+
+/// Point in time of the world. It advances once per frame through
+/// [`EntityManager::advance_tick`](crate::entity::EntityManager::advance_tick)
+/// and once per system run, so every system sees the changes made since its
+/// previous run.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct Tick(u32);
+
+/// Where the components of one type are kept.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum StorageKind {
+    /// A sparse set of its own: cheap to add and remove, the default.
+    #[default]
+    SparseSet,
+    /// Tables shared with the entity's other table-stored components, one per
+    /// combination of types. Iterating several of them together walks
+    /// contiguous arrays, at the cost of moving the entity's row whenever one
+    /// of its table-stored components is added or removed.
+    Table,
+}
+
+/// How the storage of one component type is kept and sized, see
+/// [`Component`](crate::entity::Component).
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct StorageConfig {
+    pub kind: StorageKind,
+    /// Entity indices to reserve sparse page table room for, or `None` for
+    /// the capacity the [`EntityManager`] was created with. Pages themselves
+    /// are only allocated when used.
+    pub sparse_capacity: Option<usize>,
+    /// Number of components to reserve room for when the storage is created.
+    pub dense_capacity: usize,
+}
+
+/// Callback run when a component is added to or removed from an entity.
+/// Structural changes recorded in the [`Commands`] are applied right after the
+/// change that ran the hook.
+pub type ComponentHook = fn(&EntityManager, Entity, &mut Commands);
+
+/// Lifecycle hooks of one component type, registered with
+/// [`EntityManager::register_component_hooks`].
+#[derive(Clone, Copy, Default)]
+pub struct ComponentHooks {
+    add: Option<ComponentHook>,
+    insert: Option<ComponentHook>,
+    remove: Option<ComponentHook>,
+}
+
+#[derive(Clone)]
 struct SparseSet<T> {
-    sparse: Vec<Option<usize>>,
+    sparse: SparseArray,
     dense: Vec<T>,
-    entities: Vec<usize>,
+    entities: Vec<Entity>,
+    added: Vec<Tick>,
+    changed: Vec<Tick>,
+}
+
+/// Component taken out of its storage without knowing its type, which can put
+/// itself into another storage.
+pub struct ErasedComponent {
+    value: Box<dyn Any + Send + Sync>,
+    insert: fn(&mut ComponentStorage, Entity, Box<dyn Any + Send + Sync>),
+}
+
+/// Finds the column and row holding an entity's component: the sparse set of
+/// its type, or the table the entity lives in.
+enum Rows<'w> {
+    Sparse {
+        sparse: &'w SparseArray,
+        entities: &'w [Entity],
+    },
+    /// Entity lists indexed by table, `None` for tables without the type.
+    Table {
+        locations: &'w SparseArray<TableRow>,
+        entities: Vec<Option<&'w [Entity]>>,
+    },
+}
+
+/// Value and tick arrays of one sparse set or table column.
+struct RawColumn<T> {
+    values: *mut T,
+    added: *mut Tick,
+    changed: *mut Tick,
+}
+
+/// Read-only view of the components of one type, handed out to queries.
+pub struct ComponentView<'w, T> {
+    rows: Rows<'w>,
+    columns: Vec<RawColumn<T>>,
+    marker: PhantomData<&'w [T]>,
+}
+
+/// Mutable view of the components of one type. The arrays are kept as raw
+/// pointers so a query can hand out `&mut T` for different entities at the
+/// same time. Every fetched entry is marked as changed at `this_run`.
+pub struct ComponentViewMut<'w, T> {
+    rows: Rows<'w>,
+    columns: Vec<RawColumn<T>>,
+    this_run: Tick,
+    marker: PhantomData<&'w mut [T]>,
+}
+
+/// View of which entities own a component and when their entries were added or
+/// changed, without touching the component values. Used by query filters.
+pub struct ComponentTicksView<'w> {
+    rows: Rows<'w>,
+    columns: Vec<(*const Tick, *const Tick)>,
+    marker: PhantomData<&'w [Tick]>,
 }
 
 pub struct ComponentStorage {
-    storages: HashMap<TypeId, Box<dyn Component>>,
+    storages: HashMap<TypeId, Box<UnsafeCell<dyn Component>>>,
+    tables: Tables,
     initial_capacity: usize,
+    change_tick: AtomicU32,
+    last_change_tick: Tick,
+    hooks: HashMap<TypeId, ComponentHooks>,
+    /// `on_add` and `on_insert` hooks of inserted components, waiting for the
+    /// entity manager to run them.
+    triggered_hooks: Vec<(ComponentHook, Entity)>,
+    cloners: HashMap<TypeId, Cloner>,
+    /// Last snapshotted copy of each sparse set, shared with the snapshots
+    /// until the sparse set is modified.
+    copies: HashMap<TypeId, SharedCopy<dyn Component>>,
+    tables_copy: SharedCopy<Tables>,
+    dynamic: DynamicStorage,
+    dynamic_copy: SharedCopy<DynamicStorage>,
+    groups: Vec<Group>,
+    /// Index in `groups` of the group owning each grouped type.
+    group_of: HashMap<TypeId, usize>,
+    /// Entities by their `Name`.
+    names: HashMap<String, Vec<Entity>>,
 }
 
-impl<T: 'static> Component for SparseSet<T> {
-    fn remove_component(&mut self, entity_id: usize) {
-        self.remove(entity_id);
+// SAFETY: every stored component type is `Send + Sync`, and the cells are only
+// accessed mutably through `&self` by queries whose access has been checked
+// not to conflict.
+unsafe impl Sync for ComponentStorage {}
+
+impl Tick {
+    #[must_use]
+    pub fn new(tick: u32) -> Self {
+        Self(tick)
+    }
+
+    #[must_use]
+    pub fn get(self) -> u32 {
+        self.0
+    }
+
+    /// Returns true if this tick happened after `last_run`, as seen from
+    /// `this_run`. Works across `u32` wrap-around as long as both ticks are
+    /// less than `u32::MAX / 2` ticks old.
+    #[must_use]
+    pub fn is_newer_than(self, last_run: Tick, this_run: Tick) -> bool {
+        let ticks_since_insert = this_run.0.wrapping_sub(self.0);
+        let ticks_since_run = this_run.0.wrapping_sub(last_run.0);
+        ticks_since_run > ticks_since_insert
+    }
+}
+
+impl StorageConfig {
+    pub const DEFAULT: Self = Self {
+        kind: StorageKind::SparseSet,
+        sparse_capacity: None,
+        dense_capacity: 0,
+    };
+}
+
+impl Default for StorageConfig {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+impl ComponentHooks {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Runs after the component is inserted into an entity that did not have
+    /// one of its type, before `on_insert`.
+    #[must_use]
+    pub fn on_add(mut self, hook: ComponentHook) -> Self {
+        self.add = Some(hook);
+        self
+    }
+
+    /// Runs after every insertion, including one replacing a previous value.
+    #[must_use]
+    pub fn on_insert(mut self, hook: ComponentHook) -> Self {
+        self.insert = Some(hook);
+        self
+    }
+
+    /// Runs before the component is removed, on its own or with its entity,
+    /// so the hook can still read it.
+    #[must_use]
+    pub fn on_remove(mut self, hook: ComponentHook) -> Self {
+        self.remove = Some(hook);
+        self
+    }
+
+    /// Queues the `on_add` hook if the component is new to the entity, and
+    /// the `on_insert` hook.
+    fn trigger(&self, triggered: &mut Vec<(ComponentHook, Entity)>, entity: Entity, added: bool) {
+        if added {
+            triggered.extend(self.add.map(|hook| (hook, entity)));
+        }
+        triggered.extend(self.insert.map(|hook| (hook, entity)));
+    }
+}
+
+impl<T: Send + Sync + 'static> Component for SparseSet<T> {
+    fn type_name(&self) -> &'static str {
+        type_name::<T>()
+    }
+
+    fn len(&self) -> usize {
+        self.dense.len()
+    }
+
+    fn contains(&self, entity: Entity) -> bool {
+        self.get_component_dense_index(entity).is_some()
+    }
+
+    fn remove_component(&mut self, entity: Entity) {
+        self.remove(entity);
+    }
+
+    fn take_component(&mut self, entity: Entity) -> Option<ErasedComponent> {
+        self.remove(entity).map(ErasedComponent::new)
+    }
+
+    fn get_any(&self, entity: Entity) -> Option<&dyn Any> {
+        self.get_component(entity)
+            .map(|component| component as &dyn Any)
+    }
+
+    fn get_any_mut(&mut self, entity: Entity, tick: Tick) -> Option<&mut dyn Any> {
+        self.get_component_mut(entity, tick)
+            .map(|component| component as &mut dyn Any)
+    }
+
+    fn entities(&self) -> &[Entity] {
+        &self.entities
+    }
+
+    fn row(&self, entity: Entity) -> Option<usize> {
+        self.get_component_dense_index(entity)
+    }
+
+    fn swap_rows(&mut self, a: usize, b: usize) {
+        if a == b {
+            return;
+        }
+        self.dense.swap(a, b);
+        self.entities.swap(a, b);
+        self.added.swap(a, b);
+        self.changed.swap(a, b);
+        self.sparse.insert(self.entities[a].index(), a);
+        self.sparse.insert(self.entities[b].index(), b);
+    }
+
+    fn permute(&mut self, start: usize, order: &[usize]) {
+        let end = start + order.len();
+        apply_permutation(&mut self.dense[start..end], order);
+        apply_permutation(&mut self.entities[start..end], order);
+        apply_permutation(&mut self.added[start..end], order);
+        apply_permutation(&mut self.changed[start..end], order);
+        for (row, entity) in self.entities.iter().enumerate().take(end).skip(start) {
+            self.sparse.insert(entity.index(), row);
+        }
+    }
+
+    fn stats(&self) -> ComponentStats {
+        let bytes_used = self.dense.capacity() * size_of::<T>()
+            + self.entities.capacity() * size_of::<Entity>()
+            + (self.added.capacity() + self.changed.capacity()) * size_of::<Tick>()
+            + self.sparse.heap_bytes();
+        ComponentStats::new(
+            type_name::<T>(),
+            self.dense.len(),
+            self.sparse.capacity(),
+            bytes_used,
+        )
+    }
+
+    fn shrink_to_fit(&mut self) {
+        self.sparse.shrink_to_fit();
+        self.dense.shrink_to_fit();
+        self.entities.shrink_to_fit();
+        self.added.shrink_to_fit();
+        self.changed.shrink_to_fit();
+    }
+
+    fn clear(&mut self) {
+        for entity in std::mem::take(&mut self.entities) {
+            self.sparse.remove(entity.index());
+        }
+        self.dense.clear();
+        self.added.clear();
+        self.changed.clear();
     }
 
     fn as_any_mut(&mut self) -> &mut dyn Any {
@@ -35,258 +372,1179 @@ impl<T: 'static> Component for SparseSet<T> {
 }
 
 impl ComponentStorage {
+    #[must_use]
     pub fn new(initial_capacity: usize) -> Self {
         Self {
             storages: HashMap::new(),
+            tables: Tables::default(),
             initial_capacity,
+            change_tick: AtomicU32::new(1),
+            last_change_tick: Tick(0),
+            hooks: HashMap::new(),
+            triggered_hooks: Vec::new(),
+            cloners: HashMap::new(),
+            copies: HashMap::new(),
+            tables_copy: SharedCopy::default(),
+            dynamic: DynamicStorage::default(),
+            dynamic_copy: SharedCopy::default(),
+            groups: Vec::new(),
+            group_of: HashMap::new(),
+            names: HashMap::new(),
         }
     }
 
-    pub fn register_component_type<T: 'static>(&mut self) {
-        let type_id = TypeId::of::<T>();
-        self.storages.insert(
-            type_id,
-            Box::new(SparseSet::<T>::new(self.initial_capacity)),
-        );
+    pub fn change_tick(&self) -> Tick {
+        Tick(self.change_tick.load(Ordering::Relaxed))
     }
 
-    pub fn add_component<T: 'static>(&mut self, entity_id: usize, component: T) {
-        let type_id = TypeId::of::<T>();
+    pub fn last_change_tick(&self) -> Tick {
+        self.last_change_tick
+    }
 
-        if !self.storages.contains_key(&type_id) {
+    pub fn advance_tick(&mut self) -> Tick {
+        self.last_change_tick = self.change_tick();
+        self.increment_change_tick()
+    }
+
+    /// Moves the change tick forward and returns the new value. Safe to call
+    /// from systems running in parallel.
+    pub fn increment_change_tick(&self) -> Tick {
+        Tick(
+            self.change_tick
+                .fetch_add(1, Ordering::Relaxed)
+                .wrapping_add(1),
+        )
+    }
+
+    pub fn register_component_type<T: Send + Sync + 'static>(&mut self) {
+        self.register_component_type_with::<T>(StorageConfig::DEFAULT);
+    }
+
+    /// Creates the sparse set of `T` unless `T` has a storage already.
+    pub fn register_if_missing<T: Send + Sync + 'static>(&mut self) {
+        let type_id = TypeId::of::<T>();
+        if !self.storages.contains_key(&type_id) && !self.tables.stores(type_id) {
             self.register_component_type::<T>();
         }
+    }
 
-        if self.needs_sparse_set_resize::<T>(entity_id) {
-            self.resize_sparse_set::<T>();
+    /// Creates the storage of `T` sized by `config`, or grows the existing one
+    /// to it.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `config` switches the storage kind of `T` while components of
+    /// `T` are stored, or moves `T` between tables and a sparse set while it
+    /// belongs to a group.
+    pub fn register_component_type_with<T: Send + Sync + 'static>(
+        &mut self,
+        config: StorageConfig,
+    ) {
+        let type_id = TypeId::of::<T>();
+        let sparse_capacity = config.sparse_capacity.unwrap_or(self.initial_capacity);
+        if config.kind == StorageKind::Table {
+            assert!(
+                !self.group_of.contains_key(&type_id) || self.tables.stores(type_id),
+                "Cannot move grouped {} components to tables",
+                type_name::<T>()
+            );
+            if let Some(mut storage) = self.storages.remove(&type_id) {
+                assert!(
+                    storage.get_mut().len() == 0,
+                    "Cannot move stored {} components to tables",
+                    type_name::<T>()
+                );
+            }
+            self.tables.register::<T>();
+            self.tables.reserve(sparse_capacity);
+            self.mark_modified(type_id);
+            return;
+        }
+        if self.tables.stores(type_id) {
+            assert!(
+                !self.group_of.contains_key(&type_id),
+                "Cannot move grouped {} components to a sparse set",
+                type_name::<T>()
+            );
+            assert!(
+                self.tables.len_of(type_id) == 0,
+                "Cannot move stored {} components to a sparse set",
+                type_name::<T>()
+            );
+            self.tables.unregister(type_id);
+            self.mark_modified(type_id);
         }
-
         if let Some(sparse_set) = self.get_mut_sparse_set::<T>() {
-            sparse_set.add(entity_id, component);
+            sparse_set.reserve(sparse_capacity, config.dense_capacity);
+            return;
+        }
+        let mut sparse_set = SparseSet::<T>::new(sparse_capacity);
+        sparse_set.reserve(sparse_capacity, config.dense_capacity);
+        self.storages
+            .insert(TypeId::of::<T>(), Box::new(UnsafeCell::new(sparse_set)));
+    }
+
+    pub fn add_component<T: Send + Sync + 'static>(
+        &mut self,
+        entity: Entity,
+        component: T,
+    ) -> Option<T> {
+        let type_id = TypeId::of::<T>();
+        let change_tick = self.change_tick();
+        self.mark_modified(type_id);
+        self.unindex_names(type_id, &[entity]);
+
+        let previous = if self.tables.stores(type_id) {
+            let previous = self.tables.insert(entity, component, change_tick);
+            if previous.is_none() {
+                self.enter_group(type_id, entity);
+            }
+            previous
         } else {
+            if !self.storages.contains_key(&type_id) {
+                self.register_component_type::<T>();
+            }
+            let Some(sparse_set) = self.get_mut_sparse_set::<T>() else {
+                debug_assert!(
+                    false,
+                    "Internal error: failed to get SparseSet for component type"
+                );
+                return None;
+            };
+            let previous = sparse_set.add(entity, component, change_tick);
+            if previous.is_none() {
+                self.enter_group(type_id, entity);
+            }
+            previous
+        };
+
+        self.index_names(type_id, &[entity]);
+        if let Some(hooks) = self.hooks.get(&type_id) {
+            hooks.trigger(&mut self.triggered_hooks, entity, previous.is_none());
+        }
+        previous
+    }
+
+    /// Adds `components[i]` to `entities[i]`, like `add_component` but
+    /// looking the storage of `T` up once and sizing it for all of them.
+    pub fn extend_components<T: Send + Sync + 'static>(
+        &mut self,
+        entities: &[Entity],
+        components: Vec<T>,
+    ) {
+        debug_assert_eq!(entities.len(), components.len());
+        let type_id = TypeId::of::<T>();
+        let change_tick = self.change_tick();
+        self.mark_modified(type_id);
+        self.unindex_names(type_id, entities);
+        let hooks = self.hooks.get(&type_id).copied();
+
+        if self.tables.stores(type_id) {
+            for (&entity, component) in entities.iter().zip(components) {
+                let previous = self.tables.insert(entity, component, change_tick);
+                if previous.is_none() {
+                    self.enter_group(type_id, entity);
+                }
+                if let Some(hooks) = hooks {
+                    hooks.trigger(&mut self.triggered_hooks, entity, previous.is_none());
+                }
+            }
+            self.index_names(type_id, entities);
+            return;
+        }
+
+        if !self.storages.contains_key(&type_id) {
+            self.register_component_type::<T>();
+        }
+        let Some(sparse_set) = self.storages.get_mut(&type_id).and_then(|storage| {
+            storage
+                .get_mut()
+                .as_any_mut()
+                .downcast_mut::<SparseSet<T>>()
+        }) else {
             debug_assert!(
                 false,
                 "Internal error: failed to get SparseSet for component type"
             );
+            return;
+        };
+        let sparse_capacity = entities.iter().map(|entity| entity.index() + 1).max();
+        sparse_set.reserve(sparse_capacity.unwrap_or(0), components.len());
+        for (&entity, component) in entities.iter().zip(components) {
+            let previous = sparse_set.add(entity, component, change_tick);
+            if let Some(hooks) = hooks {
+                hooks.trigger(&mut self.triggered_hooks, entity, previous.is_none());
+            }
+        }
+        if self.group_of.contains_key(&type_id) {
+            for &entity in entities {
+                self.enter_group(type_id, entity);
+            }
         }
+        self.index_names(type_id, entities);
     }
 
-    pub fn remove_all_components(&mut self, entity_id: usize) {
-        for storage in self.storages.values_mut() {
-            storage.remove_component(entity_id);
+    pub fn remove_component<T: Send + Sync + 'static>(&mut self, entity: Entity) -> Option<T> {
+        if !self.has_component::<T>(entity) {
+            return None;
+        }
+        self.mark_modified(TypeId::of::<T>());
+        self.unindex_names(TypeId::of::<T>(), &[entity]);
+        self.leave_group(TypeId::of::<T>(), entity);
+        if self.tables.stores(TypeId::of::<T>()) {
+            return self.tables.remove(entity);
         }
+        self.get_mut_sparse_set::<T>()?.remove(entity)
+    }
+
+    pub fn set_hooks<T: Send + Sync + 'static>(&mut self, hooks: ComponentHooks) {
+        self.hooks.insert(TypeId::of::<T>(), hooks);
+    }
+
+    /// Takes the `on_add` and `on_insert` hooks triggered since the last call.
+    pub fn take_triggered_hooks(&mut self) -> Vec<(ComponentHook, Entity)> {
+        std::mem::take(&mut self.triggered_hooks)
     }
 
-    pub fn get_component<T: 'static>(&self, entity_id: usize) -> Option<&T> {
+    /// The `on_remove` hook of `T`, if the entity has a `T` to remove.
+    pub fn remove_hook<T: Send + Sync + 'static>(&self, entity: Entity) -> Option<ComponentHook> {
+        let hook = self.hooks.get(&TypeId::of::<T>())?.remove?;
+        self.has_component::<T>(entity).then_some(hook)
+    }
+
+    /// The `on_remove` hooks of every component the entity has.
+    pub fn remove_hooks(&self, entity: Entity) -> impl Iterator<Item = ComponentHook> + '_ {
+        self.hooks.iter().filter_map(move |(type_id, hooks)| {
+            let hook = hooks.remove?;
+            self.contains(*type_id, entity).then_some(hook)
+        })
+    }
+
+    pub fn remove_all_components(&mut self, entity: Entity) {
+        self.remove_entities(&[entity]);
+    }
+
+    /// Removes every component of the entities, visiting each storage once.
+    pub fn remove_entities(&mut self, entities: &[Entity]) {
+        self.unindex_names(TypeId::of::<Name>(), entities);
+        if !self.groups.is_empty() {
+            for &entity in entities {
+                self.leave_groups(entity);
+            }
+        }
+        for (type_id, storage) in &mut self.storages {
+            let storage = storage.get_mut();
+            let mut modified = false;
+            for &entity in entities {
+                if storage.contains(entity) {
+                    storage.remove_component(entity);
+                    modified = true;
+                }
+            }
+            if let Some(copy) = self.copies.get(type_id).filter(|_| modified) {
+                copy.mark_modified();
+            }
+        }
+        let mut modified = false;
+        for &entity in entities {
+            modified |= self.tables.remove_entity(entity);
+        }
+        if modified {
+            self.tables_copy.mark_modified();
+        }
+        if self.dynamic.remove_entities(entities) {
+            self.dynamic_copy.mark_modified();
+        }
+    }
+
+    /// Takes every component out of the entity, e.g. to insert them into
+    /// another storage. No hooks are recorded.
+    pub fn take_all_components(&mut self, entity: Entity) -> Vec<ErasedComponent> {
+        self.leave_groups(entity);
+        self.unindex_names(TypeId::of::<Name>(), &[entity]);
+        let mut taken = Vec::new();
+        for (type_id, storage) in &mut self.storages {
+            if let Some(component) = storage.get_mut().take_component(entity) {
+                taken.push(component);
+                if let Some(copy) = self.copies.get(type_id) {
+                    copy.mark_modified();
+                }
+            }
+        }
+        let table_components = self.tables.take_entity(entity);
+        if !table_components.is_empty() {
+            self.tables_copy.mark_modified();
+            taken.extend(table_components);
+        }
+        taken
+    }
+
+    pub fn get_component<T: Send + Sync + 'static>(&self, entity: Entity) -> Option<&T> {
+        if self.tables.stores(TypeId::of::<T>()) {
+            return self.tables.get(entity);
+        }
         let sparse_set = self.get_sparse_set::<T>()?;
-        sparse_set.get_component(entity_id)
+        sparse_set.get_component(entity)
     }
 
-    pub fn has_component<T: 'static>(&self, entity_id: usize) -> bool {
-        if let Some(sparse_set) = self.get_sparse_set::<T>() {
-            sparse_set.get_component(entity_id).is_some()
-        } else {
-            false
+    /// Returns the component mutably and marks it as changed at the current tick.
+    /// `None` for [`Name`], which would leave the name index stale, and for
+    /// [`Parent`] and [`Children`], which must agree with each other; use
+    /// [`EntityManager::set_name`] and [`EntityManager::set_parent`] instead.
+    pub fn get_component_mut<T: Send + Sync + 'static>(
+        &mut self,
+        entity: Entity,
+    ) -> Option<&mut T> {
+        if is_maintained(TypeId::of::<T>()) {
+            return None;
+        }
+        self.get_component_mut_unchecked(entity)
+    }
+
+    /// [`ComponentStorage::get_component_mut`] for every type, for the code
+    /// keeping the refused types consistent.
+    pub(crate) fn get_component_mut_unchecked<T: Send + Sync + 'static>(
+        &mut self,
+        entity: Entity,
+    ) -> Option<&mut T> {
+        let change_tick = self.change_tick();
+        self.mark_modified(TypeId::of::<T>());
+        if self.tables.stores(TypeId::of::<T>()) {
+            return self.tables.get_mut(entity, change_tick);
+        }
+        let sparse_set = self.get_mut_sparse_set::<T>()?;
+        sparse_set.get_component_mut(entity, change_tick)
+    }
+
+    pub fn has_component<T: Send + Sync + 'static>(&self, entity: Entity) -> bool {
+        self.contains(TypeId::of::<T>(), entity)
+    }
+
+    /// Type-erased [`ComponentStorage::get_component`], to be downcast to the
+    /// type of `type_id`.
+    pub fn get_component_dyn(&self, type_id: TypeId, entity: Entity) -> Option<&dyn Any> {
+        if self.tables.stores(type_id) {
+            return self.tables.get_dyn(type_id, entity);
+        }
+        let storage = self.storages.get(&type_id)?;
+        // SAFETY: see `get_sparse_set`.
+        unsafe { &*storage.get() }.get_any(entity)
+    }
+
+    /// Type-erased [`ComponentStorage::get_component_mut`], also `None` for
+    /// [`Name`], [`Parent`] and [`Children`].
+    pub fn get_component_dyn_mut(
+        &mut self,
+        type_id: TypeId,
+        entity: Entity,
+    ) -> Option<&mut dyn Any> {
+        if is_maintained(type_id) {
+            return None;
         }
+        self.get_any_mut(type_id, entity)
     }
 
-    fn needs_sparse_set_resize<T: 'static>(&self, entity_id: usize) -> bool {
-        if let Some(sparse_set) = self.get_sparse_set::<T>() {
-            entity_id >= sparse_set.get_sparse_array_size()
+    fn get_any_mut(&mut self, type_id: TypeId, entity: Entity) -> Option<&mut dyn Any> {
+        let change_tick = self.change_tick();
+        self.mark_modified(type_id);
+        if self.tables.stores(type_id) {
+            return self.tables.get_dyn_mut(type_id, entity, change_tick);
+        }
+        self.storages
+            .get_mut(&type_id)?
+            .get_mut()
+            .get_any_mut(entity, change_tick)
+    }
+
+    /// Runs `update` on the component like
+    /// [`ComponentStorage::get_component_dyn_mut`], keeping the name index
+    /// current if it renames the entity. Unlike it, this also updates [`Name`].
+    pub fn update_component_dyn<R>(
+        &mut self,
+        type_id: TypeId,
+        entity: Entity,
+        update: impl FnOnce(&mut dyn Any) -> R,
+    ) -> Option<R> {
+        self.unindex_names(type_id, &[entity]);
+        let result = self.get_any_mut(type_id, entity).map(update);
+        self.index_names(type_id, &[entity]);
+        result
+    }
+
+    /// Components whose type is only known at runtime.
+    #[must_use]
+    pub fn dynamic(&self) -> &DynamicStorage {
+        &self.dynamic
+    }
+
+    /// Mutable access to the dynamic components, which stop being shared
+    /// with the last snapshot.
+    pub fn dynamic_mut(&mut self) -> &mut DynamicStorage {
+        self.dynamic_copy.mark_modified();
+        &mut self.dynamic
+    }
+
+    /// Rows of the dynamic components `ids`, for a query. `None` if one of
+    /// them is not registered.
+    ///
+    /// # Safety
+    ///
+    /// Nothing else may access the components of `ids` for `'w`.
+    pub(crate) unsafe fn dynamic_sets(
+        &self,
+        ids: &[DynamicComponentId],
+    ) -> Option<Vec<RawDynamicSet<'_>>> {
+        self.dynamic_copy.mark_modified();
+        // SAFETY: guaranteed by the caller.
+        unsafe { self.dynamic.raw_sets(ids) }
+    }
+
+    /// Type IDs and names of every component of the entity, in no particular order.
+    pub fn component_types(&self, entity: Entity) -> Vec<(TypeId, &'static str)> {
+        let mut types: Vec<_> = self
+            .storages
+            .iter()
+            .filter_map(|(type_id, storage)| {
+                // SAFETY: see `get_sparse_set`.
+                let storage = unsafe { &*storage.get() };
+                storage
+                    .contains(entity)
+                    .then(|| (*type_id, storage.type_name()))
+            })
+            .collect();
+        types.extend(self.tables.component_types(entity));
+        types
+    }
+
+    /// Returns a read-only view of the components of `T`.
+    ///
+    /// # Safety
+    ///
+    /// No mutable view of the same component type may be alive for `'w`.
+    pub(crate) unsafe fn view<T: Send + Sync + 'static>(&self) -> Option<ComponentView<'_, T>> {
+        // SAFETY: guaranteed by the caller.
+        let (rows, columns) = unsafe { self.raw_columns::<T>(false) }?;
+        Some(ComponentView {
+            rows,
+            columns,
+            marker: PhantomData,
+        })
+    }
+
+    /// Returns a mutable view of the components of `T`, which marks fetched
+    /// entries as changed at `this_run`.
+    ///
+    /// # Safety
+    ///
+    /// No other view of the same component type may be alive for `'w`.
+    pub(crate) unsafe fn view_mut<T: Send + Sync + 'static>(
+        &self,
+        this_run: Tick,
+    ) -> Option<ComponentViewMut<'_, T>> {
+        // SAFETY: guaranteed by the caller.
+        let (rows, columns) = unsafe { self.raw_columns::<T>(true) }?;
+        self.mark_modified(TypeId::of::<T>());
+        Some(ComponentViewMut {
+            rows,
+            columns,
+            this_run,
+            marker: PhantomData,
+        })
+    }
+
+    /// Returns the ownership and tick information of the components of `T`.
+    ///
+    /// # Safety
+    ///
+    /// The entity lists and ticks of `T` must not be modified for `'w`, so no
+    /// other query may hold a mutable view of `T`.
+    pub(crate) unsafe fn ticks_view<T: Send + Sync + 'static>(
+        &self,
+    ) -> Option<ComponentTicksView<'_>> {
+        // SAFETY: guaranteed by the caller.
+        let (rows, columns) = unsafe { self.raw_columns::<T>(false) }?;
+        Some(ComponentTicksView {
+            rows,
+            columns: columns
+                .into_iter()
+                .map(|column| (column.added.cast_const(), column.changed.cast_const()))
+                .collect(),
+            marker: PhantomData,
+        })
+    }
+
+    /// Rows and arrays of every sparse set or table column holding `T`.
+    /// Mutable pointers are only derived from a mutable borrow if `write` is set.
+    ///
+    /// # Safety
+    ///
+    /// Same as [`Self::view_mut`] if `write` is set, else as [`Self::view`].
+    unsafe fn raw_columns<T: Send + Sync + 'static>(
+        &self,
+        write: bool,
+    ) -> Option<(Rows<'_>, Vec<RawColumn<T>>)> {
+        let type_id = TypeId::of::<T>();
+        if self.tables.stores(type_id) {
+            let tables = self.tables.tables();
+            let mut entities = Vec::with_capacity(tables.len());
+            let mut columns = Vec::with_capacity(tables.len());
+            for table in tables {
+                // SAFETY: guaranteed by the caller.
+                let column = unsafe { table.raw_column::<T>(write) };
+                entities.push(column.is_some().then(|| table.entities()));
+                columns.push(column.unwrap_or_else(RawColumn::dangling));
+            }
+            let rows = Rows::Table {
+                locations: self.tables.locations(),
+                entities,
+            };
+            return Some((rows, columns));
+        }
+
+        let storage = self.storages.get(&type_id)?;
+        let (sparse_set, column) = if write {
+            // SAFETY: guaranteed by the caller.
+            let sparse_set = unsafe { &mut *storage.get() }
+                .as_any_mut()
+                .downcast_mut::<SparseSet<T>>()?;
+            let column = RawColumn::new_mut(
+                &mut sparse_set.dense,
+                &mut sparse_set.added,
+                &mut sparse_set.changed,
+            );
+            (&*sparse_set, column)
         } else {
-            false
+            // SAFETY: guaranteed by the caller.
+            let sparse_set = unsafe { &*storage.get() }
+                .as_any()
+                .downcast_ref::<SparseSet<T>>()?;
+            let column = RawColumn::new(&sparse_set.dense, &sparse_set.added, &sparse_set.changed);
+            (sparse_set, column)
+        };
+        let rows = Rows::Sparse {
+            sparse: &sparse_set.sparse,
+            entities: &sparse_set.entities,
+        };
+        Some((rows, vec![column]))
+    }
+
+    /// Stops sharing the storage of the type with the last snapshot, so the
+    /// next snapshot copies it again.
+    fn mark_modified(&self, type_id: TypeId) {
+        if self.tables.stores(type_id) {
+            self.tables_copy.mark_modified();
+        } else if let Some(copy) = self.copies.get(&type_id) {
+            copy.mark_modified();
         }
     }
 
-    fn resize_sparse_set<T: 'static>(&mut self) {
-        self.initial_capacity *= 2;
-        let initial_capacity = self.initial_capacity;
-        if let Some(sparse_set) = self.get_mut_sparse_set::<T>() {
-            sparse_set.resize(initial_capacity);
+    fn contains(&self, type_id: TypeId, entity: Entity) -> bool {
+        if self.tables.stores(type_id) {
+            return self.tables.contains(type_id, entity);
         }
+        self.storages.get(&type_id).is_some_and(|storage| {
+            // SAFETY: see `get_sparse_set`.
+            unsafe { &*storage.get() }.contains(entity)
+        })
     }
 
-    fn get_mut_sparse_set<T: 'static>(&mut self) -> Option<&mut SparseSet<T>> {
+    fn get_mut_sparse_set<T: Send + Sync + 'static>(&mut self) -> Option<&mut SparseSet<T>> {
         let type_id = TypeId::of::<T>();
         let storage = self.storages.get_mut(&type_id)?;
-        storage.as_any_mut().downcast_mut::<SparseSet<T>>()
+        storage
+            .get_mut()
+            .as_any_mut()
+            .downcast_mut::<SparseSet<T>>()
     }
 
-    fn get_sparse_set<T: 'static>(&self) -> Option<&SparseSet<T>> {
+    fn get_sparse_set<T: Send + Sync + 'static>(&self) -> Option<&SparseSet<T>> {
         let type_id = TypeId::of::<T>();
         let storage = self.storages.get(&type_id)?;
+        // SAFETY: components are only written through `&self` by the mutable
+        // views of queries, and the scheduler never runs a query writing `T`
+        // alongside any other access of `T`, filters included.
+        let storage = unsafe { &*storage.get() };
         storage.as_any().downcast_ref::<SparseSet<T>>()
     }
 }
 
 impl Default for ComponentStorage {
     fn default() -> Self {
-        Self::new(1000)
+        Self::new(DEFAULT_CAPACITY)
     }
 }
 
 impl<T> SparseSet<T> {
+    /// Sparse set whose page table has room for entity indices below `capacity`.
     pub fn new(capacity: usize) -> Self {
         Self {
-            sparse: vec![None; capacity],
+            sparse: SparseArray::with_capacity(capacity),
             dense: Vec::new(),
             entities: Vec::new(),
+            added: Vec::new(),
+            changed: Vec::new(),
         }
     }
 
-    pub fn resize(&mut self, new_size: usize) {
-        self.sparse.resize(new_size, None);
+    /// Makes room for entity indices below `sparse_capacity` in the page table
+    /// and for `additional` more components without reallocating.
+    pub fn reserve(&mut self, sparse_capacity: usize, additional: usize) {
+        self.sparse.reserve(sparse_capacity);
+        self.dense.reserve(additional);
+        self.entities.reserve(additional);
+        self.added.reserve(additional);
+        self.changed.reserve(additional);
     }
 
-    pub fn remove(&mut self, entity_id: usize) {
-        if let Some(index) = self.get_component_dense_index(entity_id) {
-            self.dense.swap_remove(index);
+    pub fn remove(&mut self, entity: Entity) -> Option<T> {
+        let index = self.get_component_dense_index(entity)?;
+        let component = self.dense.swap_remove(index);
+        self.added.swap_remove(index);
+        self.changed.swap_remove(index);
 
-            let last_entity_id = *self
-                .entities
-                .last()
-                .expect("This should not fail, internal logic error");
-            self.entities.swap_remove(index);
+        let last_entity = *self
+            .entities
+            .last()
+            .expect("This should not fail, internal logic error");
+        self.entities.swap_remove(index);
 
-            if index < self.entities.len() {
-                self.sparse[last_entity_id] = Some(index);
+        if index < self.entities.len() {
+            self.sparse.insert(last_entity.index(), index);
+        }
+        self.sparse.remove(entity.index());
+        Some(component)
+    }
+
+    /// Inserts the component, replacing the entity's previous value in place
+    /// so the dense array never holds two entries for the same entity. A
+    /// replaced value counts as a change, a new one as both added and changed.
+    pub fn add(&mut self, entity: Entity, component: T, tick: Tick) -> Option<T> {
+        if let Some(index) = self.get_component_dense_index(entity) {
+            self.changed[index] = tick;
+            return Some(std::mem::replace(&mut self.dense[index], component));
+        }
+
+        self.sparse.insert(entity.index(), self.dense.len());
+        self.dense.push(component);
+        self.entities.push(entity);
+        self.added.push(tick);
+        self.changed.push(tick);
+        None
+    }
+
+    pub fn get_component(&self, entity: Entity) -> Option<&T> {
+        let entity_index = self.get_component_dense_index(entity)?;
+        self.dense.get(entity_index)
+    }
+
+    pub fn get_component_mut(&mut self, entity: Entity, tick: Tick) -> Option<&mut T> {
+        let entity_index = self.get_component_dense_index(entity)?;
+        self.changed[entity_index] = tick;
+        self.dense.get_mut(entity_index)
+    }
+
+    fn get_component_dense_index(&self, entity: Entity) -> Option<usize> {
+        dense_index(&self.sparse, &self.entities, entity)
+    }
+}
+
+impl ErasedComponent {
+    fn new<T: Send + Sync + 'static>(value: T) -> Self {
+        Self {
+            value: Box::new(value),
+            insert: insert_erased::<T>,
+        }
+    }
+
+    pub fn downcast_mut<T: 'static>(&mut self) -> Option<&mut T> {
+        self.value.downcast_mut()
+    }
+
+    /// Adds the component to the entity in `storage`, like
+    /// [`ComponentStorage::add_component`].
+    pub fn insert_into(self, storage: &mut ComponentStorage, entity: Entity) {
+        (self.insert)(storage, entity, self.value);
+    }
+}
+
+impl<'w> Rows<'w> {
+    /// Column and row of the entity's component, if it has one.
+    fn find(&self, entity: Entity) -> Option<(usize, usize)> {
+        match self {
+            Self::Sparse { sparse, entities } => {
+                dense_index(sparse, entities, entity).map(|row| (0, row))
             }
-            self.sparse[entity_id] = None;
+            Self::Table {
+                locations,
+                entities,
+            } => table_row(
+                locations,
+                |table| entities.get(table).copied().flatten(),
+                entity,
+            )
+            .map(|location| (location.table, location.row)),
         }
     }
 
-    pub fn add(&mut self, entity_id: usize, component: T) {
-        if entity_id < self.sparse.len() {
-            self.dense.push(component);
-            self.entities.push(entity_id);
-            let index = self.dense.len() - 1;
-            self.sparse[entity_id] = Some(index);
-        } else {
-            debug_assert!(
-                entity_id < self.sparse.len(),
-                "ComponentStorage should ensure entity_id is valid"
-            );
+    /// Owners of the component, one list per sparse set or table.
+    fn entities(&self) -> Vec<&'w [Entity]> {
+        match self {
+            Self::Sparse { entities, .. } => vec![entities],
+            Self::Table { entities, .. } => entities.iter().flatten().copied().collect(),
         }
     }
+}
 
-    pub fn get_component(&self, entity_id: usize) -> Option<&T> {
-        if entity_id < self.sparse.len()
-            && let Some(entity_index) = self.sparse[entity_id]
-        {
-            return self.dense.get(entity_index);
+impl<T> RawColumn<T> {
+    fn new(values: &[T], added: &[Tick], changed: &[Tick]) -> Self {
+        Self {
+            values: values.as_ptr().cast_mut(),
+            added: added.as_ptr().cast_mut(),
+            changed: changed.as_ptr().cast_mut(),
         }
-        None
     }
 
-    pub fn get_sparse_array_size(&self) -> usize {
-        self.sparse.len()
+    fn new_mut(values: &mut [T], added: &mut [Tick], changed: &mut [Tick]) -> Self {
+        Self {
+            values: values.as_mut_ptr(),
+            added: added.as_mut_ptr(),
+            changed: changed.as_mut_ptr(),
+        }
     }
 
-    fn get_component_dense_index(&self, entity_id: usize) -> Option<usize> {
-        if let Some(dense_index_opt) = self.sparse.get(entity_id)
-            && let Some(dense_index) = dense_index_opt
-        {
-            return Some(*dense_index);
+    /// Stands in for tables without the column. Never dereferenced, since
+    /// [`Rows::find`] does not return rows of those tables.
+    fn dangling() -> Self {
+        Self {
+            values: std::ptr::null_mut(),
+            added: std::ptr::null_mut(),
+            changed: std::ptr::null_mut(),
         }
-        None
     }
 }
 
+impl<'w, T> ComponentView<'w, T> {
+    pub fn contains(&self, entity: Entity) -> bool {
+        self.rows.find(entity).is_some()
+    }
+
+    pub fn get(&self, entity: Entity) -> Option<&'w T> {
+        let (column, row) = self.rows.find(entity)?;
+        // SAFETY: `row` comes from the rows of `column`, so it is in bounds,
+        // and no mutable view of `T` is alive.
+        Some(unsafe { &*self.columns[column].values.add(row) })
+    }
+
+    pub fn entities(&self) -> Vec<&'w [Entity]> {
+        self.rows.entities()
+    }
+}
+
+impl<'w, T> ComponentViewMut<'w, T> {
+    pub fn contains(&self, entity: Entity) -> bool {
+        self.rows.find(entity).is_some()
+    }
+
+    /// # Safety
+    ///
+    /// The same entity must not be fetched again while the returned reference is alive.
+    pub unsafe fn get(&self, entity: Entity) -> Option<&'w mut T> {
+        let (column, row) = self.rows.find(entity)?;
+        let column = &self.columns[column];
+        // SAFETY: `row` is in bounds of the value and tick arrays of `column`,
+        // and the caller guarantees the element is not aliased.
+        unsafe {
+            *column.changed.add(row) = self.this_run;
+            Some(&mut *column.values.add(row))
+        }
+    }
+
+    pub fn entities(&self) -> Vec<&'w [Entity]> {
+        self.rows.entities()
+    }
+}
+
+impl<'w> ComponentTicksView<'w> {
+    pub fn entities(&self) -> Vec<&'w [Entity]> {
+        self.rows.entities()
+    }
+
+    pub fn contains(&self, entity: Entity) -> bool {
+        self.rows.find(entity).is_some()
+    }
+
+    pub fn added(&self, entity: Entity) -> Option<Tick> {
+        let (column, row) = self.rows.find(entity)?;
+        // SAFETY: `row` is in bounds, and tick writes never overlap a read
+        // because a query handles one entity at a time.
+        Some(unsafe { *self.columns[column].0.add(row) })
+    }
+
+    pub fn changed(&self, entity: Entity) -> Option<Tick> {
+        let (column, row) = self.rows.find(entity)?;
+        // SAFETY: same as `added`.
+        Some(unsafe { *self.columns[column].1.add(row) })
+    }
+}
+
+/// Whether the entity manager keeps components of the type consistent with
+/// other state, so they are never handed out mutably.
+fn is_maintained(type_id: TypeId) -> bool {
+    type_id == TypeId::of::<Name>()
+        || type_id == TypeId::of::<Parent>()
+        || type_id == TypeId::of::<Children>()
+}
+
+fn insert_erased<T: Send + Sync + 'static>(
+    storage: &mut ComponentStorage,
+    entity: Entity,
+    value: Box<dyn Any + Send + Sync>,
+) {
+    let value = value
+        .downcast::<T>()
+        .expect("Internal error: erased component inserted as another type");
+    storage.add_component(entity, *value);
+}
+
+/// Indices of `values` in the order `compare` sorts them, stable.
+fn sort_order<T>(
+    values: &[T],
+    compare: &mut impl FnMut(&T, &T) -> std::cmp::Ordering,
+) -> Vec<usize> {
+    let mut order: Vec<usize> = (0..values.len()).collect();
+    order.sort_by(|&a, &b| compare(&values[a], &values[b]));
+    order
+}
+
+/// Reorders `values` in place so that `values[i]` becomes the old
+/// `values[order[i]]`, `order` being a permutation of the indices.
+fn apply_permutation<T>(values: &mut [T], order: &[usize]) {
+    let mut placed = vec![false; order.len()];
+    for start in 0..order.len() {
+        let mut current = start;
+        while !placed[current] {
+            placed[current] = true;
+            let next = order[current];
+            if next == start {
+                break;
+            }
+            values.swap(current, next);
+            current = next;
+        }
+    }
+}
+
+fn dense_index(sparse: &SparseArray, entities: &[Entity], entity: Entity) -> Option<usize> {
+    let index = sparse.get(entity.index())?;
+    (entities[index] == entity).then_some(index)
+}
+
 #[cfg(test)]
 mod tests {
-    use super::ComponentStorage;
-    use super::SparseSet;
+    use std::any::TypeId;
+
+    use super::{ComponentHooks, ComponentStorage, SparseSet, StorageConfig, StorageKind, Tick};
+    use crate::entity::Entity;
 
     #[test]
     fn test_sparse_set_new() {
         let sparse_set: SparseSet<String> = SparseSet::new(0);
         assert!(sparse_set.dense.is_empty());
-        assert!(sparse_set.sparse.is_empty());
+        assert_eq!(sparse_set.sparse.allocated_pages(), 0);
         assert!(sparse_set.entities.is_empty());
     }
 
     #[test]
     fn test_sparse_set_add_component() {
         let mut sparse_set: SparseSet<String> = SparseSet::new(10);
-        sparse_set.add(0, "Hello".to_string());
-        sparse_set.add(1, "World".to_string());
-        sparse_set.add(2, "Engine".to_string());
+        sparse_set.add(Entity::new(0, 0), "Hello".to_string(), Tick::default());
+        sparse_set.add(Entity::new(1, 0), "World".to_string(), Tick::default());
+        sparse_set.add(Entity::new(2, 0), "Engine".to_string(), Tick::default());
 
         assert_eq!(sparse_set.dense.len(), 3);
         assert_eq!(sparse_set.entities.len(), 3);
-        assert_eq!(sparse_set.get_component(0).unwrap(), "Hello");
-        assert_eq!(sparse_set.get_component(1).unwrap(), "World");
-        assert_eq!(sparse_set.get_component(2).unwrap(), "Engine");
+        assert_eq!(
+            sparse_set.get_component(Entity::new(0, 0)).unwrap(),
+            "Hello"
+        );
+        assert_eq!(
+            sparse_set.get_component(Entity::new(1, 0)).unwrap(),
+            "World"
+        );
+        assert_eq!(
+            sparse_set.get_component(Entity::new(2, 0)).unwrap(),
+            "Engine"
+        );
     }
 
     #[test]
     fn test_sparse_set_remove_component() {
         let mut sparse_set: SparseSet<String> = SparseSet::new(10);
-        sparse_set.add(0, "Hello".to_string());
-        sparse_set.add(5, "World".to_string());
-        sparse_set.add(7, "Engine".to_string());
+        sparse_set.add(Entity::new(0, 0), "Hello".to_string(), Tick::default());
+        sparse_set.add(Entity::new(5, 0), "World".to_string(), Tick::default());
+        sparse_set.add(Entity::new(7, 0), "Engine".to_string(), Tick::default());
 
-        sparse_set.remove(5);
+        sparse_set.remove(Entity::new(5, 0));
 
         assert_eq!(sparse_set.dense.len(), 2);
         assert_eq!(sparse_set.entities.len(), 2);
 
-        assert!(sparse_set.get_component(5).is_none());
-        assert!(sparse_set.get_component(1).is_none());
+        assert!(sparse_set.get_component(Entity::new(5, 0)).is_none());
+        assert!(sparse_set.get_component(Entity::new(1, 0)).is_none());
 
-        assert_eq!(sparse_set.get_component(0).unwrap(), "Hello");
-        assert_eq!(sparse_set.get_component(7).unwrap(), "Engine");
+        assert_eq!(
+            sparse_set.get_component(Entity::new(0, 0)).unwrap(),
+            "Hello"
+        );
+        assert_eq!(
+            sparse_set.get_component(Entity::new(7, 0)).unwrap(),
+            "Engine"
+        );
     }
 
     #[test]
     fn test_sparse_set_add_remove_mixed() {
         let mut sparse_set: SparseSet<String> = SparseSet::new(10);
-        sparse_set.add(0, "Hello".to_string());
-        sparse_set.add(5, "World".to_string());
-        sparse_set.add(7, "Engine".to_string());
+        sparse_set.add(Entity::new(0, 0), "Hello".to_string(), Tick::default());
+        sparse_set.add(Entity::new(5, 0), "World".to_string(), Tick::default());
+        sparse_set.add(Entity::new(7, 0), "Engine".to_string(), Tick::default());
 
-        sparse_set.remove(5);
-        sparse_set.add(2, "Chronos".to_string());
+        sparse_set.remove(Entity::new(5, 0));
+        sparse_set.add(Entity::new(2, 0), "Chronos".to_string(), Tick::default());
 
         assert_eq!(sparse_set.dense.len(), 3);
         assert_eq!(sparse_set.entities.len(), 3);
 
-        assert!(sparse_set.get_component(5).is_none());
-        assert!(sparse_set.get_component(1).is_none());
+        assert!(sparse_set.get_component(Entity::new(5, 0)).is_none());
+        assert!(sparse_set.get_component(Entity::new(1, 0)).is_none());
 
-        assert_eq!(sparse_set.get_component(0).unwrap(), "Hello");
-        assert_eq!(sparse_set.get_component(7).unwrap(), "Engine");
-        assert_eq!(sparse_set.get_component(2).unwrap(), "Chronos");
+        assert_eq!(
+            sparse_set.get_component(Entity::new(0, 0)).unwrap(),
+            "Hello"
+        );
+        assert_eq!(
+            sparse_set.get_component(Entity::new(7, 0)).unwrap(),
+            "Engine"
+        );
+        assert_eq!(
+            sparse_set.get_component(Entity::new(2, 0)).unwrap(),
+            "Chronos"
+        );
     }
 
     #[test]
-    fn test_sparse_set_resize() {
+    fn test_sparse_set_grows_past_capacity() {
         let mut sparse_set: SparseSet<String> = SparseSet::new(2);
-        sparse_set.resize(5);
-        assert_eq!(sparse_set.get_sparse_array_size(), 5);
 
-        sparse_set.add(3, "Resize".to_string());
-        assert_eq!(sparse_set.get_component(3).unwrap(), "Resize");
+        sparse_set.add(Entity::new(3, 0), "Grown".to_string(), Tick::default());
+        sparse_set.add(Entity::new(100_000, 0), "Far".to_string(), Tick::default());
+
+        assert_eq!(
+            sparse_set.get_component(Entity::new(3, 0)).unwrap(),
+            "Grown"
+        );
+        assert_eq!(
+            sparse_set.get_component(Entity::new(100_000, 0)).unwrap(),
+            "Far"
+        );
+        assert_eq!(sparse_set.sparse.allocated_pages(), 2);
     }
 
     #[test]
-    fn test_component_storage_resize() {
+    fn test_component_storage_large_entity_ids() {
         let mut storage = ComponentStorage::new(2);
-        storage.add_component(0, "First".to_string());
-        storage.add_component(1, "Second".to_string());
+        storage.add_component(Entity::new(0, 0), "First".to_string());
+        storage.add_component(Entity::new(10_000, 0), "Second".to_string());
+        storage.add_component(Entity::new(usize::MAX, 0), "Last".to_string());
+        storage.add_component(Entity::new(10_000, 0), 1_u32);
 
         assert_eq!(storage.initial_capacity, 2);
-
-        storage.add_component(3, "Third".to_string());
-        assert_eq!(storage.initial_capacity, 4);
+        assert_eq!(
+            storage
+                .get_component::<String>(Entity::new(10_000, 0))
+                .unwrap(),
+            "Second"
+        );
+        assert_eq!(
+            storage
+                .get_component::<String>(Entity::new(usize::MAX, 0))
+                .unwrap(),
+            "Last"
+        );
+        assert_eq!(
+            storage
+                .get_sparse_set::<u32>()
+                .unwrap()
+                .sparse
+                .allocated_pages(),
+            1
+        );
+        assert_eq!(
+            storage
+                .remove_component::<String>(Entity::new(usize::MAX, 0))
+                .as_deref(),
+            Some("Last")
+        );
+        assert!(!storage.has_component::<String>(Entity::new(usize::MAX, 0)));
     }
 
     #[test]
     fn test_component_storage_has_component() {
         let mut storage = ComponentStorage::new(2);
-        storage.add_component(0, "First".to_string());
+        storage.add_component(Entity::new(0, 0), "First".to_string());
+
+        assert!(storage.has_component::<String>(Entity::new(0, 0)));
+        assert!(!storage.has_component::<String>(Entity::new(1, 0)));
+    }
+
+    #[test]
+    fn test_sparse_set_rejects_stale_generation() {
+        let mut sparse_set: SparseSet<String> = SparseSet::new(10);
+        sparse_set.add(Entity::new(3, 1), "Current".to_string(), Tick::default());
+
+        assert!(sparse_set.get_component(Entity::new(3, 0)).is_none());
+        sparse_set.remove(Entity::new(3, 0));
+        assert_eq!(
+            sparse_set.get_component(Entity::new(3, 1)).unwrap(),
+            "Current"
+        );
+    }
 
-        assert!(storage.has_component::<String>(0));
-        assert!(!storage.has_component::<String>(1));
+    #[test]
+    fn test_sparse_set_add_replaces_existing_component() {
+        let mut sparse_set: SparseSet<String> = SparseSet::new(10);
+        sparse_set.add(Entity::new(1, 0), "Hello".to_string(), Tick::default());
+        sparse_set.add(Entity::new(4, 0), "World".to_string(), Tick::default());
+
+        let previous = sparse_set.add(Entity::new(1, 0), "Chronos".to_string(), Tick::default());
+
+        assert_eq!(previous.as_deref(), Some("Hello"));
+        assert_eq!(sparse_set.dense.len(), 2);
+        assert_eq!(sparse_set.entities.len(), 2);
+        assert_eq!(
+            sparse_set.get_component(Entity::new(1, 0)).unwrap(),
+            "Chronos"
+        );
+    }
+
+    #[test]
+    fn test_sparse_set_get_component_mut() {
+        let mut sparse_set: SparseSet<String> = SparseSet::new(10);
+        sparse_set.add(Entity::new(2, 0), "Hello".to_string(), Tick::default());
+
+        sparse_set
+            .get_component_mut(Entity::new(2, 0), Tick::default())
+            .unwrap()
+            .push_str(" World");
+
+        assert_eq!(
+            sparse_set.get_component(Entity::new(2, 0)).unwrap(),
+            "Hello World"
+        );
+        assert!(
+            sparse_set
+                .get_component_mut(Entity::new(2, 1), Tick::default())
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn test_component_storage_remove_component() {
+        let mut storage = ComponentStorage::new(10);
+        storage.add_component(Entity::new(0, 0), "First".to_string());
+        storage.add_component(Entity::new(1, 0), "Second".to_string());
+        storage.add_component(Entity::new(0, 0), 42_u32);
+
+        let removed = storage.remove_component::<String>(Entity::new(0, 0));
+
+        assert_eq!(removed.as_deref(), Some("First"));
+        assert!(!storage.has_component::<String>(Entity::new(0, 0)));
+        assert!(storage.has_component::<u32>(Entity::new(0, 0)));
+        assert_eq!(
+            storage.get_component::<String>(Entity::new(1, 0)).unwrap(),
+            "Second"
+        );
+        assert!(
+            storage
+                .remove_component::<String>(Entity::new(0, 0))
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn test_tick_is_newer_than() {
+        assert!(Tick::new(5).is_newer_than(Tick::new(4), Tick::new(6)));
+        assert!(!Tick::new(4).is_newer_than(Tick::new(4), Tick::new(6)));
+        assert!(Tick::new(1).is_newer_than(Tick::new(u32::MAX - 1), Tick::new(2)));
+    }
+
+    #[test]
+    fn test_component_storage_tracks_ticks() {
+        let mut storage = ComponentStorage::new(10);
+        let entity = Entity::new(0, 0);
+        storage.add_component(entity, "First".to_string());
+        let added_at = storage.change_tick();
+
+        storage.advance_tick();
+        storage.get_component_mut::<String>(entity).unwrap();
+        let changed_at = storage.change_tick();
+
+        let sparse_set = storage.get_sparse_set::<String>().unwrap();
+        assert_eq!(sparse_set.added[0], added_at);
+        assert_eq!(sparse_set.changed[0], changed_at);
+
+        storage.advance_tick();
+        storage.add_component(entity, "Second".to_string());
+        let sparse_set = storage.get_sparse_set::<String>().unwrap();
+        assert_eq!(sparse_set.added[0], added_at);
+        assert_eq!(sparse_set.changed[0], storage.change_tick());
+    }
+
+    #[test]
+    fn test_component_storage_table_kind() {
+        let mut storage = ComponentStorage::new(10);
+        let table = StorageConfig {
+            kind: StorageKind::Table,
+            ..StorageConfig::DEFAULT
+        };
+        storage.register_component_type_with::<u32>(table);
+        storage.register_component_type_with::<String>(table);
+        storage.set_hooks::<u32>(ComponentHooks::new().on_remove(|_, _, _| {}));
+        let first = Entity::new(0, 0);
+        let second = Entity::new(1, 0);
+        storage.add_component(first, 1_u32);
+        storage.add_component(first, "first".to_string());
+        storage.add_component(second, 2_u32);
+        storage.add_component(second, 'x');
+
+        assert!(!storage.storages.contains_key(&TypeId::of::<u32>()));
+        assert_eq!(storage.get_component::<u32>(first), Some(&1));
+        assert_eq!(storage.get_component::<char>(second), Some(&'x'));
+        assert_eq!(storage.remove_hooks(first).count(), 1);
+
+        *storage.get_component_mut::<u32>(second).unwrap() += 10;
+        assert_eq!(storage.remove_component::<u32>(first), Some(1));
+        assert!(!storage.has_component::<u32>(first));
+        assert!(storage.has_component::<String>(first));
+        assert_eq!(storage.remove_hooks(first).count(), 0);
+
+        storage.remove_all_components(second);
+        assert!(!storage.has_component::<u32>(second));
+        assert!(!storage.has_component::<char>(second));
+
+        storage.register_component_type::<u32>();
+        storage.add_component(second, 3_u32);
+        assert!(storage.get_sparse_set::<u32>().is_some());
+    }
+
+    #[test]
+    #[should_panic(expected = "Cannot move stored")]
+    fn test_component_storage_rejects_switching_stored_kind() {
+        let mut storage = ComponentStorage::new(10);
+        storage.add_component(Entity::new(0, 0), 1_u32);
+        storage.register_component_type_with::<u32>(StorageConfig {
+            kind: StorageKind::Table,
+            ..StorageConfig::DEFAULT
+        });
     }
 }
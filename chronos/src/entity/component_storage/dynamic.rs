@@ -0,0 +1,716 @@
+use std::{cell::UnsafeCell, collections::HashMap, marker::PhantomData, sync::Arc};
+
+use super::sparse_array::SparseArray;
+use super::{ComponentStats, Tick, dense_index};
+use crate::entity::Entity;
+use crate::entity::reflect::{self, ReflectError};
+use crate::scene::{SceneError, Value};
+
+/// Handle to a component type registered at runtime with
+/// [`EntityManager::register_dynamic_component`](crate::entity::EntityManager::register_dynamic_component).
+/// Only meaningful for the entity manager that returned it.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct DynamicComponentId(usize);
+
+/// Type of a field of a dynamic component.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum FieldKind {
+    Bool,
+    U8,
+    U32,
+    U64,
+    I32,
+    I64,
+    F32,
+    F64,
+}
+
+/// Name and fields of a dynamic component. Fields are packed in declaration
+/// order, without padding.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct DynamicLayout {
+    name: String,
+    fields: Vec<DynamicField>,
+    size: usize,
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct DynamicField {
+    name: String,
+    kind: FieldKind,
+    offset: usize,
+}
+
+/// Rust type a [`FieldKind`] is read as and written from.
+pub trait DynamicFieldType: Copy {
+    const KIND: FieldKind;
+
+    fn read(bytes: &[u8]) -> Self;
+    fn write(self, bytes: &mut [u8]);
+}
+
+/// Read access to one dynamic component.
+#[derive(Clone, Copy)]
+pub struct DynamicRef<'w> {
+    layout: &'w DynamicLayout,
+    bytes: &'w [u8],
+}
+
+/// Write access to one dynamic component.
+pub struct DynamicMut<'w> {
+    layout: &'w DynamicLayout,
+    bytes: &'w mut [u8],
+}
+
+/// Every dynamic component type and its components, each type stored as one
+/// sparse set whose dense side is a byte array of `layout.size()` wide rows.
+#[derive(Clone, Default)]
+pub struct DynamicStorage {
+    sets: Vec<DynamicSet>,
+    by_name: HashMap<String, DynamicComponentId>,
+}
+
+struct DynamicSet {
+    layout: Arc<DynamicLayout>,
+    sparse: SparseArray,
+    entities: Vec<Entity>,
+    /// Written through `&self` by queries, like table columns.
+    data: UnsafeCell<Vec<u8>>,
+    added: Vec<Tick>,
+    changed: UnsafeCell<Vec<Tick>>,
+}
+
+// SAFETY: the cells are only written through `&self` by queries holding the
+// owning entity manager mutably.
+unsafe impl Sync for DynamicSet {}
+
+/// Pointer to the rows of one dynamic component type, handed out to a query
+/// that holds the storage mutably.
+pub struct RawDynamicSet<'w> {
+    layout: &'w DynamicLayout,
+    sparse: &'w SparseArray,
+    entities: &'w [Entity],
+    data: *mut u8,
+    changed: *mut Tick,
+    marker: PhantomData<&'w mut [u8]>,
+}
+
+impl FieldKind {
+    #[must_use]
+    pub fn size(self) -> usize {
+        match self {
+            Self::Bool | Self::U8 => 1,
+            Self::U32 | Self::I32 | Self::F32 => 4,
+            Self::U64 | Self::I64 | Self::F64 => 8,
+        }
+    }
+
+    fn read(self, bytes: &[u8]) -> Value {
+        match self {
+            Self::Bool => Value::Bool(bool::read(bytes)),
+            Self::U8 => Value::number(u8::read(bytes)),
+            Self::U32 => Value::number(u32::read(bytes)),
+            Self::U64 => Value::number(u64::read(bytes)),
+            Self::I32 => Value::number(i32::read(bytes)),
+            Self::I64 => Value::number(i64::read(bytes)),
+            Self::F32 => Value::number(f32::read(bytes)),
+            Self::F64 => Value::number(f64::read(bytes)),
+        }
+    }
+
+    fn write(self, value: &Value, bytes: &mut [u8]) -> Result<(), SceneError> {
+        match self {
+            Self::Bool => value.as_bool()?.write(bytes),
+            Self::U8 => value.as_number::<u8>()?.write(bytes),
+            Self::U32 => value.as_number::<u32>()?.write(bytes),
+            Self::U64 => value.as_number::<u64>()?.write(bytes),
+            Self::I32 => value.as_number::<i32>()?.write(bytes),
+            Self::I64 => value.as_number::<i64>()?.write(bytes),
+            Self::F32 => value.as_number::<f32>()?.write(bytes),
+            Self::F64 => value.as_number::<f64>()?.write(bytes),
+        }
+        Ok(())
+    }
+}
+
+impl DynamicLayout {
+    #[must_use]
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            fields: Vec::new(),
+            size: 0,
+        }
+    }
+
+    /// # Panics
+    ///
+    /// Panics if the layout already has a field called `name`.
+    #[must_use]
+    pub fn with_field(mut self, name: impl Into<String>, kind: FieldKind) -> Self {
+        let name = name.into();
+        assert!(
+            self.field(&name).is_none(),
+            "Dynamic component {} already has a field '{name}'",
+            self.name
+        );
+        self.fields.push(DynamicField {
+            name,
+            kind,
+            offset: self.size,
+        });
+        self.size += kind.size();
+        self
+    }
+
+    #[must_use]
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    #[must_use]
+    pub fn fields(&self) -> &[DynamicField] {
+        &self.fields
+    }
+
+    #[must_use]
+    pub fn field(&self, name: &str) -> Option<&DynamicField> {
+        self.fields.iter().find(|field| field.name == name)
+    }
+
+    /// Size of one component in bytes.
+    #[must_use]
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Bytes of a component whose fields are all zero or `false`.
+    #[must_use]
+    pub fn zeroed(&self) -> Vec<u8> {
+        vec![0; self.size]
+    }
+
+    fn unknown_field(&self, name: &str) -> ReflectError {
+        ReflectError::UnknownField {
+            type_name: "dynamic component",
+            field: format!("{}.{name}", self.name),
+        }
+    }
+}
+
+impl DynamicField {
+    #[must_use]
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    #[must_use]
+    pub fn kind(&self) -> FieldKind {
+        self.kind
+    }
+
+    /// Offset of the field in the component's bytes.
+    #[must_use]
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    fn range(&self) -> std::ops::Range<usize> {
+        self.offset..self.offset + self.kind.size()
+    }
+}
+
+macro_rules! impl_dynamic_field_type {
+    ($($T:ty => $kind:ident),+ $(,)?) => {
+        $(
+            impl DynamicFieldType for $T {
+                const KIND: FieldKind = FieldKind::$kind;
+
+                fn read(bytes: &[u8]) -> Self {
+                    <$T>::from_ne_bytes(bytes.try_into().expect("Field has the size of its kind"))
+                }
+
+                fn write(self, bytes: &mut [u8]) {
+                    bytes.copy_from_slice(&self.to_ne_bytes());
+                }
+            }
+        )+
+    };
+}
+
+impl_dynamic_field_type!(
+    u8 => U8,
+    u32 => U32,
+    u64 => U64,
+    i32 => I32,
+    i64 => I64,
+    f32 => F32,
+    f64 => F64,
+);
+
+impl DynamicFieldType for bool {
+    const KIND: FieldKind = FieldKind::Bool;
+
+    fn read(bytes: &[u8]) -> Self {
+        bytes[0] != 0
+    }
+
+    fn write(self, bytes: &mut [u8]) {
+        bytes[0] = u8::from(self);
+    }
+}
+
+impl<'w> DynamicRef<'w> {
+    #[must_use]
+    pub fn layout(&self) -> &'w DynamicLayout {
+        self.layout
+    }
+
+    #[must_use]
+    pub fn bytes(&self) -> &'w [u8] {
+        self.bytes
+    }
+
+    /// Value of the field, or `None` if there is no such field of kind `T::KIND`.
+    #[must_use]
+    pub fn get<T: DynamicFieldType>(&self, name: &str) -> Option<T> {
+        let field = self
+            .layout
+            .field(name)
+            .filter(|field| field.kind == T::KIND)?;
+        Some(T::read(&self.bytes[field.range()]))
+    }
+
+    /// The field as a scene value, or `None` if there is no such field.
+    #[must_use]
+    pub fn field(&self, name: &str) -> Option<Value> {
+        let field = self.layout.field(name)?;
+        Some(field.kind.read(&self.bytes[field.range()]))
+    }
+}
+
+impl<'w> DynamicMut<'w> {
+    #[must_use]
+    pub fn layout(&self) -> &'w DynamicLayout {
+        self.layout
+    }
+
+    #[must_use]
+    pub fn as_readonly(&self) -> DynamicRef<'_> {
+        DynamicRef {
+            layout: self.layout,
+            bytes: self.bytes,
+        }
+    }
+
+    #[must_use]
+    pub fn bytes_mut(&mut self) -> &mut [u8] {
+        self.bytes
+    }
+
+    #[must_use]
+    pub fn get<T: DynamicFieldType>(&self, name: &str) -> Option<T> {
+        self.as_readonly().get(name)
+    }
+
+    /// Writes the field, returning `false` if there is no such field of kind
+    /// `T::KIND`.
+    pub fn set<T: DynamicFieldType>(&mut self, name: &str, value: T) -> bool {
+        let Some(field) = self
+            .layout
+            .field(name)
+            .filter(|field| field.kind == T::KIND)
+        else {
+            return false;
+        };
+        value.write(&mut self.bytes[field.range()]);
+        true
+    }
+
+    /// Writes the field from a scene value.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if there is no such field or `value` does not fit it.
+    pub fn set_field(&mut self, name: &str, value: &Value) -> reflect::Result<()> {
+        let field = self
+            .layout
+            .field(name)
+            .ok_or_else(|| self.layout.unknown_field(name))?;
+        field.kind.write(value, &mut self.bytes[field.range()])?;
+        Ok(())
+    }
+}
+
+impl DynamicStorage {
+    /// Registers `layout`, or returns the ID it is registered under already.
+    ///
+    /// # Panics
+    ///
+    /// Panics if another layout is registered under the same name.
+    pub fn register(&mut self, layout: DynamicLayout) -> DynamicComponentId {
+        if let Some(&id) = self.by_name.get(layout.name()) {
+            assert!(
+                *self.sets[id.0].layout == layout,
+                "Dynamic component {} is registered with another layout",
+                layout.name()
+            );
+            return id;
+        }
+        let id = DynamicComponentId(self.sets.len());
+        self.by_name.insert(layout.name.clone(), id);
+        self.sets.push(DynamicSet {
+            layout: Arc::new(layout),
+            sparse: SparseArray::default(),
+            entities: Vec::new(),
+            data: UnsafeCell::new(Vec::new()),
+            added: Vec::new(),
+            changed: UnsafeCell::new(Vec::new()),
+        });
+        id
+    }
+
+    #[must_use]
+    pub fn id(&self, name: &str) -> Option<DynamicComponentId> {
+        self.by_name.get(name).copied()
+    }
+
+    #[must_use]
+    pub fn layout(&self, id: DynamicComponentId) -> Option<&DynamicLayout> {
+        self.sets.get(id.0).map(|set| &*set.layout)
+    }
+
+    /// Inserts the component, replacing and returning the previous one.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `id` is not registered or `bytes` does not have the size of
+    /// its layout.
+    pub fn insert(
+        &mut self,
+        id: DynamicComponentId,
+        entity: Entity,
+        bytes: &[u8],
+        tick: Tick,
+    ) -> Option<Vec<u8>> {
+        let set = &mut self.sets[id.0];
+        assert_eq!(
+            bytes.len(),
+            set.layout.size,
+            "Dynamic component {} is {} bytes long",
+            set.layout.name,
+            set.layout.size
+        );
+        if let Some(row) = set.row(entity) {
+            let previous = set.row_bytes(row).to_vec();
+            set.row_bytes_mut(row).copy_from_slice(bytes);
+            set.changed.get_mut()[row] = tick;
+            return Some(previous);
+        }
+        set.sparse.insert(entity.index(), set.entities.len());
+        set.entities.push(entity);
+        set.data.get_mut().extend_from_slice(bytes);
+        set.added.push(tick);
+        set.changed.get_mut().push(tick);
+        None
+    }
+
+    /// Removes the component and returns its bytes.
+    pub fn remove(&mut self, id: DynamicComponentId, entity: Entity) -> Option<Vec<u8>> {
+        self.sets.get_mut(id.0)?.remove(entity)
+    }
+
+    /// Removes every dynamic component of the entities. Returns whether any
+    /// was removed.
+    pub fn remove_entities(&mut self, entities: &[Entity]) -> bool {
+        let mut removed = false;
+        for set in &mut self.sets {
+            for &entity in entities {
+                removed |= set.remove(entity).is_some();
+            }
+        }
+        removed
+    }
+
+    /// Takes every dynamic component out of the entity, with its layout.
+    pub fn take_entity(&mut self, entity: Entity) -> Vec<(Arc<DynamicLayout>, Vec<u8>)> {
+        self.sets
+            .iter_mut()
+            .filter_map(|set| Some((set.layout.clone(), set.remove(entity)?)))
+            .collect()
+    }
+
+    /// Whether any of `entities` has a component that `target` has registered
+    /// under the same name with another layout, so it could not be moved there.
+    pub(crate) fn conflicts_with(&self, target: &DynamicStorage, entities: &[Entity]) -> bool {
+        self.sets.iter().any(|set| {
+            target
+                .id(set.layout.name())
+                .is_some_and(|id| *target.sets[id.0].layout != *set.layout)
+                && entities.iter().any(|&entity| set.row(entity).is_some())
+        })
+    }
+
+    #[must_use]
+    pub fn contains(&self, id: DynamicComponentId, entity: Entity) -> bool {
+        self.sets
+            .get(id.0)
+            .is_some_and(|set| set.row(entity).is_some())
+    }
+
+    #[must_use]
+    pub fn get(&self, id: DynamicComponentId, entity: Entity) -> Option<DynamicRef<'_>> {
+        let set = self.sets.get(id.0)?;
+        let row = set.row(entity)?;
+        Some(DynamicRef {
+            layout: &set.layout,
+            bytes: set.row_bytes(row),
+        })
+    }
+
+    /// Returns the component mutably and marks it as changed at `tick`.
+    pub fn get_mut(
+        &mut self,
+        id: DynamicComponentId,
+        entity: Entity,
+        tick: Tick,
+    ) -> Option<DynamicMut<'_>> {
+        let set = self.sets.get_mut(id.0)?;
+        let row = set.row(entity)?;
+        set.changed.get_mut()[row] = tick;
+        let size = set.layout.size;
+        Some(DynamicMut {
+            layout: &set.layout,
+            bytes: &mut set.data.get_mut()[row * size..(row + 1) * size],
+        })
+    }
+
+    /// IDs of the dynamic components of the entity.
+    #[must_use]
+    pub fn component_ids(&self, entity: Entity) -> Vec<DynamicComponentId> {
+        (0..self.sets.len())
+            .map(DynamicComponentId)
+            .filter(|id| self.sets[id.0].row(entity).is_some())
+            .collect()
+    }
+
+    /// Statistics of each registered dynamic component type.
+    #[must_use]
+    pub fn stats(&self) -> Vec<ComponentStats> {
+        self.sets
+            .iter()
+            .map(|set| {
+                // SAFETY: the cells are only written by queries holding the
+                // owning entity manager mutably.
+                let (data, changed) = unsafe { (&*set.data.get(), &*set.changed.get()) };
+                let bytes_used = data.capacity()
+                    + set.entities.capacity() * size_of::<Entity>()
+                    + (set.added.capacity() + changed.capacity()) * size_of::<Tick>()
+                    + set.sparse.heap_bytes();
+                ComponentStats::new(
+                    set.layout.name(),
+                    set.entities.len(),
+                    set.sparse.capacity(),
+                    bytes_used,
+                )
+            })
+            .collect()
+    }
+
+    pub fn shrink_to_fit(&mut self) {
+        for set in &mut self.sets {
+            set.sparse.shrink_to_fit();
+            set.entities.shrink_to_fit();
+            set.data.get_mut().shrink_to_fit();
+            set.added.shrink_to_fit();
+            set.changed.get_mut().shrink_to_fit();
+        }
+    }
+
+    /// Pointers to the rows of each of `ids`. `None` if one of them is not
+    /// registered.
+    ///
+    /// # Safety
+    ///
+    /// Nothing else may access the components of `ids` for `'w`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `ids` contains the same ID twice.
+    pub unsafe fn raw_sets(&self, ids: &[DynamicComponentId]) -> Option<Vec<RawDynamicSet<'_>>> {
+        let mut sets = Vec::with_capacity(ids.len());
+        for (index, id) in ids.iter().enumerate() {
+            assert!(
+                !ids[..index].contains(id),
+                "Query accesses dynamic component {id:?} more than once"
+            );
+            let set = self.sets.get(id.0)?;
+            // SAFETY: guaranteed by the caller.
+            let (data, changed) = unsafe {
+                (
+                    (*set.data.get()).as_mut_ptr(),
+                    (*set.changed.get()).as_mut_ptr(),
+                )
+            };
+            sets.push(RawDynamicSet {
+                layout: &set.layout,
+                sparse: &set.sparse,
+                entities: &set.entities,
+                data,
+                changed,
+                marker: PhantomData,
+            });
+        }
+        Some(sets)
+    }
+}
+
+impl DynamicSet {
+    fn row(&self, entity: Entity) -> Option<usize> {
+        dense_index(&self.sparse, &self.entities, entity)
+    }
+
+    fn row_bytes(&self, row: usize) -> &[u8] {
+        let size = self.layout.size;
+        // SAFETY: queries writing through the cell hold the storage mutably.
+        let data = unsafe { &*self.data.get() };
+        &data[row * size..(row + 1) * size]
+    }
+
+    fn row_bytes_mut(&mut self, row: usize) -> &mut [u8] {
+        let size = self.layout.size;
+        &mut self.data.get_mut()[row * size..(row + 1) * size]
+    }
+
+    fn remove(&mut self, entity: Entity) -> Option<Vec<u8>> {
+        let row = self.row(entity)?;
+        let size = self.layout.size;
+        let last = self.entities.len() - 1;
+        let bytes = self.row_bytes(row).to_vec();
+        let data = self.data.get_mut();
+        data.copy_within(last * size..(last + 1) * size, row * size);
+        data.truncate(last * size);
+        self.entities.swap_remove(row);
+        self.added.swap_remove(row);
+        self.changed.get_mut().swap_remove(row);
+        self.sparse.remove(entity.index());
+        if let Some(moved) = self.entities.get(row) {
+            self.sparse.insert(moved.index(), row);
+        }
+        Some(bytes)
+    }
+}
+
+impl Clone for DynamicSet {
+    fn clone(&self) -> Self {
+        // SAFETY: queries writing through the cells hold the storage mutably.
+        let (data, changed) =
+            unsafe { ((*self.data.get()).clone(), (*self.changed.get()).clone()) };
+        Self {
+            layout: self.layout.clone(),
+            sparse: self.sparse.clone(),
+            entities: self.entities.clone(),
+            data: UnsafeCell::new(data),
+            added: self.added.clone(),
+            changed: UnsafeCell::new(changed),
+        }
+    }
+}
+
+impl<'w> RawDynamicSet<'w> {
+    pub fn entities(&self) -> &'w [Entity] {
+        self.entities
+    }
+
+    pub fn contains(&self, entity: Entity) -> bool {
+        dense_index(self.sparse, self.entities, entity).is_some()
+    }
+
+    /// Returns the component mutably and marks it as changed at `tick`.
+    ///
+    /// # Safety
+    ///
+    /// The same entity must not be fetched twice while a returned item is alive.
+    pub unsafe fn get(&self, entity: Entity, tick: Tick) -> Option<DynamicMut<'w>> {
+        let row = dense_index(self.sparse, self.entities, entity)?;
+        let size = self.layout.size;
+        // SAFETY: `row` is in bounds of both arrays, which the query borrows
+        // mutably for `'w`, and the caller guarantees the row is not aliased.
+        unsafe {
+            *self.changed.add(row) = tick;
+            Some(DynamicMut {
+                layout: self.layout,
+                bytes: std::slice::from_raw_parts_mut(self.data.add(row * size), size),
+            })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DynamicLayout, DynamicStorage, FieldKind};
+    use crate::entity::{Entity, Tick};
+    use crate::scene::Value;
+
+    fn health() -> DynamicLayout {
+        DynamicLayout::new("Health")
+            .with_field("alive", FieldKind::Bool)
+            .with_field("current", FieldKind::F32)
+            .with_field("max", FieldKind::U32)
+    }
+
+    #[test]
+    fn test_dynamic_layout_packs_fields() {
+        let layout = health();
+
+        assert_eq!(layout.size(), 9);
+        assert_eq!(layout.field("max").unwrap().offset(), 5);
+        assert!(layout.field("armor").is_none());
+    }
+
+    #[test]
+    fn test_dynamic_storage_insert_remove_keeps_rows_packed() {
+        let mut storage = DynamicStorage::default();
+        let id = storage.register(health());
+        assert_eq!(storage.register(health()), id);
+        let entities: Vec<Entity> = (0..3).map(|index| Entity::new(index, 0)).collect();
+        for (value, &entity) in (0_u32..).zip(&entities) {
+            let mut bytes = health().zeroed();
+            bytes[5..9].copy_from_slice(&value.to_ne_bytes());
+            storage.insert(id, entity, &bytes, Tick::new(1));
+        }
+
+        let removed = storage.remove(id, entities[0]).unwrap();
+
+        assert_eq!(removed, health().zeroed());
+        assert!(!storage.contains(id, entities[0]));
+        assert_eq!(
+            storage.get(id, entities[1]).unwrap().get::<u32>("max"),
+            Some(1)
+        );
+        assert_eq!(
+            storage.get(id, entities[2]).unwrap().get::<u32>("max"),
+            Some(2)
+        );
+        assert_eq!(
+            storage.get(id, entities[2]).unwrap().get::<f32>("max"),
+            None
+        );
+
+        let mut health = storage.get_mut(id, entities[2], Tick::new(2)).unwrap();
+        assert!(health.set("alive", true));
+        health.set_field("current", &Value::number(2.5)).unwrap();
+        assert!(health.set_field("current", &Value::Bool(true)).is_err());
+        let health = storage.get(id, entities[2]).unwrap();
+        assert_eq!(health.field("alive"), Some(Value::Bool(true)));
+        assert_eq!(health.get::<f32>("current"), Some(2.5));
+    }
+
+    #[test]
+    #[should_panic(expected = "registered with another layout")]
+    fn test_dynamic_storage_rejects_conflicting_layouts() {
+        let mut storage = DynamicStorage::default();
+        storage.register(health());
+        storage.register(DynamicLayout::new("Health"));
+    }
+}
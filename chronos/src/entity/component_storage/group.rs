@@ -0,0 +1,287 @@
+use std::any::TypeId;
+use std::cmp::Ordering;
+
+use super::{Component, ComponentStorage, sort_order};
+use crate::entity::Entity;
+
+/// Sparse sets owned by a group. The entities having every type of the group
+/// sit in the first `len` rows of each owned set, in the same order. Types
+/// stored in tables are part of `types` but not `owned`: their tables keep
+/// them packed already, so they only decide which entities are grouped.
+#[derive(Clone)]
+pub(super) struct Group {
+    types: Vec<TypeId>,
+    owned: Vec<TypeId>,
+    len: usize,
+}
+
+impl ComponentStorage {
+    /// Makes one group own the sparse sets of `types`, which keeps the
+    /// entities having all of them packed at the front of every set in the
+    /// same order, so they can be walked in lockstep. Types stored in tables
+    /// are packed by their tables already and only restrict the group to the
+    /// entities having them. Does nothing if the group exists already.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `types` has fewer than two types, names a type twice, has
+    /// a type without storage or no type stored in a sparse set, or one of
+    /// them belongs to another group.
+    pub fn create_group(&mut self, types: &[TypeId]) {
+        let mut types = types.to_vec();
+        types.sort_unstable();
+        assert!(
+            types.windows(2).all(|pair| pair[0] != pair[1]),
+            "A group names each component type once"
+        );
+        if self.find_group(&types).is_some() {
+            return;
+        }
+        assert!(
+            types.len() >= 2,
+            "A group owns at least two component types"
+        );
+        for type_id in &types {
+            let name = if let Some(storage) = self.storages.get_mut(type_id) {
+                storage.get_mut().type_name()
+            } else {
+                assert!(
+                    self.tables.stores(*type_id),
+                    "Only registered components can be grouped"
+                );
+                "A table component"
+            };
+            assert!(
+                !self.group_of.contains_key(type_id),
+                "{name} already belongs to another group"
+            );
+        }
+        let owned: Vec<TypeId> = types
+            .iter()
+            .copied()
+            .filter(|type_id| self.storages.contains_key(type_id))
+            .collect();
+        assert!(
+            !owned.is_empty(),
+            "A group needs a component stored in a sparse set to order its entities"
+        );
+        let index = self.groups.len();
+        for &type_id in &types {
+            self.group_of.insert(type_id, index);
+        }
+        self.groups.push(Group {
+            types,
+            owned,
+            len: 0,
+        });
+        self.pack_group(index);
+    }
+
+    /// The entities of the group of exactly `types`, in the order shared by
+    /// its sparse sets.
+    #[must_use]
+    pub fn group_entities(&self, types: &[TypeId]) -> Option<&[Entity]> {
+        let mut types = types.to_vec();
+        types.sort_unstable();
+        let group = &self.groups[self.find_group(&types)?];
+        // SAFETY: see `get_sparse_set`.
+        let storage = unsafe { &*self.storages[&group.owned[0]].get() };
+        Some(&storage.entities()[..group.len])
+    }
+
+    /// Sorts the components of `T` with `compare`, stable, keeping every
+    /// lookup valid. In a group, the grouped entities are sorted among
+    /// themselves and the other owned sets follow their order; the entities
+    /// outside the group are sorted after them. Table components are sorted
+    /// within their tables and leave the group order as it is.
+    pub fn sort_by<T: Send + Sync + 'static>(
+        &mut self,
+        mut compare: impl FnMut(&T, &T) -> Ordering,
+    ) {
+        let type_id = TypeId::of::<T>();
+        self.mark_modified(type_id);
+        if self.tables.stores(type_id) {
+            self.tables.sort_by(compare);
+            return;
+        }
+        let group = self
+            .group_of
+            .get(&type_id)
+            .map(|&index| &self.groups[index]);
+        let grouped = group.map_or(0, |group| group.len);
+        let others: Vec<TypeId> = group
+            .map(|group| group.owned.clone())
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|&other| other != type_id)
+            .collect();
+        let Some(sparse_set) = self.get_mut_sparse_set::<T>() else {
+            return;
+        };
+        let group_order = sort_order(&sparse_set.dense[..grouped], &mut compare);
+        let rest_order = sort_order(&sparse_set.dense[grouped..], &mut compare);
+        sparse_set.permute(0, &group_order);
+        sparse_set.permute(grouped, &rest_order);
+        for other in others {
+            self.mark_modified(other);
+            self.storage_mut(other).permute(0, &group_order);
+        }
+    }
+
+    /// Moves the entity into the group of `type_id`, if it now has all of its
+    /// types. To be called after adding a component of `type_id`.
+    pub(super) fn enter_group(&mut self, type_id: TypeId, entity: Entity) {
+        let Some(&index) = self.group_of.get(&type_id) else {
+            return;
+        };
+        let Group { types, owned, len } = self.groups[index].clone();
+        let has_unowned = types
+            .iter()
+            .filter(|type_id| !owned.contains(type_id))
+            .all(|&type_id| self.contains(type_id, entity));
+        if !has_unowned {
+            return;
+        }
+        let mut rows = Vec::with_capacity(owned.len());
+        for &owned in &owned {
+            match self.storage_mut(owned).row(entity) {
+                Some(row) if row >= len => rows.push(row),
+                _ => return,
+            }
+        }
+        for (&owned, row) in owned.iter().zip(rows) {
+            self.mark_modified(owned);
+            self.storage_mut(owned).swap_rows(row, len);
+        }
+        self.groups[index].len += 1;
+    }
+
+    /// Moves the entity out of the group of `type_id`, if it is in it. To be
+    /// called before removing a component of `type_id`.
+    pub(super) fn leave_group(&mut self, type_id: TypeId, entity: Entity) {
+        if let Some(&index) = self.group_of.get(&type_id) {
+            self.leave_group_at(index, entity);
+        }
+    }
+
+    /// Moves the entity out of every group, before removing all of its
+    /// components.
+    pub(super) fn leave_groups(&mut self, entity: Entity) {
+        for index in 0..self.groups.len() {
+            self.leave_group_at(index, entity);
+        }
+    }
+
+    /// Repacks every group, e.g. after the sparse sets were restored from a
+    /// snapshot.
+    pub(super) fn rebuild_groups(&mut self) {
+        for index in 0..self.groups.len() {
+            self.pack_group(index);
+        }
+    }
+
+    fn leave_group_at(&mut self, index: usize, entity: Entity) {
+        let Group { owned, len, .. } = self.groups[index].clone();
+        let grouped = self
+            .storage_mut(owned[0])
+            .row(entity)
+            .is_some_and(|row| row < len);
+        if !grouped {
+            return;
+        }
+        let last = len - 1;
+        for &owned in &owned {
+            self.mark_modified(owned);
+            let storage = self.storage_mut(owned);
+            if let Some(row) = storage.row(entity) {
+                storage.swap_rows(row, last);
+            }
+        }
+        self.groups[index].len = last;
+    }
+
+    fn pack_group(&mut self, index: usize) {
+        self.groups[index].len = 0;
+        let first = self.groups[index].owned[0];
+        let entities = self.storage_mut(first).entities().to_vec();
+        for entity in entities {
+            self.enter_group(first, entity);
+        }
+    }
+
+    fn find_group(&self, sorted_types: &[TypeId]) -> Option<usize> {
+        let &index = self.group_of.get(sorted_types.first()?)?;
+        (self.groups[index].types == sorted_types).then_some(index)
+    }
+
+    fn storage_mut(&mut self, type_id: TypeId) -> &mut dyn Component {
+        self.storages
+            .get_mut(&type_id)
+            .expect("Internal error: grouped types keep their sparse set")
+            .get_mut()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::any::TypeId;
+
+    use crate::entity::Entity;
+    use crate::entity::component_storage::ComponentStorage;
+
+    fn entity(index: usize) -> Entity {
+        Entity::new(index, 0)
+    }
+
+    #[test]
+    fn test_group_keeps_owned_sets_aligned() {
+        let mut storage = ComponentStorage::new(16);
+        storage.register_component_type::<u32>();
+        storage.register_component_type::<i64>();
+        for index in 0..6 {
+            storage.add_component(entity(index), index as u32);
+        }
+        for index in [5, 1, 3] {
+            storage.add_component(entity(index), -(index as i64));
+        }
+        let types = [TypeId::of::<u32>(), TypeId::of::<i64>()];
+        storage.create_group(&types);
+        storage.add_component(entity(0), 0_i64);
+        storage.remove_component::<u32>(entity(3));
+        storage.remove_entities(&[entity(5)]);
+
+        let grouped = storage.group_entities(&types).unwrap().to_vec();
+        assert_eq!(grouped.len(), 2);
+        let u32s = storage.get_sparse_set::<u32>().unwrap();
+        let i64s = storage.get_sparse_set::<i64>().unwrap();
+        assert_eq!(u32s.entities[..2], grouped);
+        assert_eq!(i64s.entities[..2], grouped);
+        for &entity in &grouped {
+            assert_eq!(
+                *storage.get_component::<i64>(entity).unwrap(),
+                -i64::from(*storage.get_component::<u32>(entity).unwrap())
+            );
+        }
+    }
+
+    #[test]
+    fn test_sort_grouped_sparse_set() {
+        let mut storage = ComponentStorage::new(16);
+        for index in 0..5 {
+            storage.add_component(entity(index), 10 - index as u32);
+            if index != 2 {
+                storage.add_component(entity(index), index as i64);
+            }
+        }
+        let types = [TypeId::of::<i64>(), TypeId::of::<u32>()];
+        storage.create_group(&types);
+        storage.sort_by::<u32>(u32::cmp);
+
+        let grouped = storage.group_entities(&types).unwrap();
+        assert_eq!(grouped, [entity(4), entity(3), entity(1), entity(0)]);
+        let u32s = storage.get_sparse_set::<u32>().unwrap();
+        assert_eq!(u32s.dense, [6, 7, 9, 10, 8]);
+        assert_eq!(storage.get_sparse_set::<i64>().unwrap().dense, [4, 3, 1, 0]);
+        assert_eq!(storage.get_component::<u32>(entity(2)), Some(&8));
+    }
+}
@@ -0,0 +1,68 @@
+use std::any::TypeId;
+
+use super::ComponentStorage;
+use super::table::Table;
+use crate::entity::Entity;
+use crate::entity::name::Name;
+
+impl ComponentStorage {
+    /// Entities whose [`Name`] is `name`.
+    #[must_use]
+    pub fn entities_named(&self, name: &str) -> &[Entity] {
+        self.names.get(name).map_or(&[], Vec::as_slice)
+    }
+
+    /// Drops the current names of the entities from the index if `type_id` is
+    /// [`Name`]. To be called before their names are replaced or removed.
+    pub(super) fn unindex_names(&mut self, type_id: TypeId, entities: &[Entity]) {
+        if type_id != TypeId::of::<Name>() || self.names.is_empty() {
+            return;
+        }
+        for &entity in entities {
+            let Some(name) = self.get_component::<Name>(entity) else {
+                continue;
+            };
+            let name = name.as_str().to_owned();
+            if let Some(named) = self.names.get_mut(&name) {
+                named.retain(|&named| named != entity);
+                if named.is_empty() {
+                    self.names.remove(&name);
+                }
+            }
+        }
+    }
+
+    /// Indexes the names of the entities if `type_id` is [`Name`]. To be
+    /// called after their names are inserted.
+    pub(super) fn index_names(&mut self, type_id: TypeId, entities: &[Entity]) {
+        if type_id != TypeId::of::<Name>() {
+            return;
+        }
+        for &entity in entities {
+            if let Some(name) = self.get_component::<Name>(entity) {
+                let name = name.as_str().to_owned();
+                self.names.entry(name).or_default().push(entity);
+            }
+        }
+    }
+
+    /// Indexes every stored name again, e.g. after restoring a snapshot.
+    pub(super) fn rebuild_name_index(&mut self) {
+        self.names.clear();
+        let type_id = TypeId::of::<Name>();
+        let named: Vec<Entity> = if self.tables.stores(type_id) {
+            self.tables
+                .tables()
+                .iter()
+                .flat_map(Table::entities)
+                .copied()
+                .filter(|&entity| self.tables.contains(type_id, entity))
+                .collect()
+        } else {
+            self.get_sparse_set::<Name>()
+                .map(|names| names.entities.clone())
+                .unwrap_or_default()
+        };
+        self.index_names(type_id, &named);
+    }
+}
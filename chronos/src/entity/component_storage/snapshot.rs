@@ -0,0 +1,387 @@
+use std::{
+    any::TypeId,
+    cell::UnsafeCell,
+    collections::HashMap,
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+};
+
+use super::dynamic::DynamicStorage;
+use super::table::{Table, Tables};
+use super::{Component, ComponentStorage, SparseSet};
+use crate::entity::Entity;
+
+/// Copy of every component at one point in time. Storages that did not change
+/// between two snapshots are shared by both instead of being copied again.
+#[derive(Clone)]
+pub struct StorageSnapshot {
+    storages: HashMap<TypeId, SharedStorage>,
+    tables: Arc<Tables>,
+    dynamic: Arc<DynamicStorage>,
+}
+
+#[derive(Clone)]
+struct SharedStorage {
+    copy: Arc<dyn Component>,
+    restore: fn(&dyn Component) -> Box<UnsafeCell<dyn Component>>,
+}
+
+/// Clone functions of a sparse set type registered with
+/// [`ComponentStorage::register_clone`].
+#[derive(Clone, Copy)]
+pub(super) struct Cloner {
+    share: fn(&dyn Component) -> Arc<dyn Component>,
+    restore: fn(&dyn Component) -> Box<UnsafeCell<dyn Component>>,
+}
+
+/// The copy a live storage was last snapshotted to or restored from, kept
+/// until the storage is modified.
+pub(super) struct SharedCopy<S: ?Sized> {
+    modified: AtomicBool,
+    copy: Option<Arc<S>>,
+}
+
+/// Copies a component into or out of a snapshot. Every `Clone` type
+/// implements it; the hierarchy components implement only this, so that
+/// nothing outside the hierarchy API can duplicate them.
+pub(crate) trait SnapshotClone {
+    fn snapshot_clone(&self) -> Self;
+}
+
+impl<T: Clone> SnapshotClone for T {
+    fn snapshot_clone(&self) -> Self {
+        self.clone()
+    }
+}
+
+impl ComponentStorage {
+    /// Lets the components of `T` be snapshotted.
+    pub fn register_clone<T: Clone + Send + Sync + 'static>(&mut self) {
+        self.register_snapshot_clone::<T>();
+    }
+
+    /// [`ComponentStorage::register_clone`] for types that are only
+    /// [`SnapshotClone`].
+    pub(crate) fn register_snapshot_clone<T: SnapshotClone + Send + Sync + 'static>(&mut self) {
+        self.cloners.insert(
+            TypeId::of::<T>(),
+            Cloner {
+                share: share_sparse_set::<T>,
+                restore: restore_sparse_set::<T>,
+            },
+        );
+        self.tables.register_clone::<T>();
+    }
+
+    /// Copies every storage that changed since the last snapshot, and shares
+    /// the others with it.
+    ///
+    /// # Errors
+    ///
+    /// Returns the name of a stored component type that was not registered
+    /// with [`ComponentStorage::register_clone`].
+    pub fn snapshot(&mut self) -> Result<StorageSnapshot, &'static str> {
+        let mut storages = HashMap::new();
+        for (type_id, storage) in &mut self.storages {
+            let storage = storage.get_mut();
+            let Some(cloner) = self.cloners.get(type_id) else {
+                if storage.len() == 0 {
+                    continue;
+                }
+                return Err(storage.type_name());
+            };
+            let shared = self.copies.entry(*type_id).or_default();
+            let copy = shared.get_or_insert_with(|| (cloner.share)(&*storage));
+            storages.insert(
+                *type_id,
+                SharedStorage {
+                    copy,
+                    restore: cloner.restore,
+                },
+            );
+        }
+        let tables = if let Some(tables) = self.tables_copy.get() {
+            tables
+        } else {
+            let tables = Arc::new(self.tables.try_clone()?);
+            self.tables_copy.set(tables.clone());
+            tables
+        };
+        let dynamic = self
+            .dynamic_copy
+            .get_or_insert_with(|| Arc::new(self.dynamic.clone()));
+        Ok(StorageSnapshot {
+            storages,
+            tables,
+            dynamic,
+        })
+    }
+
+    /// Replaces every component with the ones of `snapshot`. Storages still
+    /// matching the snapshot are left untouched. No hooks run.
+    ///
+    /// Storage kinds are not rolled back: components of a type moved to or
+    /// from tables since the snapshot are restored into its current storage,
+    /// as if added at the current tick.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the snapshot's tables cannot be copied, which would be an
+    /// internal error since they were copied once already.
+    pub fn restore(&mut self, snapshot: &StorageSnapshot) {
+        for (type_id, storage) in &mut self.storages {
+            if !snapshot.storages.contains_key(type_id) {
+                storage.get_mut().clear();
+                self.copies.remove(type_id);
+            }
+        }
+        for (type_id, shared) in &snapshot.storages {
+            let copy = self.copies.entry(*type_id).or_default();
+            if copy.matches(&shared.copy) && self.storages.contains_key(type_id) {
+                continue;
+            }
+            self.storages
+                .insert(*type_id, (shared.restore)(&*shared.copy));
+            copy.set(shared.copy.clone());
+        }
+        if !self.tables_copy.matches(&snapshot.tables) {
+            let mut tables = snapshot
+                .tables
+                .try_clone()
+                .expect("Internal error: snapshotted tables are cloneable");
+            tables.copy_registration(&self.tables);
+            self.tables = tables;
+            self.tables_copy.set(snapshot.tables.clone());
+        }
+        if !self.dynamic_copy.matches(&snapshot.dynamic) {
+            self.dynamic = (*snapshot.dynamic).clone();
+            self.dynamic_copy.set(snapshot.dynamic.clone());
+        }
+        self.move_to_registered_kinds();
+        self.rebuild_groups();
+        self.rebuild_name_index();
+    }
+}
+
+impl ComponentStorage {
+    /// Moves restored components whose type was registered with another
+    /// [`StorageKind`](super::StorageKind) since the snapshot into the storage
+    /// the type uses now.
+    fn move_to_registered_kinds(&mut self) {
+        let triggered_hooks = self.triggered_hooks.len();
+        // Entities leave tables with unregistered columns first, so that no
+        // table is ever looked up with such a column again.
+        let from_tables: Vec<Entity> = self
+            .tables
+            .tables()
+            .iter()
+            .filter(|table| {
+                table
+                    .types()
+                    .iter()
+                    .any(|&type_id| !self.tables.stores(type_id))
+            })
+            .flat_map(Table::entities)
+            .copied()
+            .collect();
+        for entity in from_tables {
+            self.tables_copy.mark_modified();
+            for component in self.tables.take_entity(entity) {
+                component.insert_into(self, entity);
+            }
+        }
+
+        let to_tables: Vec<TypeId> = self
+            .storages
+            .keys()
+            .copied()
+            .filter(|&type_id| self.tables.stores(type_id))
+            .collect();
+        for type_id in to_tables {
+            self.copies.remove(&type_id);
+            let Some(mut storage) = self.storages.remove(&type_id) else {
+                continue;
+            };
+            let storage = storage.get_mut();
+            for entity in storage.entities().to_vec() {
+                if let Some(component) = storage.take_component(entity) {
+                    component.insert_into(self, entity);
+                }
+            }
+        }
+        self.triggered_hooks.truncate(triggered_hooks);
+    }
+}
+
+impl<S: ?Sized> SharedCopy<S> {
+    pub fn mark_modified(&self) {
+        self.modified.store(true, Ordering::Relaxed);
+    }
+
+    /// The shared copy, if the storage was not modified since it was made.
+    fn get(&mut self) -> Option<Arc<S>> {
+        if *self.modified.get_mut() {
+            return None;
+        }
+        self.copy.clone()
+    }
+
+    fn get_or_insert_with(&mut self, copy: impl FnOnce() -> Arc<S>) -> Arc<S> {
+        self.get().unwrap_or_else(|| {
+            let copy = copy();
+            self.set(copy.clone());
+            copy
+        })
+    }
+
+    fn set(&mut self, copy: Arc<S>) {
+        self.copy = Some(copy);
+        *self.modified.get_mut() = false;
+    }
+
+    /// Whether the storage is unchanged since it was copied to or from `other`.
+    fn matches(&mut self, other: &Arc<S>) -> bool {
+        self.get()
+            .is_some_and(|copy| std::ptr::addr_eq(Arc::as_ptr(&copy), Arc::as_ptr(other)))
+    }
+}
+
+impl<S: ?Sized> Default for SharedCopy<S> {
+    fn default() -> Self {
+        Self {
+            modified: AtomicBool::new(false),
+            copy: None,
+        }
+    }
+}
+
+fn sparse_set<T: Send + Sync + 'static>(storage: &dyn Component) -> &SparseSet<T> {
+    storage
+        .as_any()
+        .downcast_ref::<SparseSet<T>>()
+        .expect("Internal error: cloner registered for another type")
+}
+
+fn share_sparse_set<T: SnapshotClone + Send + Sync + 'static>(
+    storage: &dyn Component,
+) -> Arc<dyn Component> {
+    Arc::new(sparse_set::<T>(storage).clone_for_snapshot())
+}
+
+fn restore_sparse_set<T: SnapshotClone + Send + Sync + 'static>(
+    storage: &dyn Component,
+) -> Box<UnsafeCell<dyn Component>> {
+    Box::new(UnsafeCell::new(
+        sparse_set::<T>(storage).clone_for_snapshot(),
+    ))
+}
+
+impl<T: SnapshotClone> SparseSet<T> {
+    fn clone_for_snapshot(&self) -> Self {
+        Self {
+            sparse: self.sparse.clone(),
+            dense: self
+                .dense
+                .iter()
+                .map(SnapshotClone::snapshot_clone)
+                .collect(),
+            entities: self.entities.clone(),
+            added: self.added.clone(),
+            changed: self.changed.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{any::TypeId, sync::Arc};
+
+    use super::StorageSnapshot;
+    use crate::entity::Entity;
+    use crate::entity::component_storage::{ComponentStorage, StorageConfig, StorageKind};
+
+    fn shares<T: 'static>(first: &StorageSnapshot, second: &StorageSnapshot) -> bool {
+        let type_id = TypeId::of::<T>();
+        std::ptr::addr_eq(
+            Arc::as_ptr(&first.storages[&type_id].copy),
+            Arc::as_ptr(&second.storages[&type_id].copy),
+        )
+    }
+
+    #[test]
+    fn test_snapshot_shares_unchanged_storages() {
+        let mut storage = ComponentStorage::new(10);
+        storage.register_clone::<u32>();
+        storage.register_clone::<String>();
+        storage.register_clone::<char>();
+        storage.register_component_type_with::<char>(StorageConfig {
+            kind: StorageKind::Table,
+            ..StorageConfig::DEFAULT
+        });
+        let entity = Entity::new(0, 0);
+        storage.add_component(entity, 1_u32);
+        storage.add_component(entity, "first".to_string());
+        storage.add_component(entity, 'a');
+
+        let first = storage.snapshot().unwrap();
+        let second = storage.snapshot().unwrap();
+        assert!(shares::<u32>(&first, &second));
+        assert!(Arc::ptr_eq(&first.tables, &second.tables));
+
+        storage
+            .get_component_mut::<String>(entity)
+            .unwrap()
+            .push('!');
+        let third = storage.snapshot().unwrap();
+        assert!(shares::<u32>(&second, &third));
+        assert!(!shares::<String>(&second, &third));
+        assert!(Arc::ptr_eq(&second.tables, &third.tables));
+
+        storage.restore(&first);
+        assert_eq!(
+            storage.get_component::<String>(entity).map(String::as_str),
+            Some("first")
+        );
+        assert!(shares::<String>(&first, &storage.snapshot().unwrap()));
+
+        // SAFETY: no other view of `char` is alive.
+        unsafe { storage.view_mut::<char>(storage.change_tick()) };
+        assert!(!Arc::ptr_eq(
+            &first.tables,
+            &storage.snapshot().unwrap().tables
+        ));
+    }
+
+    #[test]
+    fn test_restore_keeps_storage_kinds() {
+        let mut storage = ComponentStorage::new(10);
+        storage.register_clone::<u32>();
+        storage.register_clone::<char>();
+        storage.register_component_type_with::<char>(StorageConfig {
+            kind: StorageKind::Table,
+            ..StorageConfig::DEFAULT
+        });
+        let entity = Entity::new(0, 0);
+        storage.add_component(entity, 1_u32);
+        storage.add_component(entity, 'a');
+        let snapshot = storage.snapshot().unwrap();
+
+        storage.remove_component::<u32>(entity);
+        storage.remove_component::<char>(entity);
+        storage.register_component_type_with::<u32>(StorageConfig {
+            kind: StorageKind::Table,
+            ..StorageConfig::DEFAULT
+        });
+        storage.register_component_type::<char>();
+        storage.restore(&snapshot);
+
+        assert!(storage.tables.stores(TypeId::of::<u32>()));
+        assert!(!storage.tables.stores(TypeId::of::<char>()));
+        assert!(!storage.storages.contains_key(&TypeId::of::<u32>()));
+        assert_eq!(storage.get_component::<u32>(entity), Some(&1));
+        assert_eq!(storage.get_component::<char>(entity), Some(&'a'));
+        assert_eq!(storage.get_sparse_set::<char>().unwrap().dense, ['a']);
+    }
+}
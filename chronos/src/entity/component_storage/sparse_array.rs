@@ -0,0 +1,203 @@
+use std::collections::HashMap;
+
+/// Number of entity indices covered by one page.
+pub const PAGE_SIZE: usize = 256;
+
+/// Page indices below this live in a table indexed directly; pages of larger
+/// entity indices are kept in a map so their page table is not allocated.
+const MAX_TABLE_PAGES: usize = 1 << 16;
+
+type Page<V> = Box<[Option<V>; PAGE_SIZE]>;
+
+/// Map from entity index to a value, by default the dense index of the
+/// entity's component, split into pages of [`PAGE_SIZE`] entries that are
+/// only allocated once an entity in their range is inserted.
+#[derive(Clone)]
+pub struct SparseArray<V = usize> {
+    table: Vec<Option<Page<V>>>,
+    far_pages: HashMap<usize, Page<V>>,
+}
+
+impl<V> Default for SparseArray<V> {
+    fn default() -> Self {
+        Self {
+            table: Vec::new(),
+            far_pages: HashMap::new(),
+        }
+    }
+}
+
+impl<V: Copy> SparseArray<V> {
+    /// Reserves room in the page table for entity indices below `capacity`,
+    /// without allocating any page.
+    pub fn with_capacity(capacity: usize) -> Self {
+        let pages = capacity.div_ceil(PAGE_SIZE).min(MAX_TABLE_PAGES);
+        Self {
+            table: Vec::with_capacity(pages),
+            far_pages: HashMap::new(),
+        }
+    }
+
+    pub fn get(&self, index: usize) -> Option<V> {
+        let (page, offset) = split(index);
+        self.page(page)?[offset]
+    }
+
+    pub fn insert(&mut self, index: usize, value: V) {
+        let (page, offset) = split(index);
+        self.page_mut(page)[offset] = Some(value);
+    }
+
+    pub fn remove(&mut self, index: usize) {
+        let (page, offset) = split(index);
+        let entry = if page < MAX_TABLE_PAGES {
+            self.table.get_mut(page).and_then(Option::as_mut)
+        } else {
+            self.far_pages.get_mut(&page)
+        };
+        if let Some(entry) = entry {
+            entry[offset] = None;
+        }
+    }
+
+    /// Reserves room in the page table for entity indices below `capacity`.
+    pub fn reserve(&mut self, capacity: usize) {
+        let pages = capacity.div_ceil(PAGE_SIZE).min(MAX_TABLE_PAGES);
+        self.table.reserve(pages.saturating_sub(self.table.len()));
+    }
+
+    pub fn allocated_pages(&self) -> usize {
+        self.table.iter().flatten().count() + self.far_pages.len()
+    }
+
+    /// Number of entity indices covered by the allocated pages.
+    pub fn capacity(&self) -> usize {
+        self.allocated_pages() * PAGE_SIZE
+    }
+
+    /// Heap bytes of the page table and the allocated pages.
+    pub fn heap_bytes(&self) -> usize {
+        self.table.capacity() * size_of::<Option<Page<V>>>()
+            + self.allocated_pages() * size_of::<[Option<V>; PAGE_SIZE]>()
+            + self.far_pages.capacity() * size_of::<(usize, Page<V>)>()
+    }
+
+    /// Frees the pages left without entries, e.g. after mass despawns, and
+    /// the part of the page table past the last allocated page.
+    pub fn shrink_to_fit(&mut self) {
+        for page in &mut self.table {
+            if page
+                .as_ref()
+                .is_some_and(|page| page.iter().all(Option::is_none))
+            {
+                *page = None;
+            }
+        }
+        while self.table.last().is_some_and(Option::is_none) {
+            self.table.pop();
+        }
+        self.table.shrink_to_fit();
+        self.far_pages
+            .retain(|_, page| page.iter().any(Option::is_some));
+        self.far_pages.shrink_to_fit();
+    }
+
+    fn page(&self, page: usize) -> Option<&[Option<V>; PAGE_SIZE]> {
+        if page < MAX_TABLE_PAGES {
+            self.table.get(page)?.as_deref()
+        } else {
+            self.far_pages.get(&page).map(|page| &**page)
+        }
+    }
+
+    fn page_mut(&mut self, page: usize) -> &mut [Option<V>; PAGE_SIZE] {
+        if page < MAX_TABLE_PAGES {
+            if page >= self.table.len() {
+                self.table.resize_with(page + 1, || None);
+            }
+            self.table[page].get_or_insert_with(|| Box::new([None; PAGE_SIZE]))
+        } else {
+            self.far_pages
+                .entry(page)
+                .or_insert_with(|| Box::new([None; PAGE_SIZE]))
+        }
+    }
+}
+
+fn split(index: usize) -> (usize, usize) {
+    (index / PAGE_SIZE, index % PAGE_SIZE)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{PAGE_SIZE, SparseArray};
+
+    #[test]
+    fn test_sparse_array_allocates_pages_lazily() {
+        let mut sparse = SparseArray::with_capacity(10 * PAGE_SIZE);
+        assert_eq!(sparse.allocated_pages(), 0);
+
+        sparse.insert(3, 0);
+        sparse.insert(5, 1);
+        sparse.insert(7 * PAGE_SIZE + 2, 2);
+
+        assert_eq!(sparse.allocated_pages(), 2);
+        assert_eq!(sparse.get(3), Some(0));
+        assert_eq!(sparse.get(5), Some(1));
+        assert_eq!(sparse.get(7 * PAGE_SIZE + 2), Some(2));
+        assert_eq!(sparse.get(4), None);
+        assert_eq!(sparse.get(3 * PAGE_SIZE), None);
+    }
+
+    #[test]
+    fn test_sparse_array_large_indices() {
+        let mut sparse = SparseArray::default();
+
+        sparse.insert(usize::MAX, 0);
+        sparse.insert(1 << 40, 1);
+        sparse.insert(10_000, 2);
+
+        assert_eq!(sparse.get(usize::MAX), Some(0));
+        assert_eq!(sparse.get(1 << 40), Some(1));
+        assert_eq!(sparse.get(10_000), Some(2));
+        assert_eq!(sparse.get((1 << 40) + 1), None);
+        assert_eq!(sparse.allocated_pages(), 3);
+        assert!(sparse.table.len() <= 10_000 / PAGE_SIZE + 1);
+    }
+
+    #[test]
+    fn test_sparse_array_shrink_to_fit() {
+        let mut sparse = SparseArray::default();
+        for index in 0..4 * PAGE_SIZE {
+            sparse.insert(index, index);
+        }
+        sparse.insert(usize::MAX, 0);
+        for index in PAGE_SIZE..4 * PAGE_SIZE {
+            sparse.remove(index);
+        }
+        sparse.remove(usize::MAX);
+        let bytes = sparse.heap_bytes();
+
+        sparse.shrink_to_fit();
+        assert_eq!(sparse.capacity(), PAGE_SIZE);
+        assert!(sparse.heap_bytes() < bytes);
+        assert_eq!(sparse.table.len(), 1);
+        assert_eq!(sparse.get(5), Some(5));
+        sparse.insert(3 * PAGE_SIZE, 1);
+        assert_eq!(sparse.get(3 * PAGE_SIZE), Some(1));
+    }
+
+    #[test]
+    fn test_sparse_array_remove() {
+        let mut sparse = SparseArray::default();
+        sparse.insert(42, (7, 1));
+
+        sparse.remove(42);
+        sparse.remove(usize::MAX);
+
+        assert_eq!(sparse.get(42), None);
+        assert_eq!(sparse.get(usize::MAX), None);
+        sparse.insert(43, (8, 2));
+        assert_eq!(sparse.get(43), Some((8, 2)));
+    }
+}
@@ -0,0 +1,116 @@
+use super::ComponentStorage;
+
+/// Memory use of the storage of one component type.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ComponentStats {
+    /// Rust type name, or layout name for dynamic components.
+    pub type_name: String,
+    /// Number of stored components.
+    pub dense_len: usize,
+    /// Entity indices covered by the allocated pages of the sparse array.
+    /// Table components share the array mapping entities to table rows.
+    pub sparse_capacity: usize,
+    /// Heap bytes of the components, their entity and tick arrays and the
+    /// sparse array. The shared array of table components is not counted.
+    pub bytes_used: usize,
+    /// Share of `sparse_capacity` that does not point to a component of the
+    /// type, 0 for an empty sparse array.
+    pub wasted_sparse_ratio: f64,
+}
+
+impl ComponentStats {
+    pub(super) fn new(
+        type_name: impl Into<String>,
+        dense_len: usize,
+        sparse_capacity: usize,
+        bytes_used: usize,
+    ) -> Self {
+        #[allow(clippy::cast_precision_loss)]
+        let wasted_sparse_ratio = if sparse_capacity == 0 {
+            0.0
+        } else {
+            1.0 - dense_len as f64 / sparse_capacity as f64
+        };
+        Self {
+            type_name: type_name.into(),
+            dense_len,
+            sparse_capacity,
+            bytes_used,
+            wasted_sparse_ratio,
+        }
+    }
+}
+
+impl ComponentStorage {
+    /// Statistics of every component type that has a storage, dynamic ones
+    /// included, sorted by type name.
+    #[must_use]
+    pub fn stats(&self) -> Vec<ComponentStats> {
+        let mut stats: Vec<ComponentStats> = self
+            .storages
+            .values()
+            // SAFETY: see `get_sparse_set`.
+            .map(|storage| unsafe { &*storage.get() }.stats())
+            .chain(self.tables.stats())
+            .chain(self.dynamic.stats())
+            .collect();
+        stats.sort_by(|a, b| a.type_name.cmp(&b.type_name));
+        stats
+    }
+
+    /// Releases the memory no stored component needs anymore: spare capacity
+    /// of the dense arrays and sparse pages left empty, e.g. after mass
+    /// despawns. Stored components, their order and snapshots are untouched.
+    pub fn shrink_to_fit(&mut self) {
+        for storage in self.storages.values_mut() {
+            storage.get_mut().shrink_to_fit();
+        }
+        self.tables.shrink_to_fit();
+        self.dynamic.shrink_to_fit();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::entity::Entity;
+    use crate::entity::component_storage::sparse_array::PAGE_SIZE;
+    use crate::entity::component_storage::{ComponentStorage, StorageConfig, StorageKind};
+
+    #[test]
+    fn test_stats_and_shrink_to_fit() {
+        let mut storage = ComponentStorage::new(16);
+        storage.register_component_type_with::<f32>(StorageConfig {
+            kind: StorageKind::Table,
+            ..StorageConfig::DEFAULT
+        });
+        let entities: Vec<Entity> = (0..2 * PAGE_SIZE)
+            .map(|index| Entity::new(index, 0))
+            .collect();
+        storage.extend_components(&entities, vec![7_u64; entities.len()]);
+        storage.add_component(entities[1], 1.0_f32);
+        storage.remove_entities(&entities[PAGE_SIZE..]);
+        storage.remove_entities(&entities[4..PAGE_SIZE]);
+
+        let before = storage.stats();
+        let u64_stats = before
+            .iter()
+            .find(|stats| stats.type_name == "u64")
+            .unwrap();
+        assert_eq!(u64_stats.dense_len, 4);
+        assert_eq!(u64_stats.sparse_capacity, 2 * PAGE_SIZE);
+        assert!(u64_stats.wasted_sparse_ratio > 0.99);
+        let f32_stats = before
+            .iter()
+            .find(|stats| stats.type_name == "f32")
+            .unwrap();
+        assert_eq!(f32_stats.dense_len, 1);
+
+        storage.shrink_to_fit();
+        let after = storage.stats();
+        let compacted = after.iter().find(|stats| stats.type_name == "u64").unwrap();
+        assert_eq!(compacted.sparse_capacity, PAGE_SIZE);
+        assert!(compacted.bytes_used < u64_stats.bytes_used);
+        assert_eq!(storage.get_component::<u64>(entities[3]), Some(&7));
+        assert_eq!(storage.get_component::<f32>(entities[1]), Some(&1.0));
+    }
+}
@@ -0,0 +1,686 @@
+use std::{
+    any::{Any, TypeId},
+    cell::UnsafeCell,
+    collections::HashMap,
+};
+
+use super::snapshot::SnapshotClone;
+use super::sparse_array::SparseArray;
+use super::{ComponentStats, ErasedComponent, RawColumn, Tick, apply_permutation, sort_order};
+use crate::entity::Entity;
+
+/// Storage of every table-stored component type. Entities with the same set of
+/// table-stored components share a [`Table`], whose columns hold their
+/// components in the same row, so joint iteration walks contiguous arrays.
+#[derive(Default)]
+pub struct Tables {
+    by_index: Vec<Table>,
+    by_types: HashMap<Vec<TypeId>, usize>,
+    locations: SparseArray<TableRow>,
+    column_constructors: HashMap<TypeId, fn() -> Box<dyn Column>>,
+    column_cloners: HashMap<TypeId, ColumnCloner>,
+}
+
+/// Entities owning exactly the table-stored component types in `types`.
+pub struct Table {
+    types: Vec<TypeId>,
+    entities: Vec<Entity>,
+    columns: HashMap<TypeId, UnsafeCell<Box<dyn Column>>>,
+}
+
+type ColumnCloner = fn(&dyn Column) -> Box<dyn Column>;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct TableRow {
+    pub table: usize,
+    pub row: usize,
+}
+
+pub trait Column: Send + Sync {
+    fn type_name(&self) -> &'static str;
+    /// New column of the same type without any value.
+    fn empty(&self) -> Box<dyn Column>;
+    fn swap_remove(&mut self, row: usize);
+    /// Takes the value of `row` out, filling the hole with the last value.
+    fn take(&mut self, row: usize) -> ErasedComponent;
+    /// Moves the value of `row` to the end of `target`, a column of the same
+    /// type, filling the hole with the last value.
+    fn move_row(&mut self, row: usize, target: &mut dyn Column);
+    fn get_any(&self, row: usize) -> &dyn Any;
+    /// Reorders the rows so that row `i` holds what row `order[i]` held.
+    fn permute(&mut self, order: &[usize]);
+    /// Heap bytes of the values and their ticks.
+    fn heap_bytes(&self) -> usize;
+    fn shrink_to_fit(&mut self);
+    /// Returns the value of `row` mutably and marks it as changed at `tick`.
+    fn get_any_mut(&mut self, row: usize, tick: Tick) -> &mut dyn Any;
+    fn as_any(&self) -> &dyn Any;
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+}
+
+#[derive(Clone)]
+pub struct TableColumn<T> {
+    pub values: Vec<T>,
+    pub added: Vec<Tick>,
+    pub changed: Vec<Tick>,
+}
+
+// SAFETY: columns only hold `Send + Sync` component types, and are accessed
+// through their cells under the same rules as the sparse sets.
+unsafe impl Sync for Table {}
+
+impl Tables {
+    pub fn register<T: Send + Sync + 'static>(&mut self) {
+        self.column_constructors
+            .entry(TypeId::of::<T>())
+            .or_insert(new_column::<T>);
+    }
+
+    /// Lets tables holding `T` be copied by [`Tables::try_clone`].
+    pub fn register_clone<T: SnapshotClone + Send + Sync + 'static>(&mut self) {
+        self.column_cloners
+            .insert(TypeId::of::<T>(), clone_column::<T>);
+    }
+
+    /// Copies every table, or returns the name of a stored type that was not
+    /// registered with [`Tables::register_clone`].
+    pub fn try_clone(&self) -> Result<Self, &'static str> {
+        let by_index = self
+            .by_index
+            .iter()
+            .map(|table| table.try_clone(&self.column_cloners))
+            .collect::<Result<_, _>>()?;
+        Ok(Self {
+            by_index,
+            by_types: self.by_types.clone(),
+            locations: self.locations.clone(),
+            column_constructors: self.column_constructors.clone(),
+            column_cloners: self.column_cloners.clone(),
+        })
+    }
+
+    /// Takes over the types stored in tables and their clone functions from
+    /// `other`, e.g. to keep the current registrations when tables are
+    /// restored from a snapshot.
+    pub fn copy_registration(&mut self, other: &Tables) {
+        self.column_constructors
+            .clone_from(&other.column_constructors);
+        self.column_cloners.clone_from(&other.column_cloners);
+    }
+
+    pub fn unregister(&mut self, type_id: TypeId) {
+        self.column_constructors.remove(&type_id);
+    }
+
+    pub fn stores(&self, type_id: TypeId) -> bool {
+        self.column_constructors.contains_key(&type_id)
+    }
+
+    /// Number of components of the type stored across all tables.
+    pub fn len_of(&self, type_id: TypeId) -> usize {
+        self.by_index
+            .iter()
+            .filter(|table| table.columns.contains_key(&type_id))
+            .map(|table| table.entities.len())
+            .sum()
+    }
+
+    pub fn reserve(&mut self, sparse_capacity: usize) {
+        self.locations.reserve(sparse_capacity);
+    }
+
+    pub fn tables(&self) -> &[Table] {
+        &self.by_index
+    }
+
+    pub fn locations(&self) -> &SparseArray<TableRow> {
+        &self.locations
+    }
+
+    /// Inserts the component, replacing the previous value in place, or moves
+    /// the entity to the table that also has a `T` column.
+    pub fn insert<T: Send + Sync + 'static>(
+        &mut self,
+        entity: Entity,
+        component: T,
+        tick: Tick,
+    ) -> Option<T> {
+        let type_id = TypeId::of::<T>();
+        let location = self.location(entity);
+        let types = match location {
+            Some(location) => {
+                let table = &mut self.by_index[location.table];
+                if let Some(column) = table.column_mut::<T>() {
+                    column.changed[location.row] = tick;
+                    return Some(std::mem::replace(
+                        &mut column.values[location.row],
+                        component,
+                    ));
+                }
+                let mut types = table.types.clone();
+                let position = types.binary_search(&type_id).unwrap_err();
+                types.insert(position, type_id);
+                types
+            }
+            None => vec![type_id],
+        };
+
+        let target = self.table_for(types);
+        let row = if let Some(location) = location {
+            self.move_entity(entity, location, Some(target), None)
+        } else {
+            let table = &mut self.by_index[target];
+            table.entities.push(entity);
+            table.entities.len() - 1
+        };
+        let column = self.by_index[target]
+            .column_mut::<T>()
+            .expect("Internal error: target table lacks the inserted column");
+        column.values.push(component);
+        column.added.push(tick);
+        column.changed.push(tick);
+        self.locations
+            .insert(entity.index(), TableRow { table: target, row });
+        None
+    }
+
+    /// Takes the entity's `T` out, moving the entity to the table without a
+    /// `T` column.
+    pub fn remove<T: Send + Sync + 'static>(&mut self, entity: Entity) -> Option<T> {
+        let type_id = TypeId::of::<T>();
+        let location = self.location(entity)?;
+        let table = &mut self.by_index[location.table];
+        let column = table.column_mut::<T>()?;
+        let component = column.values.swap_remove(location.row);
+        column.added.swap_remove(location.row);
+        column.changed.swap_remove(location.row);
+
+        let types: Vec<_> = table
+            .types
+            .iter()
+            .copied()
+            .filter(|&other| other != type_id)
+            .collect();
+        let target = (!types.is_empty()).then(|| self.table_for(types));
+        self.move_entity(entity, location, target, Some(type_id));
+        Some(component)
+    }
+
+    /// Drops all table-stored components of the entity, returning whether it
+    /// had any.
+    pub fn remove_entity(&mut self, entity: Entity) -> bool {
+        let Some(location) = self.location(entity) else {
+            return false;
+        };
+        self.move_entity(entity, location, None, None);
+        true
+    }
+
+    /// Takes every table-stored component out of the entity.
+    pub fn take_entity(&mut self, entity: Entity) -> Vec<ErasedComponent> {
+        let Some(location) = self.location(entity) else {
+            return Vec::new();
+        };
+        let table = &mut self.by_index[location.table];
+        let components = table
+            .columns
+            .values_mut()
+            .map(|column| column.get_mut().take(location.row))
+            .collect();
+        table.entities.swap_remove(location.row);
+        if let Some(&moved) = table.entities.get(location.row) {
+            self.locations.insert(moved.index(), location);
+        }
+        self.locations.remove(entity.index());
+        components
+    }
+
+    pub fn get<T: Send + Sync + 'static>(&self, entity: Entity) -> Option<&T> {
+        let location = self.location(entity)?;
+        self.by_index[location.table]
+            .column::<T>()
+            .map(|column| &column.values[location.row])
+    }
+
+    pub fn get_mut<T: Send + Sync + 'static>(
+        &mut self,
+        entity: Entity,
+        tick: Tick,
+    ) -> Option<&mut T> {
+        let location = self.location(entity)?;
+        let column = self.by_index[location.table].column_mut::<T>()?;
+        column.changed[location.row] = tick;
+        Some(&mut column.values[location.row])
+    }
+
+    pub fn get_dyn(&self, type_id: TypeId, entity: Entity) -> Option<&dyn Any> {
+        let location = self.location(entity)?;
+        let cell = self.by_index[location.table].columns.get(&type_id)?;
+        // SAFETY: see `Table::column`.
+        Some(unsafe { &*cell.get() }.get_any(location.row))
+    }
+
+    pub fn get_dyn_mut(
+        &mut self,
+        type_id: TypeId,
+        entity: Entity,
+        tick: Tick,
+    ) -> Option<&mut dyn Any> {
+        let location = self.location(entity)?;
+        let column = self.by_index[location.table].columns.get_mut(&type_id)?;
+        Some(column.get_mut().get_any_mut(location.row, tick))
+    }
+
+    /// Type IDs and names of the table components of the entity.
+    pub fn component_types(&self, entity: Entity) -> Vec<(TypeId, &'static str)> {
+        let Some(location) = self.location(entity) else {
+            return Vec::new();
+        };
+        self.by_index[location.table]
+            .columns
+            .iter()
+            .map(|(type_id, cell)| {
+                // SAFETY: see `Table::column`.
+                (*type_id, unsafe { &*cell.get() }.type_name())
+            })
+            .collect()
+    }
+
+    /// Sorts the rows of every table holding `T` by their `T`, stable.
+    pub fn sort_by<T: Send + Sync + 'static>(
+        &mut self,
+        mut compare: impl FnMut(&T, &T) -> std::cmp::Ordering,
+    ) {
+        for (index, table) in self.by_index.iter_mut().enumerate() {
+            let Some(column) = table.column::<T>() else {
+                continue;
+            };
+            let order = sort_order(&column.values, &mut compare);
+            apply_permutation(&mut table.entities, &order);
+            for column in table.columns.values_mut() {
+                column.get_mut().permute(&order);
+            }
+            for (row, entity) in table.entities.iter().enumerate() {
+                self.locations
+                    .insert(entity.index(), TableRow { table: index, row });
+            }
+        }
+    }
+
+    /// Statistics of each type with a column in some table. The entity rows
+    /// of a table are counted in each of its columns.
+    pub fn stats(&self) -> Vec<ComponentStats> {
+        let mut by_type: HashMap<TypeId, (&'static str, usize, usize)> = HashMap::new();
+        for table in &self.by_index {
+            let row_bytes = table.entities.capacity() * size_of::<Entity>();
+            for (type_id, column) in &table.columns {
+                // SAFETY: see `Table::column`.
+                let column = unsafe { &**column.get() };
+                let stats = by_type
+                    .entry(*type_id)
+                    .or_insert((column.type_name(), 0, 0));
+                stats.1 += table.entities.len();
+                stats.2 += column.heap_bytes() + row_bytes;
+            }
+        }
+        let sparse_capacity = self.locations.capacity();
+        by_type
+            .into_values()
+            .map(|(type_name, len, bytes)| {
+                ComponentStats::new(type_name, len, sparse_capacity, bytes)
+            })
+            .collect()
+    }
+
+    /// Shrinks every column and the location array to what they hold.
+    pub fn shrink_to_fit(&mut self) {
+        for table in &mut self.by_index {
+            table.entities.shrink_to_fit();
+            for column in table.columns.values_mut() {
+                column.get_mut().shrink_to_fit();
+            }
+        }
+        self.locations.shrink_to_fit();
+    }
+
+    pub fn contains(&self, type_id: TypeId, entity: Entity) -> bool {
+        self.location(entity)
+            .is_some_and(|location| self.by_index[location.table].columns.contains_key(&type_id))
+    }
+
+    fn location(&self, entity: Entity) -> Option<TableRow> {
+        table_row(
+            &self.locations,
+            |table| self.by_index.get(table).map(Table::entities),
+            entity,
+        )
+    }
+
+    /// Index of the table with exactly `types`, created if missing.
+    fn table_for(&mut self, types: Vec<TypeId>) -> usize {
+        if let Some(&index) = self.by_types.get(&types) {
+            return index;
+        }
+        let columns = types
+            .iter()
+            .map(|type_id| {
+                let constructor = self.column_constructors[type_id];
+                (*type_id, UnsafeCell::new(constructor()))
+            })
+            .collect();
+        self.by_index.push(Table {
+            types: types.clone(),
+            entities: Vec::new(),
+            columns,
+        });
+        self.by_types.insert(types, self.by_index.len() - 1);
+        self.by_index.len() - 1
+    }
+
+    /// Moves the entity's row to the end of `target`, dropping the values of
+    /// columns `target` lacks, or all of them if there is no target. The
+    /// `skip` column has already been removed by the caller. Returns the new
+    /// row.
+    fn move_entity(
+        &mut self,
+        entity: Entity,
+        location: TableRow,
+        target_index: Option<usize>,
+        skip: Option<TypeId>,
+    ) -> usize {
+        let (source, mut target) = match target_index {
+            Some(target) => {
+                let (source, target) = two_mut(&mut self.by_index, location.table, target);
+                (source, Some(target))
+            }
+            None => (&mut self.by_index[location.table], None),
+        };
+        for (type_id, column) in &mut source.columns {
+            if Some(*type_id) == skip {
+                continue;
+            }
+            let column = column.get_mut();
+            let target_column = target
+                .as_mut()
+                .and_then(|target| target.columns.get_mut(type_id));
+            if let Some(target_column) = target_column {
+                column.move_row(location.row, &mut **target_column.get_mut());
+            } else {
+                column.swap_remove(location.row);
+            }
+        }
+
+        source.entities.swap_remove(location.row);
+        if let Some(&moved) = source.entities.get(location.row) {
+            self.locations.insert(moved.index(), location);
+        }
+        if let (Some(target), Some(table)) = (target, target_index) {
+            target.entities.push(entity);
+            let row = target.entities.len() - 1;
+            self.locations
+                .insert(entity.index(), TableRow { table, row });
+            row
+        } else {
+            self.locations.remove(entity.index());
+            0
+        }
+    }
+}
+
+impl Table {
+    pub fn entities(&self) -> &[Entity] {
+        &self.entities
+    }
+
+    /// Component types of the table, sorted.
+    pub fn types(&self) -> &[TypeId] {
+        &self.types
+    }
+
+    /// Copies the table. Columns of an empty table are created anew, so only
+    /// tables holding components need their types to be cloneable.
+    fn try_clone(&self, cloners: &HashMap<TypeId, ColumnCloner>) -> Result<Self, &'static str> {
+        let columns = self
+            .columns
+            .iter()
+            .map(|(type_id, column)| {
+                // SAFETY: copies are made through `&mut ComponentStorage` or
+                // from a snapshot, so no query is writing to the column.
+                let column = unsafe { &**column.get() };
+                let copy = if self.entities.is_empty() {
+                    column.empty()
+                } else {
+                    cloners.get(type_id).ok_or_else(|| column.type_name())?(column)
+                };
+                Ok((*type_id, UnsafeCell::new(copy)))
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Self {
+            types: self.types.clone(),
+            entities: self.entities.clone(),
+            columns,
+        })
+    }
+
+    /// Arrays of the column of `T`, if the table has one.
+    ///
+    /// # Safety
+    ///
+    /// Same as [`ComponentStorage::view_mut`](super::ComponentStorage::view_mut)
+    /// if `write` is set, else as [`ComponentStorage::view`](super::ComponentStorage::view).
+    pub unsafe fn raw_column<T: Send + Sync + 'static>(&self, write: bool) -> Option<RawColumn<T>> {
+        let cell = self.columns.get(&TypeId::of::<T>())?;
+        if write {
+            // SAFETY: guaranteed by the caller.
+            let column = unsafe { &mut *cell.get() }
+                .as_any_mut()
+                .downcast_mut::<TableColumn<T>>()?;
+            Some(RawColumn::new_mut(
+                &mut column.values,
+                &mut column.added,
+                &mut column.changed,
+            ))
+        } else {
+            // SAFETY: guaranteed by the caller.
+            let column = unsafe { &*cell.get() }
+                .as_any()
+                .downcast_ref::<TableColumn<T>>()?;
+            Some(RawColumn::new(
+                &column.values,
+                &column.added,
+                &column.changed,
+            ))
+        }
+    }
+
+    fn column<T: Send + Sync + 'static>(&self) -> Option<&TableColumn<T>> {
+        let cell = self.columns.get(&TypeId::of::<T>())?;
+        // SAFETY: columns are only written through `&self` by queries, which
+        // hold the owning storage for as long as they run.
+        unsafe { &*cell.get() }
+            .as_any()
+            .downcast_ref::<TableColumn<T>>()
+    }
+
+    fn column_mut<T: Send + Sync + 'static>(&mut self) -> Option<&mut TableColumn<T>> {
+        self.columns
+            .get_mut(&TypeId::of::<T>())?
+            .get_mut()
+            .as_any_mut()
+            .downcast_mut::<TableColumn<T>>()
+    }
+}
+
+impl<T: Send + Sync + 'static> Column for TableColumn<T> {
+    fn type_name(&self) -> &'static str {
+        std::any::type_name::<T>()
+    }
+
+    fn empty(&self) -> Box<dyn Column> {
+        new_column::<T>()
+    }
+
+    fn swap_remove(&mut self, row: usize) {
+        self.values.swap_remove(row);
+        self.added.swap_remove(row);
+        self.changed.swap_remove(row);
+    }
+
+    fn take(&mut self, row: usize) -> ErasedComponent {
+        self.added.swap_remove(row);
+        self.changed.swap_remove(row);
+        ErasedComponent::new(self.values.swap_remove(row))
+    }
+
+    fn move_row(&mut self, row: usize, target: &mut dyn Column) {
+        let target = target
+            .as_any_mut()
+            .downcast_mut::<Self>()
+            .expect("Internal error: moving a row between columns of different types");
+        target.values.push(self.values.swap_remove(row));
+        target.added.push(self.added.swap_remove(row));
+        target.changed.push(self.changed.swap_remove(row));
+    }
+
+    fn get_any(&self, row: usize) -> &dyn Any {
+        &self.values[row]
+    }
+
+    fn permute(&mut self, order: &[usize]) {
+        apply_permutation(&mut self.values, order);
+        apply_permutation(&mut self.added, order);
+        apply_permutation(&mut self.changed, order);
+    }
+
+    fn heap_bytes(&self) -> usize {
+        self.values.capacity() * size_of::<T>()
+            + (self.added.capacity() + self.changed.capacity()) * size_of::<Tick>()
+    }
+
+    fn shrink_to_fit(&mut self) {
+        self.values.shrink_to_fit();
+        self.added.shrink_to_fit();
+        self.changed.shrink_to_fit();
+    }
+
+    fn get_any_mut(&mut self, row: usize, tick: Tick) -> &mut dyn Any {
+        self.changed[row] = tick;
+        &mut self.values[row]
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+/// Location of the entity, if the table row it points to still belongs to it.
+pub fn table_row<'a>(
+    locations: &SparseArray<TableRow>,
+    entities: impl FnOnce(usize) -> Option<&'a [Entity]>,
+    entity: Entity,
+) -> Option<TableRow> {
+    let location = locations.get(entity.index())?;
+    (entities(location.table)?.get(location.row) == Some(&entity)).then_some(location)
+}
+
+fn new_column<T: Send + Sync + 'static>() -> Box<dyn Column> {
+    Box::new(TableColumn::<T> {
+        values: Vec::new(),
+        added: Vec::new(),
+        changed: Vec::new(),
+    })
+}
+
+fn clone_column<T: SnapshotClone + Send + Sync + 'static>(column: &dyn Column) -> Box<dyn Column> {
+    let column = column
+        .as_any()
+        .downcast_ref::<TableColumn<T>>()
+        .expect("Internal error: column cloner registered for another type");
+    Box::new(TableColumn {
+        values: column
+            .values
+            .iter()
+            .map(SnapshotClone::snapshot_clone)
+            .collect(),
+        added: column.added.clone(),
+        changed: column.changed.clone(),
+    })
+}
+
+fn two_mut<T>(items: &mut [T], first: usize, second: usize) -> (&mut T, &mut T) {
+    assert_ne!(
+        first, second,
+        "Internal error: moving an entity within one table"
+    );
+    if first < second {
+        let (head, tail) = items.split_at_mut(second);
+        (&mut head[first], &mut tail[0])
+    } else {
+        let (head, tail) = items.split_at_mut(first);
+        (&mut tail[0], &mut head[second])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{TableRow, Tables};
+    use crate::entity::Entity;
+    use crate::entity::component_storage::Tick;
+
+    fn tables() -> Tables {
+        let mut tables = Tables::default();
+        tables.register::<u32>();
+        tables.register::<String>();
+        tables
+    }
+
+    #[test]
+    fn test_tables_move_entities_between_archetypes() {
+        let mut tables = tables();
+        let first = Entity::new(0, 0);
+        let second = Entity::new(1, 0);
+        tables.insert(first, 1_u32, Tick::default());
+        tables.insert(second, 2_u32, Tick::default());
+        tables.insert(first, "first".to_string(), Tick::default());
+
+        assert_eq!(tables.tables().len(), 2);
+        assert_eq!(tables.get::<u32>(first), Some(&1));
+        assert_eq!(tables.get::<u32>(second), Some(&2));
+        assert_eq!(
+            tables.get::<String>(first).map(String::as_str),
+            Some("first")
+        );
+        assert_eq!(tables.tables()[0].entities(), &[second]);
+        assert_eq!(tables.location(second), Some(TableRow { table: 0, row: 0 }));
+
+        assert_eq!(tables.remove::<u32>(first), Some(1));
+        assert_eq!(tables.tables().len(), 3);
+        assert_eq!(
+            tables.get::<String>(first).map(String::as_str),
+            Some("first")
+        );
+        assert_eq!(tables.len_of(std::any::TypeId::of::<u32>()), 1);
+    }
+
+    #[test]
+    fn test_tables_replace_and_remove_entity() {
+        let mut tables = tables();
+        let entities: Vec<_> = (0..3).map(|index| Entity::new(index, 0)).collect();
+        for (value, &entity) in entities.iter().enumerate() {
+            tables.insert(entity, value as u32, Tick::default());
+        }
+
+        assert_eq!(tables.insert(entities[1], 10_u32, Tick::new(3)), Some(1));
+        tables.remove_entity(entities[0]);
+
+        assert!(tables.location(entities[0]).is_none());
+        assert_eq!(tables.get::<u32>(entities[1]), Some(&10));
+        assert_eq!(tables.get::<u32>(entities[2]), Some(&2));
+        assert!(tables.get::<u32>(Entity::new(2, 1)).is_none());
+        assert_eq!(tables.tables()[0].entities().len(), 2);
+    }
+}
@@ -0,0 +1,279 @@
+//! Components whose type is only known at runtime, e.g. defined by a script
+//! or an editor, described by a name and a list of typed fields. They live in
+//! their own byte-packed sparse sets next to the Rust components, take part
+//! in despawns, snapshots and moves between entity managers, and can be
+//! queried together with Rust components.
+//!
+//! ```ignore
+//! let health = entity_manager.register_dynamic_component(
+//!     DynamicLayout::new("Health").with_field("current", FieldKind::F32),
+//! );
+//! entity_manager.add_dynamic_component(entity, health, &1.5_f32.to_ne_bytes());
+//! for ((transform,), mut dynamic) in entity_manager.query_dynamic::<(&Transform,)>(&[health]) {
+//!     let current = dynamic[0].get::<f32>("current").unwrap();
+//!     dynamic[0].set("current", current - 0.1);
+//! }
+//! ```
+
+pub use crate::entity::component_storage::{
+    DynamicComponentId, DynamicField, DynamicFieldType, DynamicLayout, DynamicMut, DynamicRef,
+    FieldKind,
+};
+use crate::entity::query::{DynamicQueryIter, QueryData, QueryFilter, TickRange};
+use crate::entity::{Entity, EntityManager};
+
+impl EntityManager {
+    /// Registers a component type described at runtime, or returns the ID it
+    /// is registered under already.
+    ///
+    /// # Panics
+    ///
+    /// Panics if another layout is registered under the same name.
+    pub fn register_dynamic_component(&mut self, layout: DynamicLayout) -> DynamicComponentId {
+        self.components.dynamic_mut().register(layout)
+    }
+
+    #[must_use]
+    pub fn dynamic_component_id(&self, name: &str) -> Option<DynamicComponentId> {
+        self.components.dynamic().id(name)
+    }
+
+    #[must_use]
+    pub fn dynamic_layout(&self, id: DynamicComponentId) -> Option<&DynamicLayout> {
+        self.components.dynamic().layout(id)
+    }
+
+    /// Adds the component from its bytes, laid out as described by its
+    /// [`DynamicLayout`], replacing and returning the previous one. Nothing
+    /// is added if the entity does not exist.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `id` is not registered or `bytes` does not have the size of
+    /// its layout.
+    pub fn add_dynamic_component(
+        &mut self,
+        entity: Entity,
+        id: DynamicComponentId,
+        bytes: &[u8],
+    ) -> Option<Vec<u8>> {
+        if !self.entity_exists(entity) {
+            return None;
+        }
+        let tick = self.components.change_tick();
+        self.components
+            .dynamic_mut()
+            .insert(id, entity, bytes, tick)
+    }
+
+    /// Removes the component and returns its bytes.
+    pub fn remove_dynamic_component(
+        &mut self,
+        entity: Entity,
+        id: DynamicComponentId,
+    ) -> Option<Vec<u8>> {
+        if !self.entity_exists(entity) {
+            return None;
+        }
+        self.components.dynamic_mut().remove(id, entity)
+    }
+
+    #[must_use]
+    pub fn get_dynamic_component(
+        &self,
+        entity: Entity,
+        id: DynamicComponentId,
+    ) -> Option<DynamicRef<'_>> {
+        if !self.entity_exists(entity) {
+            return None;
+        }
+        self.components.dynamic().get(id, entity)
+    }
+
+    #[must_use]
+    pub fn get_dynamic_component_mut(
+        &mut self,
+        entity: Entity,
+        id: DynamicComponentId,
+    ) -> Option<DynamicMut<'_>> {
+        if !self.entity_exists(entity) {
+            return None;
+        }
+        let tick = self.components.change_tick();
+        self.components.dynamic_mut().get_mut(id, entity, tick)
+    }
+
+    #[must_use]
+    pub fn has_dynamic_component(&self, entity: Entity, id: DynamicComponentId) -> bool {
+        self.entity_exists(entity) && self.components.dynamic().contains(id, entity)
+    }
+
+    /// IDs of the dynamic components of the entity.
+    #[must_use]
+    pub fn dynamic_components_of(&self, entity: Entity) -> Vec<DynamicComponentId> {
+        if !self.entity_exists(entity) {
+            return Vec::new();
+        }
+        self.components.dynamic().component_ids(entity)
+    }
+
+    /// Same as [`EntityManager::query`], but only visits entities that also
+    /// have every dynamic component of `ids`, and yields those mutably next
+    /// to the item of `Q`, in the order of `ids`. Matches nothing if one of
+    /// `ids` is not registered.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `Q` accesses a component mutably more than once, or `ids`
+    /// contains the same ID twice.
+    pub fn query_dynamic<Q: QueryData>(
+        &mut self,
+        ids: &[DynamicComponentId],
+    ) -> DynamicQueryIter<'_, Q> {
+        self.query_dynamic_filtered::<Q, ()>(ids)
+    }
+
+    /// [`EntityManager::query_dynamic`] with a filter, see
+    /// [`EntityManager::query_filtered`].
+    ///
+    /// # Panics
+    ///
+    /// Same as [`EntityManager::query_dynamic`].
+    pub fn query_dynamic_filtered<Q: QueryData, F: QueryFilter>(
+        &mut self,
+        ids: &[DynamicComponentId],
+    ) -> DynamicQueryIter<'_, Q, F> {
+        let ticks = TickRange::new(
+            self.components.last_change_tick(),
+            self.components.change_tick(),
+        );
+        // SAFETY: the iterator keeps `self` mutably borrowed.
+        unsafe { DynamicQueryIter::new(self, ticks, ids) }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::Vec3;
+
+    use super::{DynamicLayout, FieldKind};
+    use crate::components::transform::Transform;
+    use crate::entity::query::With;
+    use crate::entity::{Entity, EntityManager};
+
+    fn health() -> DynamicLayout {
+        DynamicLayout::new("Health")
+            .with_field("current", FieldKind::F32)
+            .with_field("regenerates", FieldKind::Bool)
+    }
+
+    #[test]
+    fn test_dynamic_components_on_entities() {
+        let mut entity_manager = EntityManager::new(10);
+        let health = entity_manager.register_dynamic_component(health());
+        let entity = entity_manager.create_entity((1_u32,));
+
+        assert!(
+            entity_manager
+                .add_dynamic_component(entity, health, &[0; 5])
+                .is_none()
+        );
+        assert!(
+            entity_manager
+                .get_dynamic_component_mut(entity, health)
+                .unwrap()
+                .set("current", 2.5_f32)
+        );
+        assert_eq!(entity_manager.dynamic_component_id("Health"), Some(health));
+        assert_eq!(
+            entity_manager
+                .get_dynamic_component(entity, health)
+                .unwrap()
+                .get::<f32>("current"),
+            Some(2.5)
+        );
+        assert_eq!(entity_manager.dynamic_components_of(entity), [health]);
+
+        entity_manager.remove_entity(entity);
+        let reused = entity_manager.create_entity(());
+        assert_eq!(reused.index(), entity.index());
+        assert!(!entity_manager.has_dynamic_component(reused, health));
+        assert!(entity_manager.dynamic_components_of(reused).is_empty());
+    }
+
+    #[test]
+    fn test_query_dynamic_alongside_static_components() {
+        let mut entity_manager = EntityManager::new(10);
+        let health = entity_manager.register_dynamic_component(health());
+        let tag = entity_manager.register_dynamic_component(DynamicLayout::new("Tag"));
+        let entities: Vec<Entity> = (0..6)
+            .map(|index| {
+                entity_manager.create_entity((Transform::from_translation(Vec3::X * index as f32),))
+            })
+            .collect();
+        for &entity in &entities[..4] {
+            let mut bytes = 10.0_f32.to_ne_bytes().to_vec();
+            bytes.push(1);
+            entity_manager.add_dynamic_component(entity, health, &bytes);
+        }
+        entity_manager.add_dynamic_component(entities[1], tag, &[]);
+        entity_manager.add_dynamic_component(entities[5], tag, &[]);
+        entity_manager.add_component(entities[0], 'p');
+
+        for (transform, mut dynamic) in entity_manager.query_dynamic::<&Transform>(&[health]) {
+            let current = dynamic[0].get::<f32>("current").unwrap();
+            dynamic[0].set("current", current - transform.matrix().w_axis.x);
+        }
+        let mut tagged: Vec<Entity> = entity_manager
+            .query_dynamic::<Entity>(&[tag, health])
+            .map(|(entity, dynamic)| {
+                assert_eq!(dynamic.len(), 2);
+                entity
+            })
+            .collect();
+        tagged.sort_by_key(|entity| entity.index());
+
+        assert_eq!(tagged, [entities[1]]);
+        assert_eq!(
+            entity_manager
+                .get_dynamic_component(entities[3], health)
+                .unwrap()
+                .get::<f32>("current"),
+            Some(7.0)
+        );
+        assert_eq!(
+            entity_manager
+                .query_dynamic_filtered::<Entity, With<char>>(&[health])
+                .count(),
+            1
+        );
+    }
+
+    #[test]
+    fn test_dynamic_components_in_snapshots_and_moves() {
+        let mut entity_manager = EntityManager::new(10);
+        let health = entity_manager.register_dynamic_component(health());
+        let entity = entity_manager.create_entity(());
+        entity_manager.add_dynamic_component(entity, health, &[0; 5]);
+        let snapshot = entity_manager.snapshot().unwrap();
+
+        entity_manager.remove_dynamic_component(entity, health);
+        entity_manager.restore(&snapshot);
+        assert!(entity_manager.has_dynamic_component(entity, health));
+
+        let mut target = EntityManager::new(10);
+        target.register_dynamic_component(DynamicLayout::new("Other"));
+        let map = entity_manager.move_entity_to(entity, &mut target).unwrap();
+        let moved = map[&entity];
+        let target_health = target.dynamic_component_id("Health").unwrap();
+
+        assert_ne!(target_health, health);
+        assert_eq!(
+            target
+                .get_dynamic_component(moved, target_health)
+                .unwrap()
+                .bytes(),
+            [0; 5]
+        );
+    }
+}
@@ -0,0 +1,242 @@
+use std::marker::PhantomData;
+
+/// Double-buffered queue of events of type `T`, stored as a resource.
+///
+/// Events stay readable for the frame they are sent in and the following one:
+/// every [`Events::update`] drops the older buffer and starts a new one.
+pub struct Events<T> {
+    previous: Vec<T>,
+    current: Vec<T>,
+    previous_start: usize,
+    current_start: usize,
+    event_count: usize,
+}
+
+/// Position of one reader in an [`Events`] queue, so each reader sees every
+/// event exactly once.
+pub struct EventCursor<T> {
+    last_event_count: usize,
+    marker: PhantomData<fn() -> T>,
+}
+
+/// System parameter sending events of type `T`. The type must be registered
+/// with [`EntityManager::add_event`](crate::entity::EntityManager::add_event).
+pub struct EventWriter<'w, T: 'static> {
+    events: &'w mut Events<T>,
+}
+
+/// System parameter reading the events of type `T` sent since the system last
+/// ran. The type must be registered with
+/// [`EntityManager::add_event`](crate::entity::EntityManager::add_event).
+pub struct EventReader<'w, T: 'static> {
+    cursor: &'w mut EventCursor<T>,
+    events: &'w Events<T>,
+}
+
+impl<T> Events<T> {
+    pub fn send(&mut self, event: T) {
+        self.current.push(event);
+        self.event_count += 1;
+    }
+
+    pub fn send_batch(&mut self, events: impl IntoIterator<Item = T>) {
+        for event in events {
+            self.send(event);
+        }
+    }
+
+    /// Drops the events of the previous frame and starts a new buffer. Called
+    /// once per frame by
+    /// [`EntityManager::update_events`](crate::entity::EntityManager::update_events).
+    pub fn update(&mut self) {
+        self.previous = std::mem::take(&mut self.current);
+        self.previous_start = self.current_start;
+        self.current_start = self.event_count;
+    }
+
+    /// Drops every buffered event. Cursors skip the dropped events.
+    pub fn clear(&mut self) {
+        self.previous.clear();
+        self.current.clear();
+        self.previous_start = self.event_count;
+        self.current_start = self.event_count;
+    }
+
+    /// Number of buffered events.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.previous.len() + self.current.len()
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Cursor that only sees events sent from now on.
+    #[must_use]
+    pub fn cursor_at_end(&self) -> EventCursor<T> {
+        EventCursor {
+            last_event_count: self.event_count,
+            marker: PhantomData,
+        }
+    }
+
+    /// Iterates every buffered event, oldest first.
+    pub fn iter(&self) -> impl Iterator<Item = &T> {
+        self.previous.iter().chain(&self.current)
+    }
+}
+
+impl<T> EventCursor<T> {
+    /// Returns the events sent since the last call and moves the cursor past them.
+    pub fn read<'a>(&mut self, events: &'a Events<T>) -> impl Iterator<Item = &'a T> + use<'a, T> {
+        let previous_skip = self.last_event_count.saturating_sub(events.previous_start);
+        let current_skip = self.last_event_count.saturating_sub(events.current_start);
+        self.last_event_count = events.event_count;
+        events
+            .previous
+            .iter()
+            .skip(previous_skip)
+            .chain(events.current.iter().skip(current_skip))
+    }
+
+    /// Number of events [`EventCursor::read`] would return.
+    #[must_use]
+    pub fn len(&self, events: &Events<T>) -> usize {
+        let oldest = events.previous_start.max(self.last_event_count);
+        events.event_count.saturating_sub(oldest)
+    }
+
+    #[must_use]
+    pub fn is_empty(&self, events: &Events<T>) -> bool {
+        self.len(events) == 0
+    }
+
+    /// Marks every buffered event as read.
+    pub fn clear(&mut self, events: &Events<T>) {
+        self.last_event_count = events.event_count;
+    }
+}
+
+impl<'w, T: 'static> EventWriter<'w, T> {
+    pub(crate) fn new(events: &'w mut Events<T>) -> Self {
+        Self { events }
+    }
+
+    pub fn send(&mut self, event: T) {
+        self.events.send(event);
+    }
+
+    pub fn send_batch(&mut self, events: impl IntoIterator<Item = T>) {
+        self.events.send_batch(events);
+    }
+}
+
+impl<'w, T: 'static> EventReader<'w, T> {
+    pub(crate) fn new(cursor: &'w mut EventCursor<T>, events: &'w Events<T>) -> Self {
+        Self { cursor, events }
+    }
+
+    pub fn read(&mut self) -> impl Iterator<Item = &'w T> + use<'w, T> {
+        self.cursor.read(self.events)
+    }
+
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.cursor.len(self.events)
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.cursor.is_empty(self.events)
+    }
+
+    /// Marks every pending event as read.
+    pub fn clear(&mut self) {
+        self.cursor.clear(self.events);
+    }
+}
+
+impl<T> Default for Events<T> {
+    fn default() -> Self {
+        Self {
+            previous: Vec::new(),
+            current: Vec::new(),
+            previous_start: 0,
+            current_start: 0,
+            event_count: 0,
+        }
+    }
+}
+
+impl<T> Default for EventCursor<T> {
+    fn default() -> Self {
+        Self {
+            last_event_count: 0,
+            marker: PhantomData,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{EventCursor, Events};
+
+    #[test]
+    fn test_events_read_once_per_cursor() {
+        let mut events = Events::default();
+        let mut first = EventCursor::default();
+        let mut second = EventCursor::default();
+        events.send(1);
+        events.send(2);
+
+        assert_eq!(first.read(&events).copied().collect::<Vec<_>>(), vec![1, 2]);
+        events.send(3);
+
+        assert_eq!(first.len(&events), 1);
+        assert_eq!(first.read(&events).copied().collect::<Vec<_>>(), vec![3]);
+        assert!(first.is_empty(&events));
+        assert_eq!(
+            second.read(&events).copied().collect::<Vec<_>>(),
+            vec![1, 2, 3]
+        );
+    }
+
+    #[test]
+    fn test_events_dropped_after_two_updates() {
+        let mut events = Events::default();
+        let mut cursor = EventCursor::default();
+        events.send("first");
+
+        events.update();
+        events.send("second");
+        assert_eq!(events.len(), 2);
+
+        events.update();
+        assert_eq!(events.iter().copied().collect::<Vec<_>>(), vec!["second"]);
+        assert_eq!(
+            cursor.read(&events).copied().collect::<Vec<_>>(),
+            vec!["second"]
+        );
+
+        events.update();
+        assert!(events.is_empty());
+        assert!(cursor.is_empty(&events));
+    }
+
+    #[test]
+    fn test_events_cursor_at_end_and_clear() {
+        let mut events = Events::default();
+        events.send(1);
+        let mut cursor = events.cursor_at_end();
+        events.send(2);
+
+        assert_eq!(cursor.read(&events).copied().collect::<Vec<_>>(), vec![2]);
+
+        events.send(3);
+        events.clear();
+        events.send(4);
+        assert_eq!(cursor.read(&events).copied().collect::<Vec<_>>(), vec![4]);
+    }
+}
@@ -0,0 +1,225 @@
+//! Owning groups and sorting of sparse-set components.
+//!
+//! A group owns the sparse sets of a few component types and keeps the
+//! entities having all of them at the front of each set, in the same order,
+//! so iterating them together walks the dense arrays linearly instead of
+//! jumping between unrelated rows. Sorting one of the owned types reorders
+//! the others along with it. Types stored in tables can be part of a group
+//! too: their tables keep them packed already, so they only restrict the
+//! group to the entities having them.
+//!
+//! ```ignore
+//! entity_manager.create_group::<(Depth, Material)>();
+//! entity_manager.sort_components_by::<Depth>(|a, b| a.0.total_cmp(&b.0));
+//! for &entity in entity_manager.group_entities::<(Depth, Material)>() {
+//!     // back to front, with `Material`s stored in the same order
+//! }
+//! ```
+
+use std::any::TypeId;
+use std::cmp::Ordering;
+
+use crate::entity::{ComponentStorage, Entity, EntityManager};
+
+/// Tuple of the component types owned by a group.
+pub trait ComponentGroup {
+    fn type_ids() -> Vec<TypeId>;
+
+    /// Creates the sparse sets of the types that have no storage yet.
+    fn register(storage: &mut ComponentStorage);
+}
+
+macro_rules! impl_component_group_for_tuple {
+    ($($T:ident),+) => {
+        impl<$($T: Send + Sync + 'static),+> ComponentGroup for ($($T,)+) {
+            fn type_ids() -> Vec<TypeId> {
+                vec![$(TypeId::of::<$T>()),+]
+            }
+
+            fn register(storage: &mut ComponentStorage) {
+                $(storage.register_if_missing::<$T>();)+
+            }
+        }
+    };
+}
+
+impl_component_group_for_tuple!(T1, T2);
+impl_component_group_for_tuple!(T1, T2, T3);
+impl_component_group_for_tuple!(T1, T2, T3, T4);
+impl_component_group_for_tuple!(T1, T2, T3, T4, T5);
+impl_component_group_for_tuple!(T1, T2, T3, T4, T5, T6);
+impl_component_group_for_tuple!(T1, T2, T3, T4, T5, T6, T7);
+impl_component_group_for_tuple!(T1, T2, T3, T4, T5, T6, T7, T8);
+
+impl EntityManager {
+    /// Creates the group owning the sparse sets of the types of `G`, packing
+    /// the entities that already have all of them. Does nothing if the group
+    /// exists already.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `G` names the same type twice, has no type stored in a
+    /// sparse set, or a type belongs to another group.
+    pub fn create_group<G: ComponentGroup>(&mut self) {
+        G::register(&mut self.components);
+        self.components.create_group(&G::type_ids());
+    }
+
+    /// The entities having every type of `G`, in the order of the dense
+    /// arrays of the group. Empty if the group was not created.
+    #[must_use]
+    pub fn group_entities<G: ComponentGroup>(&self) -> &[Entity] {
+        self.components
+            .group_entities(&G::type_ids())
+            .unwrap_or_default()
+    }
+
+    /// Sorts the components of `T` with `compare`, stable, e.g. by material
+    /// or depth so that queries visit them in that order. If `T` is owned by
+    /// a group, the other owned types follow the new order of the grouped
+    /// entities.
+    pub fn sort_components_by<T: Send + Sync + 'static>(
+        &mut self,
+        compare: impl FnMut(&T, &T) -> Ordering,
+    ) {
+        self.components.sort_by(compare);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::Vec3;
+
+    use crate::components::shape::Shape;
+    use crate::components::transform::Transform;
+    use crate::entity::{Entity, EntityManager};
+
+    #[derive(Clone, Debug, PartialEq)]
+    struct Depth(f32);
+
+    #[test]
+    fn test_group_survives_adds_removes_and_despawns() {
+        let mut entity_manager = EntityManager::new(16);
+        entity_manager.register_cloneable::<u32>();
+        entity_manager.register_cloneable::<Depth>();
+        let entities: Vec<Entity> = (0..8)
+            .map(|index| entity_manager.create_entity((index as u32,)))
+            .collect();
+        for &entity in entities.iter().step_by(2) {
+            entity_manager.add_component(entity, Depth(entity.index() as f32));
+        }
+        entity_manager.create_group::<(u32, Depth)>();
+        entity_manager.create_group::<(Depth, u32)>();
+        assert_eq!(entity_manager.group_entities::<(u32, Depth)>().len(), 4);
+
+        entity_manager.add_component(entities[1], Depth(1.0));
+        entity_manager.remove_component::<u32>(entities[4]);
+        entity_manager.remove_entity(entities[0]);
+        let snapshot = entity_manager.snapshot().unwrap();
+        entity_manager.remove_component::<Depth>(entities[2]);
+        entity_manager.restore(&snapshot);
+
+        let mut grouped = entity_manager.group_entities::<(u32, Depth)>().to_vec();
+        for &entity in &grouped {
+            assert_eq!(
+                entity_manager.get_component::<Depth>(entity).unwrap().0,
+                *entity_manager.get_component::<u32>(entity).unwrap() as f32
+            );
+        }
+        grouped.sort_by_key(|entity| entity.index());
+        assert_eq!(grouped, [entities[1], entities[2], entities[6]]);
+    }
+
+    #[test]
+    fn test_sort_components_by_keeps_lookups() {
+        let mut entity_manager = EntityManager::new(16);
+        let entities: Vec<Entity> = [3.0, 1.0, 4.0, 1.5, 5.0]
+            .into_iter()
+            .map(|depth| entity_manager.create_entity((Depth(depth), depth as u32)))
+            .collect();
+        entity_manager.create_group::<(Depth, u32)>();
+        entity_manager.sort_components_by::<Depth>(|a, b| b.0.total_cmp(&a.0));
+
+        let depths: Vec<f32> = entity_manager
+            .query::<&Depth>()
+            .map(|depth| depth.0)
+            .collect();
+        assert_eq!(depths, [5.0, 4.0, 3.0, 1.5, 1.0]);
+        assert_eq!(
+            entity_manager.group_entities::<(u32, Depth)>(),
+            [
+                entities[4],
+                entities[2],
+                entities[0],
+                entities[3],
+                entities[1]
+            ]
+        );
+        assert_eq!(entity_manager.get_component::<u32>(entities[3]), Some(&1));
+        assert_eq!(
+            entity_manager.get_component::<Depth>(entities[1]),
+            Some(&Depth(1.0))
+        );
+    }
+
+    #[test]
+    fn test_sort_table_components() {
+        let mut entity_manager = EntityManager::new(16);
+        let entities: Vec<Entity> = [2.0, -1.0, 0.5]
+            .into_iter()
+            .map(|x| entity_manager.create_entity((Transform::from_translation(Vec3::X * x),)))
+            .collect();
+        entity_manager.sort_components_by::<Transform>(|a, b| {
+            a.matrix().w_axis.x.total_cmp(&b.matrix().w_axis.x)
+        });
+
+        let xs: Vec<f32> = entity_manager
+            .query::<&Transform>()
+            .map(|transform| transform.matrix().w_axis.x)
+            .collect();
+        assert_eq!(xs, [-1.0, 0.5, 2.0]);
+        assert_eq!(
+            entity_manager
+                .get_component::<Transform>(entities[0])
+                .unwrap()
+                .matrix()
+                .w_axis
+                .x,
+            2.0
+        );
+    }
+
+    #[test]
+    fn test_group_with_table_components() {
+        let mut entity_manager = EntityManager::new(16);
+        entity_manager.register_component::<Transform>();
+        entity_manager.register_component::<Shape>();
+        let shape = Shape::new_triangle(Vec3::ZERO, Vec3::X, Vec3::Y);
+        let entities: Vec<Entity> = (0..4)
+            .map(|index| entity_manager.create_entity((Transform::identity(), Depth(index as f32))))
+            .collect();
+        entity_manager.add_component(entities[1], shape.clone());
+        entity_manager.add_component(entities[3], shape.clone());
+        entity_manager.create_group::<(Shape, Transform, Depth)>();
+        assert_eq!(
+            entity_manager.group_entities::<(Shape, Transform, Depth)>(),
+            [entities[1], entities[3]]
+        );
+
+        entity_manager.add_component(entities[0], shape);
+        entity_manager.remove_component::<Transform>(entities[1]);
+        entity_manager.sort_components_by::<Depth>(|a, b| b.0.total_cmp(&a.0));
+
+        assert_eq!(
+            entity_manager.group_entities::<(Depth, Shape, Transform)>(),
+            [entities[3], entities[0]]
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "each component type once")]
+    fn test_group_rejects_duplicate_types() {
+        let mut entity_manager = EntityManager::new(16);
+        entity_manager.create_group::<(u32, Depth, u32)>();
+    }
+}
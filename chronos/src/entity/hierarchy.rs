@@ -0,0 +1,445 @@
+use std::collections::HashMap;
+
+use glam::Mat4;
+
+use crate::components::transform::{GlobalTransform, Transform};
+use crate::entity::component_storage::SnapshotClone;
+use crate::entity::{Entity, EntityManager};
+
+/// Parent of an entity, maintained by [`EntityManager::set_parent`]. Neither
+/// it nor [`Children`] can be created, cloned, added, removed or mutated
+/// outside the hierarchy API, so the two always agree.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Parent(Entity);
+
+/// Children of an entity in attachment order, maintained by
+/// [`EntityManager::set_parent`].
+#[derive(Debug, PartialEq, Eq)]
+pub struct Children(Vec<Entity>);
+
+/// Which transform survives when an entity changes parent.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ReparentMode {
+    /// Rewrites the local `Transform` so the entity stays where it is in the world.
+    KeepWorld,
+    /// Keeps the local `Transform`, so the entity moves along with its new parent.
+    KeepLocal,
+}
+
+impl SnapshotClone for Parent {
+    fn snapshot_clone(&self) -> Self {
+        Self(self.0)
+    }
+}
+
+impl SnapshotClone for Children {
+    fn snapshot_clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
+
+impl Parent {
+    pub(crate) fn new(parent: Entity) -> Self {
+        Self(parent)
+    }
+
+    #[must_use]
+    pub fn get(&self) -> Entity {
+        self.0
+    }
+
+    /// Points to the new handle of the parent, if it is in `map`.
+    pub(crate) fn remap(&mut self, map: &HashMap<Entity, Entity>) {
+        if let Some(&parent) = map.get(&self.0) {
+            self.0 = parent;
+        }
+    }
+}
+
+impl Children {
+    #[must_use]
+    pub fn as_slice(&self) -> &[Entity] {
+        &self.0
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = Entity> + '_ {
+        self.0.iter().copied()
+    }
+
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Points to the new handles of the children found in `map`.
+    pub(crate) fn remap(&mut self, map: &HashMap<Entity, Entity>) {
+        for child in &mut self.0 {
+            if let Some(&new) = map.get(child) {
+                *child = new;
+            }
+        }
+    }
+}
+
+impl EntityManager {
+    /// Attaches `child` to `parent`, detaching it from its previous parent.
+    /// Does nothing if either entity does not exist.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `parent` is `child` or one of its descendants.
+    pub fn set_parent(&mut self, child: Entity, parent: Entity, mode: ReparentMode) {
+        if !self.entity_exists(child) || !self.entity_exists(parent) {
+            return;
+        }
+        assert!(
+            child != parent && !self.descendants(child).contains(&parent),
+            "Cannot attach {child:?} to its own descendant {parent:?}"
+        );
+
+        let world_matrix = self.world_matrix(child);
+        self.detach_from_parent(child);
+        self.add_component_unchecked(child, Parent(parent));
+        match self
+            .components
+            .get_component_mut_unchecked::<Children>(parent)
+        {
+            Some(children) => children.0.push(child),
+            None => {
+                self.add_component_unchecked(parent, Children(vec![child]));
+            }
+        }
+
+        if mode == ReparentMode::KeepWorld {
+            let local_matrix = self.world_matrix(parent).inverse() * world_matrix;
+            self.add_component(child, Transform::from_matrix(local_matrix));
+        }
+    }
+
+    /// Detaches `child` from its parent, making it a root.
+    pub fn remove_parent(&mut self, child: Entity, mode: ReparentMode) {
+        if self.parent(child).is_none() {
+            return;
+        }
+        let world_matrix = self.world_matrix(child);
+        self.detach_from_parent(child);
+        if mode == ReparentMode::KeepWorld {
+            self.add_component(child, Transform::from_matrix(world_matrix));
+        }
+    }
+
+    #[must_use]
+    pub fn parent(&self, entity: Entity) -> Option<Entity> {
+        self.get_component::<Parent>(entity).map(Parent::get)
+    }
+
+    #[must_use]
+    pub fn children(&self, entity: Entity) -> &[Entity] {
+        self.get_component::<Children>(entity)
+            .map_or(&[], Children::as_slice)
+    }
+
+    /// All descendants of `entity`, depth first.
+    #[must_use]
+    pub fn descendants(&self, entity: Entity) -> Vec<Entity> {
+        let mut descendants = Vec::new();
+        let mut stack: Vec<Entity> = self.children(entity).iter().rev().copied().collect();
+        while let Some(next) = stack.pop() {
+            descendants.push(next);
+            stack.extend(self.children(next).iter().rev());
+        }
+        descendants
+    }
+
+    /// Recomputes the [`GlobalTransform`] of every entity with a `Transform`
+    /// from the root of its hierarchy down. Entities without a `Transform`
+    /// pass their parent's transform on unchanged and lose any leftover
+    /// `GlobalTransform`.
+    pub fn propagate_transforms(&mut self) {
+        let mut stack: Vec<(Entity, Mat4)> = self
+            .entities()
+            .filter(|&entity| {
+                !self.has_component::<Parent>(entity)
+                    && (self.has_component::<Transform>(entity)
+                        || self.has_component::<GlobalTransform>(entity)
+                        || self.has_component::<Children>(entity))
+            })
+            .map(|entity| (entity, Mat4::IDENTITY))
+            .collect();
+
+        while let Some((entity, parent_matrix)) = stack.pop() {
+            let matrix = parent_matrix * self.local_matrix(entity);
+            if self.has_component::<Transform>(entity) {
+                let global_transform = GlobalTransform::from_matrix(matrix);
+                // Only write on change, so `Changed<GlobalTransform>` stays meaningful.
+                if self.get_component::<GlobalTransform>(entity) != Some(&global_transform) {
+                    self.add_component(entity, global_transform);
+                }
+            } else if self.has_component::<GlobalTransform>(entity) {
+                self.remove_component::<GlobalTransform>(entity);
+            }
+            stack.extend(self.children(entity).iter().map(|&child| (child, matrix)));
+        }
+    }
+
+    /// Removes `child` from its parent's `Children` and drops its `Parent`.
+    pub(crate) fn detach_from_parent(&mut self, child: Entity) {
+        let Some(Parent(parent)) = self.remove_component_unchecked::<Parent>(child) else {
+            return;
+        };
+        if let Some(children) = self
+            .components
+            .get_component_mut_unchecked::<Children>(parent)
+        {
+            children.0.retain(|&other| other != child);
+            if children.0.is_empty() {
+                self.remove_component_unchecked::<Children>(parent);
+            }
+        }
+    }
+
+    fn local_matrix(&self, entity: Entity) -> Mat4 {
+        self.get_component::<Transform>(entity)
+            .map_or(Mat4::IDENTITY, Transform::matrix)
+    }
+
+    /// World matrix computed from the local transforms, independent of
+    /// whether [`EntityManager::propagate_transforms`] ran since the last change.
+    fn world_matrix(&self, entity: Entity) -> Mat4 {
+        let mut matrix = self.local_matrix(entity);
+        let mut current = self.parent(entity);
+        while let Some(ancestor) = current {
+            matrix = self.local_matrix(ancestor) * matrix;
+            current = self.parent(ancestor);
+        }
+        matrix
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::{Mat4, Vec3, vec3};
+
+    use super::{Children, Parent, ReparentMode};
+    use crate::components::transform::{GlobalTransform, Transform};
+    use crate::entity::{Entity, EntityManager};
+
+    const EPSILON: f32 = 0.0001;
+
+    fn global(entity_manager: &EntityManager, entity: Entity) -> Mat4 {
+        entity_manager
+            .get_component::<GlobalTransform>(entity)
+            .unwrap()
+            .matrix()
+    }
+
+    #[test]
+    fn test_hierarchy_set_parent() {
+        let mut entity_manager = EntityManager::new(10);
+        let parent = entity_manager.create_entity((Transform::identity(),));
+        let first = entity_manager.create_entity((Transform::identity(),));
+        let second = entity_manager.create_entity((Transform::identity(),));
+
+        entity_manager.set_parent(first, parent, ReparentMode::KeepLocal);
+        entity_manager.set_parent(second, parent, ReparentMode::KeepLocal);
+
+        assert_eq!(entity_manager.parent(first), Some(parent));
+        assert_eq!(entity_manager.children(parent), &[first, second]);
+        assert!(entity_manager.children(first).is_empty());
+    }
+
+    #[test]
+    fn test_hierarchy_reparent_updates_both_parents() {
+        let mut entity_manager = EntityManager::new(10);
+        let old_parent = entity_manager.create_entity(());
+        let new_parent = entity_manager.create_entity(());
+        let child = entity_manager.create_entity(());
+        entity_manager.set_parent(child, old_parent, ReparentMode::KeepLocal);
+
+        entity_manager.set_parent(child, new_parent, ReparentMode::KeepLocal);
+
+        assert!(!entity_manager.has_component::<Children>(old_parent));
+        assert_eq!(entity_manager.children(new_parent), &[child]);
+        assert_eq!(
+            entity_manager
+                .get_component::<Parent>(child)
+                .map(Parent::get),
+            Some(new_parent)
+        );
+
+        entity_manager.remove_parent(child, ReparentMode::KeepLocal);
+        assert!(entity_manager.parent(child).is_none());
+        assert!(entity_manager.children(new_parent).is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "own descendant")]
+    fn test_hierarchy_rejects_cycle() {
+        let mut entity_manager = EntityManager::new(10);
+        let root = entity_manager.create_entity(());
+        let child = entity_manager.create_entity(());
+        entity_manager.set_parent(child, root, ReparentMode::KeepLocal);
+
+        entity_manager.set_parent(root, child, ReparentMode::KeepLocal);
+    }
+
+    #[test]
+    fn test_hierarchy_propagate_transforms() {
+        let mut entity_manager = EntityManager::new(10);
+        let root =
+            entity_manager.create_entity((Transform::from_translation(vec3(1.0, 0.0, 0.0)),));
+        let middle = entity_manager.create_entity((Transform::from_scale(vec3(2.0, 2.0, 2.0)),));
+        let leaf =
+            entity_manager.create_entity((Transform::from_translation(vec3(0.0, 1.0, 0.0)),));
+        entity_manager.set_parent(middle, root, ReparentMode::KeepLocal);
+        entity_manager.set_parent(leaf, middle, ReparentMode::KeepLocal);
+
+        entity_manager.propagate_transforms();
+
+        assert_eq!(
+            global(&entity_manager, root),
+            Mat4::from_translation(vec3(1.0, 0.0, 0.0))
+        );
+        let leaf_position = global(&entity_manager, leaf).transform_point3(Vec3::ZERO);
+        assert!(leaf_position.abs_diff_eq(vec3(1.0, 2.0, 0.0), EPSILON));
+    }
+
+    #[test]
+    fn test_hierarchy_propagate_drops_leftover_global_transforms() {
+        let mut entity_manager = EntityManager::new(10);
+        let root = entity_manager.create_entity((Transform::identity(),));
+        let child = entity_manager.create_entity((Transform::identity(),));
+        let lone = entity_manager.create_entity((Transform::identity(),));
+        entity_manager.set_parent(child, root, ReparentMode::KeepLocal);
+        entity_manager.propagate_transforms();
+
+        entity_manager.remove_component::<Transform>(child);
+        entity_manager.remove_component::<Transform>(lone);
+        entity_manager.propagate_transforms();
+
+        assert!(entity_manager.has_component::<GlobalTransform>(root));
+        assert!(!entity_manager.has_component::<GlobalTransform>(child));
+        assert!(!entity_manager.has_component::<GlobalTransform>(lone));
+    }
+
+    #[test]
+    fn test_hierarchy_reparent_keep_world() {
+        let mut entity_manager = EntityManager::new(10);
+        let parent =
+            entity_manager.create_entity((Transform::from_translation(vec3(5.0, 0.0, 0.0)),));
+        let child =
+            entity_manager.create_entity((Transform::from_translation(vec3(1.0, 0.0, 0.0)),));
+
+        entity_manager.set_parent(child, parent, ReparentMode::KeepWorld);
+        entity_manager.propagate_transforms();
+
+        let local = entity_manager.get_component::<Transform>(child).unwrap();
+        assert!(
+            local
+                .matrix()
+                .abs_diff_eq(Mat4::from_translation(vec3(-4.0, 0.0, 0.0)), EPSILON)
+        );
+        assert!(
+            global(&entity_manager, child)
+                .abs_diff_eq(Mat4::from_translation(vec3(1.0, 0.0, 0.0)), EPSILON)
+        );
+
+        entity_manager.remove_parent(child, ReparentMode::KeepWorld);
+        let local = entity_manager.get_component::<Transform>(child).unwrap();
+        assert!(
+            local
+                .matrix()
+                .abs_diff_eq(Mat4::from_translation(vec3(1.0, 0.0, 0.0)), EPSILON)
+        );
+    }
+
+    #[test]
+    fn test_hierarchy_reparent_keep_local() {
+        let mut entity_manager = EntityManager::new(10);
+        let parent =
+            entity_manager.create_entity((Transform::from_translation(vec3(5.0, 0.0, 0.0)),));
+        let child =
+            entity_manager.create_entity((Transform::from_translation(vec3(1.0, 0.0, 0.0)),));
+
+        entity_manager.set_parent(child, parent, ReparentMode::KeepLocal);
+        entity_manager.propagate_transforms();
+
+        assert_eq!(
+            entity_manager
+                .get_component::<Transform>(child)
+                .unwrap()
+                .matrix(),
+            Mat4::from_translation(vec3(1.0, 0.0, 0.0))
+        );
+        assert!(
+            global(&entity_manager, child)
+                .abs_diff_eq(Mat4::from_translation(vec3(6.0, 0.0, 0.0)), EPSILON)
+        );
+    }
+
+    #[test]
+    fn test_hierarchy_remove_entity_is_recursive() {
+        let mut entity_manager = EntityManager::new(10);
+        let root = entity_manager.create_entity(());
+        let parent = entity_manager.create_entity(());
+        let child = entity_manager.create_entity(());
+        let grandchild = entity_manager.create_entity(());
+        let sibling = entity_manager.create_entity(());
+        entity_manager.set_parent(parent, root, ReparentMode::KeepLocal);
+        entity_manager.set_parent(sibling, root, ReparentMode::KeepLocal);
+        entity_manager.set_parent(child, parent, ReparentMode::KeepLocal);
+        entity_manager.set_parent(grandchild, child, ReparentMode::KeepLocal);
+
+        entity_manager.remove_entity(parent);
+
+        assert!(!entity_manager.entity_exists(parent));
+        assert!(!entity_manager.entity_exists(child));
+        assert!(!entity_manager.entity_exists(grandchild));
+        assert!(entity_manager.entity_exists(sibling));
+        assert_eq!(entity_manager.children(root), &[sibling]);
+        assert_eq!(entity_manager.get_entity_count(), 2);
+    }
+
+    #[test]
+    #[should_panic(expected = "maintained by the hierarchy")]
+    fn test_hierarchy_refuses_adding_parent() {
+        let mut entity_manager = EntityManager::new(10);
+        let root = entity_manager.create_entity(());
+        let child = entity_manager.create_entity(());
+        let other = entity_manager.create_entity(());
+        entity_manager.set_parent(child, root, ReparentMode::KeepLocal);
+
+        let parent = entity_manager.remove_component::<Parent>(child).unwrap();
+        entity_manager.add_component(other, parent);
+    }
+
+    #[test]
+    #[should_panic(expected = "maintained by the hierarchy")]
+    fn test_hierarchy_refuses_removing_children() {
+        let mut entity_manager = EntityManager::new(10);
+        let root = entity_manager.create_entity(());
+        let child = entity_manager.create_entity(());
+        entity_manager.set_parent(child, root, ReparentMode::KeepLocal);
+
+        entity_manager.remove_component::<Children>(root);
+    }
+
+    #[test]
+    fn test_hierarchy_refuses_mutable_access() {
+        let mut entity_manager = EntityManager::new(10);
+        let root = entity_manager.create_entity(());
+        let child = entity_manager.create_entity(());
+        entity_manager.set_parent(child, root, ReparentMode::KeepLocal);
+
+        assert!(entity_manager.get_component_mut::<Parent>(child).is_none());
+        assert!(entity_manager.get_component_mut::<Children>(root).is_none());
+
+        entity_manager.remove_entity(root);
+        assert!(!entity_manager.entity_exists(child));
+    }
+}
@@ -0,0 +1,160 @@
+//! Human-readable entity names, e.g. `player` or `main_camera`, with an index
+//! kept by the entity manager so entities can be looked up by name in O(1).
+//!
+//! ```ignore
+//! let player = entity_manager.create_entity((Name::new("player"), Transform::default()));
+//! assert_eq!(entity_manager.entity_by_name("player"), Some(player));
+//! entity_manager.set_name(player, "hero");
+//! ```
+
+use std::fmt;
+
+use crate::entity::{Entity, EntityManager};
+
+/// Name of an entity. Names are indexed whenever a `Name` is inserted,
+/// removed or written through [`EntityManager::set_field`]. To keep the index
+/// current, `Name` is never handed out mutably: `get_component_mut::<Name>`
+/// returns `None` and queries for `&mut Name` panic, so rename entities with
+/// [`EntityManager::set_name`].
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Name(String);
+
+impl Name {
+    #[must_use]
+    pub fn new(name: impl Into<String>) -> Self {
+        Self(name.into())
+    }
+
+    #[must_use]
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Display for Name {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl From<&str> for Name {
+    fn from(name: &str) -> Self {
+        Self::new(name)
+    }
+}
+
+impl From<String> for Name {
+    fn from(name: String) -> Self {
+        Self(name)
+    }
+}
+
+impl EntityManager {
+    /// Names the entity, replacing and returning its previous name.
+    pub fn set_name(&mut self, entity: Entity, name: impl Into<Name>) -> Option<Name> {
+        self.add_component(entity, name.into())
+    }
+
+    #[must_use]
+    pub fn name(&self, entity: Entity) -> Option<&str> {
+        self.get_component::<Name>(entity).map(Name::as_str)
+    }
+
+    /// One of the entities named `name`, if any.
+    #[must_use]
+    pub fn entity_by_name(&self, name: &str) -> Option<Entity> {
+        self.entities_by_name(name).first().copied()
+    }
+
+    /// Every entity named `name`, as names need not be unique.
+    #[must_use]
+    pub fn entities_by_name(&self, name: &str) -> &[Entity] {
+        self.components.entities_named(name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::any::TypeId;
+
+    use super::Name;
+    use crate::entity::EntityManager;
+    use crate::scene::{EntityMap, SceneComponent, Value};
+
+    #[test]
+    fn test_name_index_follows_renames_and_despawns() {
+        let mut entity_manager = EntityManager::new(10);
+        let player = entity_manager.create_entity((Name::new("player"), 1_u32));
+        let camera = entity_manager.spawn_batch([(Name::new("main_camera"),)])[0];
+        let other = entity_manager.create_entity(());
+        entity_manager.set_name(other, "player");
+
+        assert_eq!(entity_manager.entity_by_name("main_camera"), Some(camera));
+        assert_eq!(entity_manager.entities_by_name("player"), [player, other]);
+
+        assert_eq!(
+            entity_manager.set_name(player, "hero"),
+            Some(Name::new("player"))
+        );
+        assert_eq!(entity_manager.entity_by_name("player"), Some(other));
+        assert_eq!(entity_manager.entity_by_name("hero"), Some(player));
+        assert_eq!(entity_manager.name(player), Some("hero"));
+
+        entity_manager
+            .set_field(camera, "Name", "name", &Value::String("camera".to_string()))
+            .unwrap();
+        assert_eq!(entity_manager.entity_by_name("camera"), Some(camera));
+
+        entity_manager.remove_entity(other);
+        entity_manager.remove_component::<Name>(camera);
+        assert!(entity_manager.entities_by_name("player").is_empty());
+        assert_eq!(entity_manager.entity_by_name("camera"), None);
+    }
+
+    #[test]
+    fn test_name_is_not_handed_out_mutably() {
+        let mut entity_manager = EntityManager::new(10);
+        let player = entity_manager.create_entity((Name::new("player"),));
+
+        assert!(entity_manager.get_component_mut::<Name>(player).is_none());
+        assert!(
+            entity_manager
+                .get_component_dyn_mut(player, TypeId::of::<Name>())
+                .is_none()
+        );
+        assert_eq!(entity_manager.entity_by_name("player"), Some(player));
+    }
+
+    #[test]
+    #[should_panic(expected = "name index stale")]
+    fn test_query_rejects_mutable_name() {
+        let mut entity_manager = EntityManager::new(10);
+        entity_manager.create_entity((Name::new("player"),));
+
+        let _ = entity_manager.query::<&mut Name>().count();
+    }
+
+    #[test]
+    fn test_name_index_in_snapshots() {
+        let mut entity_manager = EntityManager::new(10);
+        let player = entity_manager.create_entity((Name::new("player"),));
+        let snapshot = entity_manager.snapshot().unwrap();
+        entity_manager.set_name(player, "hero");
+
+        entity_manager.restore(&snapshot);
+        assert_eq!(entity_manager.entity_by_name("player"), Some(player));
+        assert_eq!(entity_manager.entity_by_name("hero"), None);
+    }
+
+    #[test]
+    fn test_name_scene_value() {
+        let name = Name::new("main_camera");
+        let value = name.to_value(&EntityMap::default()).unwrap();
+
+        assert_eq!(value, Value::String("main_camera".to_string()));
+        assert_eq!(
+            Name::from_value(&value, &EntityMap::default()).unwrap(),
+            name
+        );
+    }
+}
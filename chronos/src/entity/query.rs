@@ -0,0 +1,1221 @@
+use std::{
+    any::{TypeId, type_name},
+    borrow::Cow,
+    collections::HashSet,
+    marker::PhantomData,
+};
+
+use crate::entity::{
+    Entity, EntityManager,
+    component_storage::{
+        ComponentStorage, ComponentTicksView, ComponentView, ComponentViewMut, DynamicComponentId,
+        DynamicMut, RawDynamicSet, Tick,
+    },
+    hierarchy::{Children, Parent},
+    name::Name,
+};
+
+/// Component types read and written by a query.
+#[derive(Default, Debug, Clone)]
+pub struct ComponentAccess {
+    reads: HashSet<TypeId>,
+    writes: HashSet<TypeId>,
+    /// Types whose entity lists and ticks filters look at. They may be
+    /// written by the same query, but conflict with writes of other queries.
+    filter_reads: HashSet<TypeId>,
+}
+
+/// Entity lists driving a query, one per sparse set or table holding the
+/// component they come from.
+pub type EntityChunks<'w> = Vec<&'w [Entity]>;
+
+/// Something that can be fetched per entity by [`EntityManager::query`]:
+/// `&T`, `&mut T`, `Option<Q>`, [`Entity`] and tuples of those.
+///
+/// # Safety
+///
+/// `component_access` must register every component type the implementation
+/// touches, and mutable ones as writes. Queries rely on it to rule out aliasing.
+pub unsafe trait QueryData {
+    type Item<'w>;
+    type Fetch<'w>;
+
+    fn component_access(access: &mut ComponentAccess);
+
+    /// Returns `None` when a required component type has never been stored,
+    /// in which case the query matches nothing.
+    ///
+    /// # Safety
+    ///
+    /// The access registered by `component_access` must not conflict with any
+    /// other view of `storage` alive for `'w`.
+    unsafe fn init_fetch(storage: &ComponentStorage, ticks: TickRange) -> Option<Self::Fetch<'_>>;
+
+    /// Entity lists of the smallest required component, used to drive iteration.
+    fn required_entities<'w>(fetch: &Self::Fetch<'w>) -> Option<EntityChunks<'w>>;
+
+    /// Whether [`QueryData::fetch`] returns an item for the entity. Tuples
+    /// check every term before fetching any, so that a `&mut T` term is only
+    /// marked as changed for entities the query yields.
+    fn matches(fetch: &Self::Fetch<'_>, entity: Entity) -> bool;
+
+    /// # Safety
+    ///
+    /// The same entity must not be fetched twice while a returned item is alive.
+    unsafe fn fetch<'w>(fetch: &Self::Fetch<'w>, entity: Entity) -> Option<Self::Item<'w>>;
+}
+
+/// [`QueryData`] that never accesses components mutably, so it can be
+/// iterated through a shared [`Query`].
+///
+/// # Safety
+///
+/// `component_access` must not register any writes.
+pub unsafe trait ReadOnlyQueryData: QueryData {}
+
+/// Condition on an entity that does not fetch any data: [`With`], [`Without`],
+/// [`Or`], [`Added`], [`Changed`], `()` and tuples of filters, which must all match.
+///
+/// # Safety
+///
+/// `matches` may only look at which entities own a component, never at the
+/// component values, unless `component_access` registers them as reads.
+pub unsafe trait QueryFilter {
+    type Fetch<'w>;
+
+    fn component_access(_access: &mut ComponentAccess) {}
+
+    /// # Safety
+    ///
+    /// Same contract as [`QueryData::init_fetch`].
+    unsafe fn init_fetch(storage: &ComponentStorage, ticks: TickRange) -> Self::Fetch<'_>;
+
+    fn required_entities<'w>(_fetch: &Self::Fetch<'w>) -> Option<EntityChunks<'w>> {
+        None
+    }
+
+    fn matches(fetch: &Self::Fetch<'_>, entity: Entity) -> bool;
+}
+
+/// Matches entities that have a `T`. Zero-sized marker components work as
+/// tags here, since their dense array never allocates.
+pub struct With<T>(PhantomData<T>);
+
+/// Matches entities that do not have a `T`.
+pub struct Without<T>(PhantomData<T>);
+
+/// Matches entities that pass any of the filters in the tuple, e.g.
+/// `Or<(With<Shape>, With<Color>)>`.
+pub struct Or<T>(PhantomData<T>);
+
+/// Matches entities whose `T` was added after the query's last run.
+pub struct Added<T>(PhantomData<T>);
+
+/// Matches entities whose `T` was added or mutably accessed after the query's
+/// last run. Fetching `&mut T` counts as a change even if nothing is written.
+pub struct Changed<T>(PhantomData<T>);
+
+/// Ticks of the query's previous and current run. [`Added`] and [`Changed`]
+/// match components touched in between, and `&mut T` marks fetched
+/// components as changed at `this_run`.
+#[derive(Clone, Copy, Debug)]
+pub struct TickRange {
+    last_run: Tick,
+    this_run: Tick,
+}
+
+/// System parameter giving access to the entities matching `Q` and `F`,
+/// e.g. `fn movement(mut query: Query<(&mut Transform, &Velocity)>)`.
+pub struct Query<'w, Q: QueryData, F: QueryFilter = ()> {
+    entity_manager: &'w EntityManager,
+    ticks: TickRange,
+    marker: PhantomData<fn() -> (Q, F)>,
+}
+
+/// Iterator over the entities matching `Q` and `F`, created by
+/// [`EntityManager::query`] and [`EntityManager::query_filtered`].
+pub struct QueryIter<'w, Q: QueryData, F: QueryFilter = ()> {
+    fetch: Option<Q::Fetch<'w>>,
+    filter: F::Fetch<'w>,
+    entities: Vec<Cow<'w, [Entity]>>,
+    chunk: usize,
+    position: usize,
+    marker: PhantomData<&'w EntityManager>,
+}
+
+/// Iterator over the entities matching `Q` and `F` that also have every
+/// dynamic component asked for, created by
+/// [`EntityManager::query_dynamic`]. Yields the item of `Q` with the dynamic
+/// components in the order they were asked for.
+pub struct DynamicQueryIter<'w, Q: QueryData, F: QueryFilter = ()> {
+    inner: QueryIter<'w, (Entity, Q), F>,
+    sets: Vec<RawDynamicSet<'w>>,
+    this_run: Tick,
+}
+
+impl ComponentAccess {
+    /// # Panics
+    ///
+    /// Panics if `T` is already written by the same query.
+    pub fn add_read<T: 'static>(&mut self) {
+        assert!(
+            !self.writes.contains(&TypeId::of::<T>()),
+            "Query reads {} while also writing it",
+            type_name::<T>()
+        );
+        self.reads.insert(TypeId::of::<T>());
+    }
+
+    /// # Panics
+    ///
+    /// Panics if `T` is already read or written by the same query.
+    pub fn add_write<T: 'static>(&mut self) {
+        let type_id = TypeId::of::<T>();
+        assert!(
+            !self.reads.contains(&type_id) && !self.writes.contains(&type_id),
+            "Query writes {} while also accessing it elsewhere",
+            type_name::<T>()
+        );
+        self.writes.insert(type_id);
+    }
+
+    /// Registers a filter over `T`, which may be combined with a write of `T`
+    /// in the same query.
+    pub fn add_filter_read<T: 'static>(&mut self) {
+        self.filter_reads.insert(TypeId::of::<T>());
+    }
+
+    #[must_use]
+    pub fn reads(&self) -> &HashSet<TypeId> {
+        &self.reads
+    }
+
+    #[must_use]
+    pub fn filter_reads(&self) -> &HashSet<TypeId> {
+        &self.filter_reads
+    }
+
+    #[must_use]
+    pub fn writes(&self) -> &HashSet<TypeId> {
+        &self.writes
+    }
+
+    /// Merges `other` into `self`, with the same conflict rules as a single query.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `other` writes a type `self` accesses, or the other way round.
+    pub fn extend(&mut self, other: &ComponentAccess) {
+        assert!(
+            self.is_compatible(other),
+            "Conflicting component access within one system"
+        );
+        self.reads.extend(other.reads.iter().copied());
+        self.writes.extend(other.writes.iter().copied());
+        self.filter_reads.extend(other.filter_reads.iter().copied());
+    }
+
+    /// Two accesses are compatible if neither writes a type the other touches,
+    /// filters included.
+    #[must_use]
+    pub fn is_compatible(&self, other: &ComponentAccess) -> bool {
+        self.writes.is_disjoint(&other.reads)
+            && self.writes.is_disjoint(&other.writes)
+            && self.writes.is_disjoint(&other.filter_reads)
+            && other.writes.is_disjoint(&self.reads)
+            && other.writes.is_disjoint(&self.filter_reads)
+    }
+}
+
+impl<'w, Q: QueryData, F: QueryFilter> Query<'w, Q, F> {
+    /// # Safety
+    ///
+    /// Nothing else may access the components registered by `Q` and `F` for `'w`.
+    pub(crate) unsafe fn new(entity_manager: &'w EntityManager, ticks: TickRange) -> Self {
+        Self {
+            entity_manager,
+            ticks,
+            marker: PhantomData,
+        }
+    }
+
+    #[must_use]
+    pub fn component_access() -> ComponentAccess {
+        let mut access = ComponentAccess::default();
+        Q::component_access(&mut access);
+        F::component_access(&mut access);
+        access
+    }
+
+    pub fn iter_mut(&mut self) -> QueryIter<'_, Q, F> {
+        // SAFETY: `&mut self` rules out other items of this query being alive.
+        unsafe { QueryIter::new(self.entity_manager, self.ticks) }
+    }
+
+    /// Returns the item of one entity, or `None` if it does not match the query.
+    pub fn get_mut(&mut self, entity: Entity) -> Option<Q::Item<'_>> {
+        // SAFETY: `&mut self` rules out other items of this query being alive.
+        unsafe { self.get_unchecked(entity) }
+    }
+
+    #[must_use]
+    pub fn iter(&self) -> QueryIter<'_, Q, F>
+    where
+        Q: ReadOnlyQueryData,
+    {
+        // SAFETY: the query only reads, so its items may alias each other.
+        unsafe { QueryIter::new(self.entity_manager, self.ticks) }
+    }
+
+    #[must_use]
+    pub fn get(&self, entity: Entity) -> Option<Q::Item<'_>>
+    where
+        Q: ReadOnlyQueryData,
+    {
+        // SAFETY: the query only reads, so its items may alias each other.
+        unsafe { self.get_unchecked(entity) }
+    }
+
+    unsafe fn get_unchecked(&self, entity: Entity) -> Option<Q::Item<'_>> {
+        if !self.entity_manager.entity_exists(entity) {
+            return None;
+        }
+        let storage = &self.entity_manager.components;
+        // SAFETY: guaranteed by `Query::new` and the caller.
+        unsafe {
+            let fetch = Q::init_fetch(storage, self.ticks)?;
+            let filter = F::init_fetch(storage, self.ticks);
+            if !F::matches(&filter, entity) {
+                return None;
+            }
+            Q::fetch(&fetch, entity)
+        }
+    }
+}
+
+impl<'a, Q: ReadOnlyQueryData, F: QueryFilter> IntoIterator for &'a Query<'_, Q, F> {
+    type Item = Q::Item<'a>;
+    type IntoIter = QueryIter<'a, Q, F>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<'a, Q: QueryData, F: QueryFilter> IntoIterator for &'a mut Query<'_, Q, F> {
+    type Item = Q::Item<'a>;
+    type IntoIter = QueryIter<'a, Q, F>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter_mut()
+    }
+}
+
+impl TickRange {
+    pub(crate) fn new(last_run: Tick, this_run: Tick) -> Self {
+        Self { last_run, this_run }
+    }
+}
+
+impl<'w, Q: QueryData, F: QueryFilter> QueryIter<'w, Q, F> {
+    /// # Safety
+    ///
+    /// Nothing else may access the components registered by `Q` and `F` for `'w`.
+    pub(crate) unsafe fn new(entity_manager: &'w EntityManager, ticks: TickRange) -> Self {
+        let mut access = ComponentAccess::default();
+        Q::component_access(&mut access);
+        F::component_access(&mut access);
+
+        let storage = &entity_manager.components;
+        // SAFETY: guaranteed by the caller, and `component_access` has
+        // rejected aliasing terms inside the query.
+        let (fetch, filter) =
+            unsafe { (Q::init_fetch(storage, ticks), F::init_fetch(storage, ticks)) };
+        let entities = match fetch.as_ref().map(Q::required_entities) {
+            Some(required) => match smaller(required, F::required_entities(&filter)) {
+                Some(chunks) => chunks.into_iter().map(Cow::Borrowed).collect(),
+                None => vec![Cow::Owned(entity_manager.entities().collect())],
+            },
+            None => Vec::new(),
+        };
+
+        Self {
+            fetch,
+            filter,
+            entities,
+            chunk: 0,
+            position: 0,
+            marker: PhantomData,
+        }
+    }
+
+    /// Next item of an entity that also passes `accept`, which is checked
+    /// before anything is fetched.
+    fn next_where(&mut self, accept: impl Fn(Entity) -> bool) -> Option<Q::Item<'w>> {
+        let fetch = self.fetch.as_ref()?;
+        while let Some(chunk) = self.entities.get(self.chunk) {
+            let Some(&entity) = chunk.get(self.position) else {
+                self.chunk += 1;
+                self.position = 0;
+                continue;
+            };
+            self.position += 1;
+            if !F::matches(&self.filter, entity) || !accept(entity) {
+                continue;
+            }
+            // SAFETY: every entity appears once in the driving list, so items
+            // returned so far never alias this one.
+            if let Some(item) = unsafe { Q::fetch(fetch, entity) } {
+                return Some(item);
+            }
+        }
+        None
+    }
+}
+
+impl<'w, Q: QueryData, F: QueryFilter> Iterator for QueryIter<'w, Q, F> {
+    type Item = Q::Item<'w>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_where(|_| true)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining: usize = self.entities[self.chunk.min(self.entities.len())..]
+            .iter()
+            .map(|chunk| chunk.len())
+            .sum();
+        (0, Some(remaining.saturating_sub(self.position)))
+    }
+}
+
+impl<'w, Q: QueryData, F: QueryFilter> DynamicQueryIter<'w, Q, F> {
+    /// # Safety
+    ///
+    /// Nothing else may access the components registered by `Q` and `F`, nor
+    /// the dynamic components `ids`, for `'w`.
+    pub(crate) unsafe fn new(
+        entity_manager: &'w EntityManager,
+        ticks: TickRange,
+        ids: &[DynamicComponentId],
+    ) -> Self {
+        // SAFETY: guaranteed by the caller.
+        let (mut inner, sets) = unsafe {
+            (
+                QueryIter::new(entity_manager, ticks),
+                entity_manager.components.dynamic_sets(ids),
+            )
+        };
+        let Some(sets) = sets else {
+            // An unregistered dynamic component matches nothing.
+            inner.fetch = None;
+            return Self {
+                inner,
+                sets: Vec::new(),
+                this_run: ticks.this_run,
+            };
+        };
+        let dynamic = sets
+            .iter()
+            .map(|set| Some(vec![set.entities()]))
+            .fold(None, smaller);
+        let driving = inner
+            .entities
+            .iter()
+            .map(|chunk| chunk.len())
+            .sum::<usize>();
+        if let Some(dynamic) = dynamic.filter(|dynamic| dynamic[0].len() < driving) {
+            inner.entities = dynamic.into_iter().map(Cow::Borrowed).collect();
+        }
+        Self {
+            inner,
+            sets,
+            this_run: ticks.this_run,
+        }
+    }
+}
+
+impl<'w, Q: QueryData, F: QueryFilter> Iterator for DynamicQueryIter<'w, Q, F> {
+    type Item = (Q::Item<'w>, Vec<DynamicMut<'w>>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let sets = &self.sets;
+        // The dynamic sets are checked before `Q` is fetched, so entities
+        // missing one of them are never marked as changed.
+        let (entity, item) = self
+            .inner
+            .next_where(|entity| sets.iter().all(|set| set.contains(entity)))?;
+        let dynamic = self
+            .sets
+            .iter()
+            // SAFETY: every entity appears once in the driving list.
+            .filter_map(|set| unsafe { set.get(entity, self.this_run) })
+            .collect();
+        Some((item, dynamic))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, self.inner.size_hint().1)
+    }
+}
+
+unsafe impl<T: Send + Sync + 'static> QueryData for &T {
+    type Item<'w> = &'w T;
+    type Fetch<'w> = ComponentView<'w, T>;
+
+    fn component_access(access: &mut ComponentAccess) {
+        access.add_read::<T>();
+    }
+
+    unsafe fn init_fetch(storage: &ComponentStorage, _ticks: TickRange) -> Option<Self::Fetch<'_>> {
+        // SAFETY: guaranteed by the caller.
+        unsafe { storage.view::<T>() }
+    }
+
+    fn required_entities<'w>(fetch: &Self::Fetch<'w>) -> Option<EntityChunks<'w>> {
+        Some(fetch.entities())
+    }
+
+    fn matches(fetch: &Self::Fetch<'_>, entity: Entity) -> bool {
+        fetch.contains(entity)
+    }
+
+    unsafe fn fetch<'w>(fetch: &Self::Fetch<'w>, entity: Entity) -> Option<Self::Item<'w>> {
+        fetch.get(entity)
+    }
+}
+
+unsafe impl<T: Send + Sync + 'static> ReadOnlyQueryData for &T {}
+
+unsafe impl<T: Send + Sync + 'static> QueryData for &mut T {
+    type Item<'w> = &'w mut T;
+    type Fetch<'w> = ComponentViewMut<'w, T>;
+
+    fn component_access(access: &mut ComponentAccess) {
+        assert!(
+            TypeId::of::<T>() != TypeId::of::<Name>(),
+            "Query writes Name, which would leave the name index stale; use EntityManager::set_name"
+        );
+        assert!(
+            TypeId::of::<T>() != TypeId::of::<Parent>()
+                && TypeId::of::<T>() != TypeId::of::<Children>(),
+            "Query writes {}, which is maintained by the hierarchy; use EntityManager::set_parent",
+            type_name::<T>()
+        );
+        access.add_write::<T>();
+    }
+
+    unsafe fn init_fetch(storage: &ComponentStorage, ticks: TickRange) -> Option<Self::Fetch<'_>> {
+        // SAFETY: guaranteed by the caller.
+        unsafe { storage.view_mut::<T>(ticks.this_run) }
+    }
+
+    fn required_entities<'w>(fetch: &Self::Fetch<'w>) -> Option<EntityChunks<'w>> {
+        Some(fetch.entities())
+    }
+
+    fn matches(fetch: &Self::Fetch<'_>, entity: Entity) -> bool {
+        fetch.contains(entity)
+    }
+
+    unsafe fn fetch<'w>(fetch: &Self::Fetch<'w>, entity: Entity) -> Option<Self::Item<'w>> {
+        // SAFETY: guaranteed by the caller.
+        unsafe { fetch.get(entity) }
+    }
+}
+
+unsafe impl<Q: QueryData> QueryData for Option<Q> {
+    type Item<'w> = Option<Q::Item<'w>>;
+    type Fetch<'w> = Option<Q::Fetch<'w>>;
+
+    fn component_access(access: &mut ComponentAccess) {
+        Q::component_access(access);
+    }
+
+    unsafe fn init_fetch(storage: &ComponentStorage, ticks: TickRange) -> Option<Self::Fetch<'_>> {
+        // SAFETY: guaranteed by the caller.
+        Some(unsafe { Q::init_fetch(storage, ticks) })
+    }
+
+    fn required_entities<'w>(_fetch: &Self::Fetch<'w>) -> Option<EntityChunks<'w>> {
+        None
+    }
+
+    fn matches(_fetch: &Self::Fetch<'_>, _entity: Entity) -> bool {
+        true
+    }
+
+    unsafe fn fetch<'w>(fetch: &Self::Fetch<'w>, entity: Entity) -> Option<Self::Item<'w>> {
+        // SAFETY: guaranteed by the caller.
+        Some(
+            fetch
+                .as_ref()
+                .and_then(|fetch| unsafe { Q::fetch(fetch, entity) }),
+        )
+    }
+}
+
+unsafe impl<Q: ReadOnlyQueryData> ReadOnlyQueryData for Option<Q> {}
+
+unsafe impl QueryData for Entity {
+    type Item<'w> = Entity;
+    type Fetch<'w> = ();
+
+    fn component_access(_access: &mut ComponentAccess) {}
+
+    unsafe fn init_fetch(
+        _storage: &ComponentStorage,
+        _ticks: TickRange,
+    ) -> Option<Self::Fetch<'_>> {
+        Some(())
+    }
+
+    fn required_entities<'w>(_fetch: &Self::Fetch<'w>) -> Option<EntityChunks<'w>> {
+        None
+    }
+
+    fn matches(_fetch: &Self::Fetch<'_>, _entity: Entity) -> bool {
+        true
+    }
+
+    unsafe fn fetch<'w>(_fetch: &Self::Fetch<'w>, entity: Entity) -> Option<Self::Item<'w>> {
+        Some(entity)
+    }
+}
+
+unsafe impl ReadOnlyQueryData for Entity {}
+
+fn smaller<'w>(
+    current: Option<EntityChunks<'w>>,
+    other: Option<EntityChunks<'w>>,
+) -> Option<EntityChunks<'w>> {
+    let len = |chunks: &EntityChunks<'w>| chunks.iter().map(|chunk| chunk.len()).sum::<usize>();
+    match (current, other) {
+        (Some(current), Some(other)) if len(&other) < len(&current) => Some(other),
+        (None, other) => other,
+        (current, _) => current,
+    }
+}
+
+macro_rules! impl_query_data_for_tuple {
+    ($($Q:ident),+) => {
+        #[allow(non_snake_case)]
+        unsafe impl<$($Q: QueryData),+> QueryData for ($($Q,)+) {
+            type Item<'w> = ($($Q::Item<'w>,)+);
+            type Fetch<'w> = ($($Q::Fetch<'w>,)+);
+
+            fn component_access(access: &mut ComponentAccess) {
+                $($Q::component_access(access);)+
+            }
+
+            unsafe fn init_fetch(storage: &ComponentStorage, ticks: TickRange) -> Option<Self::Fetch<'_>> {
+                // SAFETY: guaranteed by the caller.
+                Some(($(unsafe { $Q::init_fetch(storage, ticks) }?,)+))
+            }
+
+            fn required_entities<'w>(fetch: &Self::Fetch<'w>) -> Option<EntityChunks<'w>> {
+                let ($($Q,)+) = fetch;
+                let entities = None;
+                $(let entities = smaller(entities, $Q::required_entities($Q));)+
+                entities
+            }
+
+            fn matches(fetch: &Self::Fetch<'_>, entity: Entity) -> bool {
+                let ($($Q,)+) = fetch;
+                $($Q::matches($Q, entity))&&+
+            }
+
+            unsafe fn fetch<'w>(fetch: &Self::Fetch<'w>, entity: Entity) -> Option<Self::Item<'w>> {
+                if !Self::matches(fetch, entity) {
+                    return None;
+                }
+                let ($($Q,)+) = fetch;
+                // SAFETY: guaranteed by the caller.
+                Some(($(unsafe { $Q::fetch($Q, entity) }?,)+))
+            }
+        }
+
+        unsafe impl<$($Q: ReadOnlyQueryData),+> ReadOnlyQueryData for ($($Q,)+) {}
+    };
+}
+
+impl_query_data_for_tuple!(Q1);
+impl_query_data_for_tuple!(Q1, Q2);
+impl_query_data_for_tuple!(Q1, Q2, Q3);
+impl_query_data_for_tuple!(Q1, Q2, Q3, Q4);
+impl_query_data_for_tuple!(Q1, Q2, Q3, Q4, Q5);
+impl_query_data_for_tuple!(Q1, Q2, Q3, Q4, Q5, Q6);
+impl_query_data_for_tuple!(Q1, Q2, Q3, Q4, Q5, Q6, Q7);
+impl_query_data_for_tuple!(Q1, Q2, Q3, Q4, Q5, Q6, Q7, Q8);
+
+unsafe impl<T: Send + Sync + 'static> QueryFilter for With<T> {
+    type Fetch<'w> = Option<ComponentTicksView<'w>>;
+
+    fn component_access(access: &mut ComponentAccess) {
+        access.add_filter_read::<T>();
+    }
+
+    unsafe fn init_fetch(storage: &ComponentStorage, _ticks: TickRange) -> Self::Fetch<'_> {
+        // SAFETY: guaranteed by the caller.
+        unsafe { storage.ticks_view::<T>() }
+    }
+
+    fn required_entities<'w>(fetch: &Self::Fetch<'w>) -> Option<EntityChunks<'w>> {
+        Some(
+            fetch
+                .as_ref()
+                .map_or_else(Vec::new, ComponentTicksView::entities),
+        )
+    }
+
+    fn matches(fetch: &Self::Fetch<'_>, entity: Entity) -> bool {
+        fetch.as_ref().is_some_and(|view| view.contains(entity))
+    }
+}
+
+unsafe impl<T: Send + Sync + 'static> QueryFilter for Without<T> {
+    type Fetch<'w> = Option<ComponentTicksView<'w>>;
+
+    fn component_access(access: &mut ComponentAccess) {
+        access.add_filter_read::<T>();
+    }
+
+    unsafe fn init_fetch(storage: &ComponentStorage, _ticks: TickRange) -> Self::Fetch<'_> {
+        // SAFETY: guaranteed by the caller.
+        unsafe { storage.ticks_view::<T>() }
+    }
+
+    fn matches(fetch: &Self::Fetch<'_>, entity: Entity) -> bool {
+        !fetch.as_ref().is_some_and(|view| view.contains(entity))
+    }
+}
+
+unsafe impl<T: Send + Sync + 'static> QueryFilter for Added<T> {
+    type Fetch<'w> = (Option<ComponentTicksView<'w>>, TickRange);
+
+    fn component_access(access: &mut ComponentAccess) {
+        access.add_filter_read::<T>();
+    }
+
+    unsafe fn init_fetch(storage: &ComponentStorage, ticks: TickRange) -> Self::Fetch<'_> {
+        // SAFETY: guaranteed by the caller.
+        (unsafe { storage.ticks_view::<T>() }, ticks)
+    }
+
+    fn required_entities<'w>((view, _): &Self::Fetch<'w>) -> Option<EntityChunks<'w>> {
+        Some(
+            view.as_ref()
+                .map_or_else(Vec::new, ComponentTicksView::entities),
+        )
+    }
+
+    fn matches((view, ticks): &Self::Fetch<'_>, entity: Entity) -> bool {
+        view.as_ref()
+            .and_then(|view| view.added(entity))
+            .is_some_and(|added| added.is_newer_than(ticks.last_run, ticks.this_run))
+    }
+}
+
+unsafe impl<T: Send + Sync + 'static> QueryFilter for Changed<T> {
+    type Fetch<'w> = (Option<ComponentTicksView<'w>>, TickRange);
+
+    fn component_access(access: &mut ComponentAccess) {
+        access.add_filter_read::<T>();
+    }
+
+    unsafe fn init_fetch(storage: &ComponentStorage, ticks: TickRange) -> Self::Fetch<'_> {
+        // SAFETY: guaranteed by the caller.
+        (unsafe { storage.ticks_view::<T>() }, ticks)
+    }
+
+    fn required_entities<'w>((view, _): &Self::Fetch<'w>) -> Option<EntityChunks<'w>> {
+        Some(
+            view.as_ref()
+                .map_or_else(Vec::new, ComponentTicksView::entities),
+        )
+    }
+
+    fn matches((view, ticks): &Self::Fetch<'_>, entity: Entity) -> bool {
+        view.as_ref()
+            .and_then(|view| view.changed(entity))
+            .is_some_and(|changed| changed.is_newer_than(ticks.last_run, ticks.this_run))
+    }
+}
+
+unsafe impl QueryFilter for () {
+    type Fetch<'w> = ();
+
+    unsafe fn init_fetch(_storage: &ComponentStorage, _ticks: TickRange) -> Self::Fetch<'_> {}
+
+    fn matches(_fetch: &Self::Fetch<'_>, _entity: Entity) -> bool {
+        true
+    }
+}
+
+macro_rules! impl_query_filter_for_tuple {
+    ($($F:ident),+) => {
+        #[allow(non_snake_case)]
+        unsafe impl<$($F: QueryFilter),+> QueryFilter for ($($F,)+) {
+            type Fetch<'w> = ($($F::Fetch<'w>,)+);
+
+            fn component_access(access: &mut ComponentAccess) {
+                $($F::component_access(access);)+
+            }
+
+            unsafe fn init_fetch(storage: &ComponentStorage, ticks: TickRange) -> Self::Fetch<'_> {
+                // SAFETY: guaranteed by the caller.
+                ($(unsafe { $F::init_fetch(storage, ticks) },)+)
+            }
+
+            fn required_entities<'w>(fetch: &Self::Fetch<'w>) -> Option<EntityChunks<'w>> {
+                let ($($F,)+) = fetch;
+                let entities = None;
+                $(let entities = smaller(entities, $F::required_entities($F));)+
+                entities
+            }
+
+            fn matches(fetch: &Self::Fetch<'_>, entity: Entity) -> bool {
+                let ($($F,)+) = fetch;
+                $($F::matches($F, entity))&&+
+            }
+        }
+
+        #[allow(non_snake_case)]
+        unsafe impl<$($F: QueryFilter),+> QueryFilter for Or<($($F,)+)> {
+            type Fetch<'w> = ($($F::Fetch<'w>,)+);
+
+            fn component_access(access: &mut ComponentAccess) {
+                $($F::component_access(access);)+
+            }
+
+            unsafe fn init_fetch(storage: &ComponentStorage, ticks: TickRange) -> Self::Fetch<'_> {
+                // SAFETY: guaranteed by the caller.
+                ($(unsafe { $F::init_fetch(storage, ticks) },)+)
+            }
+
+            fn matches(fetch: &Self::Fetch<'_>, entity: Entity) -> bool {
+                let ($($F,)+) = fetch;
+                $($F::matches($F, entity))||+
+            }
+        }
+    };
+}
+
+impl_query_filter_for_tuple!(F1);
+impl_query_filter_for_tuple!(F1, F2);
+impl_query_filter_for_tuple!(F1, F2, F3);
+impl_query_filter_for_tuple!(F1, F2, F3, F4);
+impl_query_filter_for_tuple!(F1, F2, F3, F4, F5);
+impl_query_filter_for_tuple!(F1, F2, F3, F4, F5, F6);
+impl_query_filter_for_tuple!(F1, F2, F3, F4, F5, F6, F7);
+impl_query_filter_for_tuple!(F1, F2, F3, F4, F5, F6, F7, F8);
+
+#[cfg(test)]
+mod tests {
+    use glam::Vec3;
+
+    use super::{Added, Changed, Or, With, Without};
+    use crate::components::{
+        color::{Color, RGBA},
+        material::Material,
+        shape::Shape,
+        transform::Transform,
+    };
+    use crate::entity::dynamic::DynamicLayout;
+    use crate::entity::{Entity, EntityManager};
+
+    struct Player;
+
+    fn triangle() -> Shape {
+        Shape::new_triangle(
+            Vec3::new(0.0, 0.0, 0.0),
+            Vec3::new(1.0, 0.0, 0.0),
+            Vec3::new(0.0, 1.0, 0.0),
+        )
+    }
+
+    #[test]
+    fn test_query_single_component() {
+        let mut entity_manager = EntityManager::new(10);
+        entity_manager.create_entity((triangle(),));
+        entity_manager.create_entity((Color::default(),));
+        entity_manager.create_entity((triangle(), Color::default()));
+
+        assert_eq!(entity_manager.query::<&Shape>().count(), 2);
+        assert_eq!(entity_manager.query::<&Color>().count(), 2);
+    }
+
+    #[test]
+    fn test_query_joins_components() {
+        let mut entity_manager = EntityManager::new(10);
+        let only_shape = entity_manager.create_entity((triangle(),));
+        let full = entity_manager.create_entity((
+            triangle(),
+            Transform::identity(),
+            Color::Uniform(RGBA::new(1, 2, 3, 1.0)),
+        ));
+        let no_color = entity_manager.create_entity((triangle(), Transform::identity()));
+
+        let mut matched: Vec<(Entity, bool)> = entity_manager
+            .query::<(Entity, &Shape, &mut Transform, Option<&Color>)>()
+            .map(|(entity, _, _, color)| (entity, color.is_some()))
+            .collect();
+        matched.sort_by_key(|(entity, _)| entity.index());
+
+        assert_eq!(matched, vec![(full, true), (no_color, false)]);
+        assert!(!matched.iter().any(|(entity, _)| *entity == only_shape));
+    }
+
+    #[test]
+    fn test_query_mutates_components() {
+        let mut entity_manager = EntityManager::new(10);
+        let first = entity_manager.create_entity((Transform::identity(),));
+        let second = entity_manager.create_entity((Transform::identity(),));
+
+        for transform in entity_manager.query::<&mut Transform>() {
+            *transform = Transform::from_translation(Vec3::new(1.0, 2.0, 3.0));
+        }
+
+        for entity in [first, second] {
+            assert_eq!(
+                entity_manager
+                    .get_component::<Transform>(entity)
+                    .unwrap()
+                    .matrix()
+                    .w_axis
+                    .truncate(),
+                Vec3::new(1.0, 2.0, 3.0)
+            );
+        }
+    }
+
+    #[test]
+    fn test_query_skips_removed_entities() {
+        let mut entity_manager = EntityManager::new(10);
+        let removed = entity_manager.create_entity((triangle(), Color::default()));
+        let kept = entity_manager.create_entity((triangle(), Color::default()));
+        entity_manager.remove_entity(removed);
+
+        let entities: Vec<Entity> = entity_manager
+            .query::<(Entity, &Shape, &Color)>()
+            .map(|(entity, _, _)| entity)
+            .collect();
+
+        assert_eq!(entities, vec![kept]);
+    }
+
+    #[test]
+    fn test_query_unregistered_component_is_empty() {
+        let mut entity_manager = EntityManager::new(10);
+        entity_manager.create_entity((triangle(),));
+
+        assert_eq!(entity_manager.query::<(&Shape, &Transform)>().count(), 0);
+        assert_eq!(
+            entity_manager
+                .query::<(&Shape, Option<&Transform>)>()
+                .count(),
+            1
+        );
+    }
+
+    #[test]
+    fn test_query_only_optional_terms_visits_every_entity() {
+        let mut entity_manager = EntityManager::new(10);
+        entity_manager.create_entity((triangle(),));
+        entity_manager.create_entity((Color::default(),));
+        let removed = entity_manager.create_entity((Color::default(),));
+        entity_manager.remove_entity(removed);
+
+        let shapes: Vec<bool> = entity_manager
+            .query::<(Entity, Option<&Shape>)>()
+            .map(|(_, shape)| shape.is_some())
+            .collect();
+
+        assert_eq!(shapes, vec![true, false]);
+    }
+
+    #[test]
+    #[should_panic(expected = "while also")]
+    fn test_query_rejects_aliasing_access() {
+        let mut entity_manager = EntityManager::new(10);
+        entity_manager.create_entity((Transform::identity(),));
+
+        let _ = entity_manager
+            .query::<(&mut Transform, &Transform)>()
+            .count();
+    }
+
+    #[test]
+    fn test_query_filter_without() {
+        let mut entity_manager = EntityManager::new(10);
+        let plain = entity_manager.create_entity((triangle(),));
+        entity_manager.create_entity((
+            triangle(),
+            Material {
+                shader_id: 1,
+                color: Color::default(),
+            },
+        ));
+
+        let entities: Vec<Entity> = entity_manager
+            .query_filtered::<Entity, (With<Shape>, Without<Material>)>()
+            .collect();
+
+        assert_eq!(entities, vec![plain]);
+    }
+
+    #[test]
+    fn test_query_filter_with_tag() {
+        let mut entity_manager = EntityManager::new(10);
+        entity_manager.create_entity((Transform::identity(),));
+        let player = entity_manager.create_entity((Transform::identity(), Player));
+
+        let entities: Vec<Entity> = entity_manager
+            .query_filtered::<(Entity, &mut Transform), With<Player>>()
+            .map(|(entity, _)| entity)
+            .collect();
+
+        assert_eq!(entities, vec![player]);
+    }
+
+    #[test]
+    fn test_query_filter_with_unregistered_component() {
+        let mut entity_manager = EntityManager::new(10);
+        entity_manager.create_entity((triangle(),));
+
+        assert_eq!(
+            entity_manager
+                .query_filtered::<&Shape, With<Player>>()
+                .count(),
+            0
+        );
+        assert_eq!(
+            entity_manager
+                .query_filtered::<&Shape, Without<Player>>()
+                .count(),
+            1
+        );
+    }
+
+    #[test]
+    fn test_query_filter_or() {
+        let mut entity_manager = EntityManager::new(10);
+        let shape = entity_manager.create_entity((triangle(),));
+        let color = entity_manager.create_entity((Color::default(),));
+        entity_manager.create_entity((Transform::identity(),));
+
+        let mut entities: Vec<Entity> = entity_manager
+            .query_filtered::<Entity, Or<(With<Shape>, With<Color>)>>()
+            .collect();
+        entities.sort_by_key(|entity| entity.index());
+
+        assert_eq!(entities, vec![shape, color]);
+    }
+
+    #[test]
+    fn test_query_filter_added() {
+        let mut entity_manager = EntityManager::new(10);
+        let first = entity_manager.create_entity((Transform::identity(),));
+        assert_eq!(
+            entity_manager
+                .query_filtered::<Entity, Added<Transform>>()
+                .collect::<Vec<_>>(),
+            vec![first]
+        );
+
+        entity_manager.advance_tick();
+        let second = entity_manager.create_entity((Transform::identity(),));
+        entity_manager.add_component(first, Transform::identity());
+
+        assert_eq!(
+            entity_manager
+                .query_filtered::<Entity, Added<Transform>>()
+                .collect::<Vec<_>>(),
+            vec![second]
+        );
+    }
+
+    #[test]
+    fn test_query_filter_changed() {
+        let mut entity_manager = EntityManager::new(10);
+        entity_manager.create_entity((Transform::identity(), Color::default()));
+        let second = entity_manager.create_entity((Transform::identity(), Color::default()));
+
+        entity_manager.advance_tick();
+        assert_eq!(
+            entity_manager
+                .query_filtered::<Entity, Changed<Transform>>()
+                .count(),
+            0
+        );
+
+        entity_manager
+            .get_component_mut::<Transform>(second)
+            .unwrap();
+        assert_eq!(
+            entity_manager
+                .query_filtered::<Entity, Changed<Transform>>()
+                .collect::<Vec<_>>(),
+            vec![second]
+        );
+
+        // Entities rejected by the filter are never fetched, so their colors stay unchanged.
+        entity_manager
+            .query_filtered::<&mut Color, With<Shape>>()
+            .for_each(|color| *color = Color::default());
+        assert_eq!(
+            entity_manager
+                .query_filtered::<Entity, Changed<Color>>()
+                .count(),
+            0
+        );
+
+        entity_manager.advance_tick();
+        entity_manager
+            .query_filtered::<&mut Transform, Changed<Transform>>()
+            .for_each(|transform| *transform = Transform::identity());
+        assert_eq!(
+            entity_manager
+                .query_filtered::<Entity, Changed<Transform>>()
+                .count(),
+            0
+        );
+    }
+
+    #[test]
+    fn test_query_changed_only_for_yielded_entities() {
+        let mut entity_manager = EntityManager::new(10);
+        let both = entity_manager.create_entity((Color::default(), triangle()));
+        let color_only = entity_manager.create_entity((Color::default(),));
+        entity_manager.create_entity((triangle(),));
+        entity_manager.create_entity((triangle(),));
+        let tag = entity_manager.register_dynamic_component(DynamicLayout::new("Tag"));
+        entity_manager.add_dynamic_component(both, tag, &[]);
+
+        entity_manager.advance_tick();
+        assert_eq!(entity_manager.query::<(&mut Color, &Shape)>().count(), 1);
+        assert_eq!(
+            entity_manager
+                .query_filtered::<Entity, Changed<Color>>()
+                .collect::<Vec<_>>(),
+            vec![both]
+        );
+
+        entity_manager.advance_tick();
+        assert_eq!(
+            entity_manager.query_dynamic::<&mut Color>(&[tag]).count(),
+            1
+        );
+        assert_eq!(
+            entity_manager
+                .query_filtered::<Entity, Changed<Color>>()
+                .collect::<Vec<_>>(),
+            vec![both]
+        );
+        assert!(
+            !entity_manager
+                .query_filtered::<Entity, Changed<Color>>()
+                .any(|entity| entity == color_only)
+        );
+    }
+
+    fn table_entity_manager() -> EntityManager {
+        let mut entity_manager = EntityManager::new(10);
+        entity_manager.register_component::<Transform>();
+        entity_manager.register_component::<Shape>();
+        entity_manager.register_component::<Color>();
+        entity_manager
+    }
+
+    #[test]
+    fn test_query_spans_table_and_sparse_storage() {
+        let mut entity_manager = table_entity_manager();
+        let full =
+            entity_manager.create_entity((triangle(), Transform::identity(), Color::default()));
+        let no_color = entity_manager.create_entity((triangle(), Transform::identity()));
+        let player = entity_manager.create_entity((Transform::identity(), Player));
+        let shape_player = entity_manager.create_entity((triangle(), Color::default(), Player));
+
+        let mut matched: Vec<(Entity, bool)> = entity_manager
+            .query::<(Entity, &Shape, &mut Transform, Option<&Color>)>()
+            .map(|(entity, _, _, color)| (entity, color.is_some()))
+            .collect();
+        matched.sort_by_key(|(entity, _)| entity.index());
+        assert_eq!(matched, vec![(full, true), (no_color, false)]);
+
+        let mut players: Vec<Entity> = entity_manager
+            .query_filtered::<Entity, (With<Player>, With<Shape>)>()
+            .collect();
+        players.sort_by_key(|entity| entity.index());
+        assert_eq!(players, vec![shape_player]);
+
+        for transform in entity_manager.query::<&mut Transform>() {
+            *transform = Transform::from_translation(Vec3::new(1.0, 2.0, 3.0));
+        }
+        for entity in [full, no_color, player] {
+            assert_eq!(
+                entity_manager
+                    .get_component::<Transform>(entity)
+                    .unwrap()
+                    .matrix()
+                    .w_axis
+                    .truncate(),
+                Vec3::new(1.0, 2.0, 3.0)
+            );
+        }
+    }
+
+    #[test]
+    fn test_query_table_components_follow_archetype_moves() {
+        let mut entity_manager = table_entity_manager();
+        let first = entity_manager.create_entity((triangle(), Color::default()));
+        let second = entity_manager.create_entity((triangle(), Color::default()));
+
+        entity_manager.remove_component::<Color>(first);
+        entity_manager.add_component(second, Transform::identity());
+        entity_manager.remove_entity(first);
+
+        assert_eq!(
+            entity_manager
+                .query::<(Entity, &Shape, &Color, &Transform)>()
+                .map(|(entity, ..)| entity)
+                .collect::<Vec<_>>(),
+            vec![second]
+        );
+        assert_eq!(entity_manager.query::<&Shape>().count(), 1);
+        assert_eq!(
+            entity_manager.get_component::<Shape>(second),
+            Some(&triangle())
+        );
+    }
+
+    #[test]
+    fn test_query_filter_changed_on_table_component() {
+        let mut entity_manager = table_entity_manager();
+        let first = entity_manager.create_entity((Transform::identity(), Color::default()));
+        let second = entity_manager.create_entity((Transform::identity(),));
+
+        entity_manager.advance_tick();
+        entity_manager
+            .get_component_mut::<Transform>(second)
+            .unwrap();
+        let third = entity_manager.create_entity((Transform::identity(), Player));
+
+        assert_eq!(
+            entity_manager
+                .query_filtered::<Entity, Added<Transform>>()
+                .collect::<Vec<_>>(),
+            vec![third]
+        );
+        let mut changed: Vec<Entity> = entity_manager
+            .query_filtered::<Entity, Changed<Transform>>()
+            .collect();
+        changed.sort_by_key(|entity| entity.index());
+        assert_eq!(changed, vec![second, third]);
+        assert!(
+            !entity_manager
+                .query_filtered::<Entity, Changed<Transform>>()
+                .any(|entity| entity == first)
+        );
+    }
+}
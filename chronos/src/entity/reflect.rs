@@ -0,0 +1,654 @@
+//! Runtime descriptions of component types, for inspectors, scripting and
+//! serialization that only know components by name.
+//!
+//! A type registered with [`TypeRegistry::register`] exposes its fields by
+//! name as scene [`Value`]s; one registered with
+//! [`TypeRegistry::register_serializable`] can be written and read as a whole.
+//! The built-in components are registered both ways already.
+//!
+//! ```ignore
+//! for component in entity_manager.components_of(entity) {
+//!     println!("{}", component.name());
+//! }
+//! entity_manager.set_field(entity, "RGBA", "alpha", &Value::number(0.5))?;
+//! ```
+
+mod builtins;
+
+use std::any::{Any, TypeId, type_name};
+use std::collections::HashMap;
+
+use crate::entity::{Entity, EntityManager};
+use crate::scene::{self, EntityMap, SceneComponent, SceneError, Value};
+
+pub type Result<T> = std::result::Result<T, ReflectError>;
+
+#[derive(thiserror::Error, Debug)]
+pub enum ReflectError {
+    #[error("Type '{0}' is not registered")]
+    UnknownType(String),
+    #[error("Type '{type_name}' has no field '{field}'")]
+    UnknownField {
+        type_name: &'static str,
+        field: String,
+    },
+    #[error("Type '{0}' does not expose its fields")]
+    NotReflected(&'static str),
+    #[error("Entity {0:?} has no '{1}' component")]
+    MissingComponent(Entity, &'static str),
+    #[error(transparent)]
+    Value(#[from] SceneError),
+}
+
+/// Component whose fields can be read and written by name. Register it with
+/// [`EntityManager::register_reflect`].
+pub trait Reflect: Send + Sync + 'static {
+    /// Descriptors of the fields, in declaration order.
+    fn fields() -> Vec<FieldInfo>;
+
+    /// # Errors
+    ///
+    /// Returns an error if the type has no field called `name`.
+    fn field(&self, name: &str) -> Result<Value>;
+
+    /// # Errors
+    ///
+    /// Returns an error if the type has no field called `name` or `value`
+    /// does not fit it.
+    fn set_field(&mut self, name: &str, value: &Value) -> Result<()>;
+}
+
+/// Name and type of a field.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct FieldInfo {
+    name: &'static str,
+    type_name: &'static str,
+}
+
+/// Everything known about a registered type.
+pub struct TypeInfo {
+    type_id: TypeId,
+    type_name: &'static str,
+    name: &'static str,
+    fields: Vec<FieldInfo>,
+    reflect: Option<FieldAccess>,
+    serde: Option<Serde>,
+}
+
+#[derive(Clone, Copy)]
+struct FieldAccess {
+    get: fn(&dyn Any, &str) -> Result<Value>,
+    set: fn(&mut dyn Any, &str, &Value) -> Result<()>,
+}
+
+#[derive(Clone, Copy)]
+struct Serde {
+    serialize: fn(&dyn Any, &EntityMap) -> scene::Result<Value>,
+    deserialize: fn(&Value, &EntityMap) -> scene::Result<Box<dyn Any + Send + Sync>>,
+}
+
+/// Registered types keyed by [`TypeId`], also looked up by name.
+pub struct TypeRegistry {
+    types: HashMap<TypeId, TypeInfo>,
+    names: HashMap<&'static str, TypeId>,
+}
+
+/// A component of an entity, with its description if its type is registered.
+#[derive(Clone, Copy)]
+pub struct ComponentType<'a> {
+    type_id: TypeId,
+    type_name: &'static str,
+    info: Option<&'a TypeInfo>,
+}
+
+impl FieldInfo {
+    #[must_use]
+    pub fn new<T: 'static>(name: &'static str) -> Self {
+        Self {
+            name,
+            type_name: type_name::<T>(),
+        }
+    }
+
+    #[must_use]
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    #[must_use]
+    pub fn type_name(&self) -> &'static str {
+        self.type_name
+    }
+}
+
+impl TypeInfo {
+    fn new<T: 'static>() -> Self {
+        Self {
+            type_id: TypeId::of::<T>(),
+            type_name: type_name::<T>(),
+            name: short_name(type_name::<T>()),
+            fields: Vec::new(),
+            reflect: None,
+            serde: None,
+        }
+    }
+
+    #[must_use]
+    pub fn type_id(&self) -> TypeId {
+        self.type_id
+    }
+
+    /// Full Rust path of the type.
+    #[must_use]
+    pub fn type_name(&self) -> &'static str {
+        self.type_name
+    }
+
+    /// Name the type is looked up by: its scene name if it is serializable,
+    /// its Rust name without the module path otherwise.
+    #[must_use]
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// Fields of the type, empty unless it is registered with
+    /// [`TypeRegistry::register`].
+    #[must_use]
+    pub fn fields(&self) -> &[FieldInfo] {
+        &self.fields
+    }
+
+    #[must_use]
+    pub fn is_reflected(&self) -> bool {
+        self.reflect.is_some()
+    }
+
+    #[must_use]
+    pub fn is_serializable(&self) -> bool {
+        self.serde.is_some()
+    }
+
+    /// Reads a field of `component`, a value of this type.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the type does not expose its fields or has no
+    /// field called `name`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `component` is not of this type.
+    pub fn field(&self, component: &dyn Any, name: &str) -> Result<Value> {
+        let reflect = self
+            .reflect
+            .ok_or(ReflectError::NotReflected(self.type_name))?;
+        (reflect.get)(component, name)
+    }
+
+    /// Writes a field of `component`, a value of this type.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the type does not expose its fields, has no field
+    /// called `name`, or `value` does not fit it.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `component` is not of this type.
+    pub fn set_field(&self, component: &mut dyn Any, name: &str, value: &Value) -> Result<()> {
+        let reflect = self
+            .reflect
+            .ok_or(ReflectError::NotReflected(self.type_name))?;
+        (reflect.set)(component, name, value)
+    }
+
+    /// Writes `component`, a value of this type, as in scene files. `None`
+    /// if the type is not serializable.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `component` is not of this type.
+    #[must_use]
+    pub fn serialize(
+        &self,
+        component: &dyn Any,
+        entities: &EntityMap,
+    ) -> Option<scene::Result<Value>> {
+        self.serde
+            .map(|serde| (serde.serialize)(component, entities))
+    }
+
+    /// Reads a value of this type as written by [`TypeInfo::serialize`].
+    /// `None` if the type is not serializable.
+    #[must_use]
+    pub fn deserialize(
+        &self,
+        value: &Value,
+        entities: &EntityMap,
+    ) -> Option<scene::Result<Box<dyn Any + Send + Sync>>> {
+        self.serde.map(|serde| (serde.deserialize)(value, entities))
+    }
+}
+
+impl TypeRegistry {
+    /// Exposes the fields of `T`.
+    pub fn register<T: Reflect>(&mut self) {
+        let info = self.entry::<T>();
+        info.fields = T::fields();
+        info.reflect = Some(FieldAccess {
+            get: |component, name| downcast_ref::<T>(component).field(name),
+            set: |component, name, value| downcast_mut::<T>(component).set_field(name, value),
+        });
+    }
+
+    /// Lets `T` be serialized, and looked up by its scene name.
+    pub fn register_serializable<T: SceneComponent>(&mut self) {
+        let info = self.entry::<T>();
+        let previous_name = std::mem::replace(&mut info.name, T::NAME);
+        info.serde = Some(Serde {
+            serialize: |component, entities| downcast_ref::<T>(component).to_value(entities),
+            deserialize: |value, entities| {
+                T::from_value(value, entities)
+                    .map(|component| Box::new(component) as Box<dyn Any + Send + Sync>)
+            },
+        });
+        if self.names.get(previous_name) == Some(&TypeId::of::<T>()) {
+            self.names.remove(previous_name);
+        }
+        self.names.insert(T::NAME, TypeId::of::<T>());
+    }
+
+    #[must_use]
+    pub fn get(&self, type_id: TypeId) -> Option<&TypeInfo> {
+        self.types.get(&type_id)
+    }
+
+    /// Type registered under `name`, see [`TypeInfo::name`].
+    #[must_use]
+    pub fn get_by_name(&self, name: &str) -> Option<&TypeInfo> {
+        self.names
+            .get(name)
+            .and_then(|type_id| self.types.get(type_id))
+    }
+
+    #[must_use]
+    pub fn contains(&self, type_id: TypeId) -> bool {
+        self.types.contains_key(&type_id)
+    }
+
+    /// Registered types in no particular order.
+    pub fn iter(&self) -> impl Iterator<Item = &TypeInfo> {
+        self.types.values()
+    }
+
+    fn entry<T: 'static>(&mut self) -> &mut TypeInfo {
+        let info = self
+            .types
+            .entry(TypeId::of::<T>())
+            .or_insert_with(TypeInfo::new::<T>);
+        self.names.entry(info.name).or_insert(info.type_id);
+        info
+    }
+}
+
+impl Default for TypeRegistry {
+    /// Registry with the built-in components: `Transform`, `Shape`, `Color`,
+    /// `RGBA`, `Material` and `Name` expose their fields, and they are
+    /// serializable along with `Parent`.
+    fn default() -> Self {
+        let mut registry = Self {
+            types: HashMap::new(),
+            names: HashMap::new(),
+        };
+        builtins::register(&mut registry);
+        registry
+    }
+}
+
+impl<'a> ComponentType<'a> {
+    #[must_use]
+    pub fn type_id(&self) -> TypeId {
+        self.type_id
+    }
+
+    /// Full Rust path of the type.
+    #[must_use]
+    pub fn type_name(&self) -> &'static str {
+        self.type_name
+    }
+
+    /// [`TypeInfo::name`] if the type is registered, its Rust name without
+    /// the module path otherwise.
+    #[must_use]
+    pub fn name(&self) -> &'static str {
+        self.info
+            .map_or_else(|| short_name(self.type_name), TypeInfo::name)
+    }
+
+    #[must_use]
+    pub fn info(&self) -> Option<&'a TypeInfo> {
+        self.info
+    }
+}
+
+impl EntityManager {
+    /// Exposes the fields of `T` to [`EntityManager::field`] and
+    /// [`EntityManager::set_field`].
+    pub fn register_reflect<T: Reflect>(&mut self) {
+        self.type_registry.register::<T>();
+    }
+
+    #[must_use]
+    pub fn type_registry(&self) -> &TypeRegistry {
+        &self.type_registry
+    }
+
+    /// Components of the entity sorted by name, registered in the type
+    /// registry or not. Empty if the entity does not exist.
+    #[must_use]
+    pub fn components_of(&self, entity: Entity) -> Vec<ComponentType<'_>> {
+        if !self.entity_exists(entity) {
+            return Vec::new();
+        }
+        let mut components: Vec<ComponentType<'_>> = self
+            .components
+            .component_types(entity)
+            .into_iter()
+            .map(|(type_id, type_name)| ComponentType {
+                type_id,
+                type_name,
+                info: self.type_registry.get(type_id),
+            })
+            .collect();
+        components.sort_by_key(|component| (component.name(), component.type_name));
+        components
+    }
+
+    /// Type-erased [`EntityManager::get_component`], to be downcast to the
+    /// type of `type_id`.
+    #[must_use]
+    pub fn get_component_dyn(&self, entity: Entity, type_id: TypeId) -> Option<&dyn Any> {
+        if !self.entity_exists(entity) {
+            return None;
+        }
+        self.components.get_component_dyn(type_id, entity)
+    }
+
+    /// Type-erased [`EntityManager::get_component_mut`].
+    #[must_use]
+    pub fn get_component_dyn_mut(
+        &mut self,
+        entity: Entity,
+        type_id: TypeId,
+    ) -> Option<&mut dyn Any> {
+        if !self.entity_exists(entity) {
+            return None;
+        }
+        self.components.get_component_dyn_mut(type_id, entity)
+    }
+
+    /// Reads `field` of the entity's component registered as `component`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if no type is registered as `component`, the entity
+    /// does not have it, or it has no such field.
+    pub fn field(&self, entity: Entity, component: &str, field: &str) -> Result<Value> {
+        let info = self.reflected_type(component)?;
+        let value = self
+            .get_component_dyn(entity, info.type_id)
+            .ok_or(ReflectError::MissingComponent(entity, info.name))?;
+        info.field(value, field)
+    }
+
+    /// Writes `field` of the entity's component registered as `component`,
+    /// marking the component as changed.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if no type is registered as `component`, the entity
+    /// does not have it, it has no such field, or `value` does not fit it.
+    pub fn set_field(
+        &mut self,
+        entity: Entity,
+        component: &str,
+        field: &str,
+        value: &Value,
+    ) -> Result<()> {
+        let info = self.reflected_type(component)?;
+        let (type_id, name, set) = (info.type_id, info.name, info.reflect);
+        let set = set.ok_or(ReflectError::NotReflected(info.type_name))?.set;
+        if !self.entity_exists(entity) {
+            return Err(ReflectError::MissingComponent(entity, name));
+        }
+        self.components
+            .update_component_dyn(type_id, entity, |target| set(target, field, value))
+            .ok_or(ReflectError::MissingComponent(entity, name))?
+    }
+
+    fn reflected_type(&self, name: &str) -> Result<&TypeInfo> {
+        self.type_registry
+            .get_by_name(name)
+            .ok_or_else(|| ReflectError::UnknownType(name.to_string()))
+    }
+}
+
+/// Error for a field that `T` does not have, for [`Reflect`] implementations.
+#[must_use]
+pub fn unknown_field<T>(name: &str) -> ReflectError {
+    ReflectError::UnknownField {
+        type_name: type_name::<T>(),
+        field: name.to_string(),
+    }
+}
+
+fn downcast_ref<T: 'static>(component: &dyn Any) -> &T {
+    component
+        .downcast_ref()
+        .unwrap_or_else(|| panic!("Expected a component of type {}", type_name::<T>()))
+}
+
+fn downcast_mut<T: 'static>(component: &mut dyn Any) -> &mut T {
+    component
+        .downcast_mut()
+        .unwrap_or_else(|| panic!("Expected a component of type {}", type_name::<T>()))
+}
+
+/// `path::to::Type<Param>` without the path: `Type<Param>`.
+fn short_name(type_name: &'static str) -> &'static str {
+    let end = type_name.find('<').unwrap_or(type_name.len());
+    let start = type_name[..end].rfind("::").map_or(0, |index| index + 2);
+    &type_name[start..]
+}
+
+#[cfg(test)]
+mod tests {
+    use std::any::TypeId;
+
+    use glam::Vec3;
+
+    use super::{FieldInfo, Reflect, ReflectError, Result, short_name, unknown_field};
+    use crate::components::color::{Color, RGBA};
+    use crate::components::material::Material;
+    use crate::components::transform::Transform;
+    use crate::entity::EntityManager;
+    use crate::scene::{EntityMap, Value};
+
+    struct Health {
+        current: u32,
+        max: u32,
+    }
+
+    impl Reflect for Health {
+        fn fields() -> Vec<FieldInfo> {
+            vec![
+                FieldInfo::new::<u32>("current"),
+                FieldInfo::new::<u32>("max"),
+            ]
+        }
+
+        fn field(&self, name: &str) -> Result<Value> {
+            match name {
+                "current" => Ok(Value::number(self.current)),
+                "max" => Ok(Value::number(self.max)),
+                _ => Err(unknown_field::<Self>(name)),
+            }
+        }
+
+        fn set_field(&mut self, name: &str, value: &Value) -> Result<()> {
+            match name {
+                "current" => self.current = value.as_number()?,
+                "max" => self.max = value.as_number()?,
+                _ => return Err(unknown_field::<Self>(name)),
+            }
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_reflect_lists_components_of_entity() {
+        let mut entity_manager = EntityManager::new(10);
+        entity_manager.register_reflect::<Health>();
+        let entity = entity_manager.create_entity((
+            Health { current: 3, max: 5 },
+            Transform::identity(),
+            Color::uniform(RGBA::empty()),
+            7_u8,
+        ));
+
+        let components = entity_manager.components_of(entity);
+        let names: Vec<&str> = components
+            .iter()
+            .map(|component| component.name())
+            .collect();
+        assert_eq!(names, ["Color", "Health", "Transform", "u8"]);
+        assert!(components[1].info().unwrap().is_reflected());
+        assert!(!components[1].info().unwrap().is_serializable());
+        assert_eq!(
+            components[1].info().unwrap().fields()[1],
+            FieldInfo::new::<u32>("max")
+        );
+        assert!(components[3].info().is_none());
+        assert_eq!(components[3].type_id(), TypeId::of::<u8>());
+
+        entity_manager.remove_entity(entity);
+        assert!(entity_manager.components_of(entity).is_empty());
+    }
+
+    #[test]
+    fn test_reflect_reads_and_writes_fields_by_name() {
+        let mut entity_manager = EntityManager::new(10);
+        entity_manager.register_reflect::<Health>();
+        let entity = entity_manager.create_entity((
+            Health { current: 3, max: 5 },
+            Transform::identity(),
+            Material {
+                shader_id: 1,
+                color: Color::uniform(RGBA::new(1, 2, 3, 1.0)),
+            },
+        ));
+
+        entity_manager
+            .set_field(entity, "Health", "current", &Value::number(4))
+            .unwrap();
+        entity_manager
+            .set_field(
+                entity,
+                "Transform",
+                "translation",
+                &"[1, 2, 3]".parse().unwrap(),
+            )
+            .unwrap();
+        entity_manager
+            .set_field(entity, "Material", "shader_id", &Value::number(9))
+            .unwrap();
+
+        assert_eq!(
+            entity_manager.field(entity, "Health", "current").unwrap(),
+            Value::number(4)
+        );
+        assert_eq!(
+            entity_manager
+                .get_component::<Transform>(entity)
+                .unwrap()
+                .matrix()
+                .w_axis
+                .truncate(),
+            Vec3::new(1.0, 2.0, 3.0)
+        );
+        assert_eq!(
+            entity_manager
+                .get_component::<Material>(entity)
+                .unwrap()
+                .shader_id,
+            9
+        );
+        assert_eq!(
+            entity_manager
+                .field(entity, "Material", "color")
+                .unwrap()
+                .to_string(),
+            "Uniform({ r: 1, g: 2, b: 3, alpha: 1 })"
+        );
+        assert!(matches!(
+            entity_manager.field(entity, "Health", "armor"),
+            Err(ReflectError::UnknownField { field, .. }) if field == "armor"
+        ));
+        assert!(matches!(
+            entity_manager.field(entity, "Shape", "vertices"),
+            Err(ReflectError::MissingComponent(..))
+        ));
+        assert!(matches!(
+            entity_manager.set_field(entity, "Health", "max", &Value::Bool(true)),
+            Err(ReflectError::Value(_))
+        ));
+        assert!(matches!(
+            entity_manager.field(entity, "Mana", "current"),
+            Err(ReflectError::UnknownType(name)) if name == "Mana"
+        ));
+    }
+
+    #[test]
+    fn test_reflect_serializes_builtins() {
+        let entity_manager = EntityManager::new(10);
+        let registry = entity_manager.type_registry();
+        let info = registry.get_by_name("RGBA").unwrap();
+        let entities = EntityMap::default();
+
+        let value = info
+            .serialize(&RGBA::new(10, 20, 30, 0.5), &entities)
+            .unwrap()
+            .unwrap();
+        let mut rgba = info.deserialize(&value, &entities).unwrap().unwrap();
+
+        assert_eq!(
+            rgba.downcast_mut::<RGBA>(),
+            Some(&mut RGBA::new(10, 20, 30, 0.5))
+        );
+        for name in [
+            "Transform",
+            "Shape",
+            "Color",
+            "RGBA",
+            "Material",
+            "Parent",
+            "Name",
+        ] {
+            assert!(
+                registry.get_by_name(name).unwrap().is_serializable(),
+                "{name}"
+            );
+        }
+        assert!(!registry.get_by_name("Parent").unwrap().is_reflected());
+    }
+
+    #[test]
+    fn test_short_name() {
+        assert_eq!(short_name("chronos::components::color::RGBA"), "RGBA");
+        assert_eq!(short_name("u8"), "u8");
+        assert_eq!(
+            short_name("alloc::vec::Vec<chronos::entity::Entity>"),
+            "Vec<chronos::entity::Entity>"
+        );
+    }
+}
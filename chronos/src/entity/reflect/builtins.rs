@@ -0,0 +1,222 @@
+use glam::{Vec3, Vec4};
+
+use crate::components::color::{Color, RGBA};
+use crate::components::material::Material;
+use crate::components::shape::Shape;
+use crate::components::transform::Transform;
+use crate::entity::hierarchy::Parent;
+use crate::entity::name::Name;
+use crate::entity::reflect::{FieldInfo, Reflect, Result, TypeRegistry, unknown_field};
+use crate::scene::{EntityMap, SceneComponent, SceneError, Value};
+
+pub(super) fn register(registry: &mut TypeRegistry) {
+    registry.register::<Transform>();
+    registry.register::<Shape>();
+    registry.register::<Color>();
+    registry.register::<RGBA>();
+    registry.register::<Material>();
+    registry.register::<Name>();
+
+    registry.register_serializable::<Transform>();
+    registry.register_serializable::<Shape>();
+    registry.register_serializable::<Color>();
+    registry.register_serializable::<RGBA>();
+    registry.register_serializable::<Material>();
+    registry.register_serializable::<Parent>();
+    registry.register_serializable::<Name>();
+}
+
+impl Reflect for Transform {
+    /// `matrix` is written as its four columns, as in scene files;
+    /// `translation` is the translation part of the matrix.
+    fn fields() -> Vec<FieldInfo> {
+        vec![
+            FieldInfo::new::<glam::Mat4>("matrix"),
+            FieldInfo::new::<Vec3>("translation"),
+        ]
+    }
+
+    fn field(&self, name: &str) -> Result<Value> {
+        match name {
+            "matrix" => Ok(self.to_value(&EntityMap::default())?),
+            "translation" => Ok(Value::List(
+                self.matrix()
+                    .w_axis
+                    .truncate()
+                    .to_array()
+                    .iter()
+                    .map(|&value| Value::number(value))
+                    .collect(),
+            )),
+            _ => Err(unknown_field::<Self>(name)),
+        }
+    }
+
+    fn set_field(&mut self, name: &str, value: &Value) -> Result<()> {
+        match name {
+            "matrix" => *self = Self::from_value(value, &EntityMap::default())?,
+            "translation" => {
+                let [x, y, z] = value.as_array::<3>()?;
+                let translation = Vec3::new(x.as_number()?, y.as_number()?, z.as_number()?);
+                let mut matrix = self.matrix();
+                matrix.w_axis = Vec4::from((translation, matrix.w_axis.w));
+                *self = Self::from_matrix(matrix);
+            }
+            _ => return Err(unknown_field::<Self>(name)),
+        }
+        Ok(())
+    }
+}
+
+impl Reflect for Shape {
+    /// `vertices` is a list of `[x, y, z]` positions.
+    fn fields() -> Vec<FieldInfo> {
+        vec![FieldInfo::new::<Vec<Vec3>>("vertices")]
+    }
+
+    fn field(&self, name: &str) -> Result<Value> {
+        match name {
+            "vertices" => Ok(self.to_value(&EntityMap::default())?),
+            _ => Err(unknown_field::<Self>(name)),
+        }
+    }
+
+    fn set_field(&mut self, name: &str, value: &Value) -> Result<()> {
+        match name {
+            "vertices" => *self = Self::from_value(value, &EntityMap::default())?,
+            _ => return Err(unknown_field::<Self>(name)),
+        }
+        Ok(())
+    }
+}
+
+impl Reflect for Name {
+    fn fields() -> Vec<FieldInfo> {
+        vec![FieldInfo::new::<String>("name")]
+    }
+
+    fn field(&self, name: &str) -> Result<Value> {
+        match name {
+            "name" => Ok(Value::String(self.as_str().to_string())),
+            _ => Err(unknown_field::<Self>(name)),
+        }
+    }
+
+    fn set_field(&mut self, name: &str, value: &Value) -> Result<()> {
+        match name {
+            "name" => *self = Name::new(value.as_str()?),
+            _ => return Err(unknown_field::<Self>(name)),
+        }
+        Ok(())
+    }
+}
+
+impl Reflect for RGBA {
+    fn fields() -> Vec<FieldInfo> {
+        vec![
+            FieldInfo::new::<u8>("r"),
+            FieldInfo::new::<u8>("g"),
+            FieldInfo::new::<u8>("b"),
+            FieldInfo::new::<f32>("alpha"),
+        ]
+    }
+
+    fn field(&self, name: &str) -> Result<Value> {
+        let (r, g, b, alpha) = self.get();
+        match name {
+            "r" => Ok(Value::number(r)),
+            "g" => Ok(Value::number(g)),
+            "b" => Ok(Value::number(b)),
+            "alpha" => Ok(Value::number(alpha)),
+            _ => Err(unknown_field::<Self>(name)),
+        }
+    }
+
+    fn set_field(&mut self, name: &str, value: &Value) -> Result<()> {
+        let (mut r, mut g, mut b, mut alpha) = self.get();
+        match name {
+            "r" => r = value.as_number()?,
+            "g" => g = value.as_number()?,
+            "b" => b = value.as_number()?,
+            "alpha" => alpha = value.as_number()?,
+            _ => return Err(unknown_field::<Self>(name)),
+        }
+        *self = Self::new(r, g, b, alpha);
+        Ok(())
+    }
+}
+
+impl Reflect for Color {
+    /// One field per variant. Reading the field of the other variant is an
+    /// error; writing it switches the variant.
+    fn fields() -> Vec<FieldInfo> {
+        vec![
+            FieldInfo::new::<RGBA>("uniform"),
+            FieldInfo::new::<Vec<f32>>("per_vertex"),
+        ]
+    }
+
+    fn field(&self, name: &str) -> Result<Value> {
+        let entities = EntityMap::default();
+        match (name, self) {
+            ("uniform", Color::Uniform(rgba)) => Ok(rgba.to_value(&entities)?),
+            ("per_vertex", Color::PerVertex(colors)) => Ok(Value::List(
+                colors.iter().map(|&value| Value::number(value)).collect(),
+            )),
+            ("uniform" | "per_vertex", _) => Err(SceneError::InvalidValue(format!(
+                "Color has no '{name}' value, it is {}",
+                if self.is_uniform() {
+                    "uniform"
+                } else {
+                    "per vertex"
+                }
+            ))
+            .into()),
+            _ => Err(unknown_field::<Self>(name)),
+        }
+    }
+
+    fn set_field(&mut self, name: &str, value: &Value) -> Result<()> {
+        match name {
+            "uniform" => *self = Color::Uniform(RGBA::from_value(value, &EntityMap::default())?),
+            "per_vertex" => {
+                *self = Color::PerVertex(
+                    value
+                        .as_list()?
+                        .iter()
+                        .map(Value::as_number)
+                        .collect::<crate::scene::Result<_>>()?,
+                );
+            }
+            _ => return Err(unknown_field::<Self>(name)),
+        }
+        Ok(())
+    }
+}
+
+impl Reflect for Material {
+    /// `color` is written as in scene files, e.g. `Uniform({ r: 1, ... })`.
+    fn fields() -> Vec<FieldInfo> {
+        vec![
+            FieldInfo::new::<u32>("shader_id"),
+            FieldInfo::new::<Color>("color"),
+        ]
+    }
+
+    fn field(&self, name: &str) -> Result<Value> {
+        match name {
+            "shader_id" => Ok(Value::number(self.shader_id)),
+            "color" => Ok(self.color.to_value(&EntityMap::default())?),
+            _ => Err(unknown_field::<Self>(name)),
+        }
+    }
+
+    fn set_field(&mut self, name: &str, value: &Value) -> Result<()> {
+        match name {
+            "shader_id" => self.shader_id = value.as_number()?,
+            "color" => self.color = Color::from_value(value, &EntityMap::default())?,
+            _ => return Err(unknown_field::<Self>(name)),
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,171 @@
+use std::{
+    any::{Any, TypeId, type_name},
+    cell::UnsafeCell,
+    collections::HashMap,
+    ops::{Deref, DerefMut},
+};
+
+/// Typed map of singletons that do not belong to any entity, e.g. delta time
+/// or input state. Holds at most one value per type.
+#[derive(Default)]
+pub struct Resources {
+    resources: HashMap<TypeId, UnsafeCell<Box<dyn Any + Send + Sync>>>,
+}
+
+// SAFETY: every resource is `Send + Sync`, and the cells are only accessed
+// mutably through `&self` by systems whose access has been checked not to
+// conflict.
+unsafe impl Sync for Resources {}
+
+/// System parameter giving shared access to the resource `T`.
+///
+/// # Panics
+///
+/// The system panics when it runs if the resource does not exist. Use
+/// `Option<Res<T>>` for resources that may be missing.
+pub struct Res<'w, T: 'static> {
+    value: &'w T,
+}
+
+/// System parameter giving mutable access to the resource `T`.
+///
+/// # Panics
+///
+/// The system panics when it runs if the resource does not exist. Use
+/// `Option<ResMut<T>>` for resources that may be missing.
+pub struct ResMut<'w, T: 'static> {
+    value: &'w mut T,
+}
+
+impl Resources {
+    /// Inserts `value`, returning the previous resource of the same type.
+    pub fn insert<T: Send + Sync + 'static>(&mut self, value: T) -> Option<T> {
+        self.resources
+            .insert(TypeId::of::<T>(), UnsafeCell::new(Box::new(value)))
+            .and_then(|previous| previous.into_inner().downcast::<T>().ok())
+            .map(|previous| *previous)
+    }
+
+    pub fn remove<T: Send + Sync + 'static>(&mut self) -> Option<T> {
+        let resource = self.resources.remove(&TypeId::of::<T>())?;
+        resource
+            .into_inner()
+            .downcast::<T>()
+            .ok()
+            .map(|resource| *resource)
+    }
+
+    #[must_use]
+    pub fn contains<T: Send + Sync + 'static>(&self) -> bool {
+        self.resources.contains_key(&TypeId::of::<T>())
+    }
+
+    #[must_use]
+    pub fn get<T: Send + Sync + 'static>(&self) -> Option<&T> {
+        // SAFETY: `&self` excludes `get_mut`, and systems only write through
+        // `get_ptr` to resources nobody else accesses.
+        self.get_ptr::<T>().map(|value| unsafe { &*value })
+    }
+
+    pub fn get_mut<T: Send + Sync + 'static>(&mut self) -> Option<&mut T> {
+        // SAFETY: `&mut self` guarantees exclusive access.
+        self.get_ptr::<T>().map(|value| unsafe { &mut *value })
+    }
+
+    /// Pointer to the resource `T`. Writing through it requires that nothing
+    /// else accesses the resource.
+    pub(crate) fn get_ptr<T: Send + Sync + 'static>(&self) -> Option<*mut T> {
+        let cell = self.resources.get(&TypeId::of::<T>())?;
+        // SAFETY: the box itself is never replaced through `&self`, and
+        // resources are stored under their own `TypeId`.
+        Some(unsafe { (&raw mut **cell.get()).cast::<T>() })
+    }
+}
+
+impl<'w, T: 'static> Res<'w, T> {
+    pub(crate) fn new(value: &'w T) -> Self {
+        Self { value }
+    }
+
+    #[must_use]
+    pub fn into_inner(self) -> &'w T {
+        self.value
+    }
+}
+
+impl<'w, T: 'static> ResMut<'w, T> {
+    pub(crate) fn new(value: &'w mut T) -> Self {
+        Self { value }
+    }
+
+    #[must_use]
+    pub fn into_inner(self) -> &'w mut T {
+        self.value
+    }
+}
+
+impl<T: 'static> Deref for Res<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        self.value
+    }
+}
+
+impl<T: 'static> Deref for ResMut<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        self.value
+    }
+}
+
+impl<T: 'static> DerefMut for ResMut<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        self.value
+    }
+}
+
+pub(crate) fn missing_resource<T>() -> ! {
+    panic!("Resource {} does not exist", type_name::<T>())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Resources;
+
+    #[derive(Debug, PartialEq)]
+    struct DeltaTime(f32);
+
+    #[test]
+    fn test_resources_insert_get() {
+        let mut resources = Resources::default();
+
+        assert!(resources.insert(DeltaTime(0.5)).is_none());
+
+        assert!(resources.contains::<DeltaTime>());
+        assert_eq!(resources.get::<DeltaTime>(), Some(&DeltaTime(0.5)));
+        assert!(resources.get::<u32>().is_none());
+    }
+
+    #[test]
+    fn test_resources_insert_replaces_previous() {
+        let mut resources = Resources::default();
+        resources.insert(DeltaTime(0.5));
+
+        assert_eq!(resources.insert(DeltaTime(1.0)), Some(DeltaTime(0.5)));
+        assert_eq!(resources.get::<DeltaTime>(), Some(&DeltaTime(1.0)));
+    }
+
+    #[test]
+    fn test_resources_get_mut_remove() {
+        let mut resources = Resources::default();
+        resources.insert(DeltaTime(0.5));
+
+        resources.get_mut::<DeltaTime>().unwrap().0 = 2.0;
+
+        assert_eq!(resources.remove::<DeltaTime>(), Some(DeltaTime(2.0)));
+        assert!(!resources.contains::<DeltaTime>());
+        assert!(resources.remove::<DeltaTime>().is_none());
+    }
+}
@@ -0,0 +1,321 @@
+//! Snapshots of an [`EntityManager`] that can be restored later, e.g. to roll
+//! back a few frames for netcode or to undo the last frame while debugging.
+//!
+//! Every stored component type must be registered with
+//! [`EntityManager::register_cloneable`]; the built-in components are
+//! registered already. Storages that did not change since the previous
+//! snapshot are shared with it rather than copied, so keeping a
+//! [`SnapshotBuffer`] of recent frames is cheap when most components are static.
+//!
+//! ```ignore
+//! let mut history = SnapshotBuffer::new(8);
+//! history.push(entity_manager.snapshot()?);
+//! // ...two frames later, roll back to the snapshot before them:
+//! if let Some(snapshot) = history.rewind(2) {
+//!     entity_manager.restore(snapshot);
+//! }
+//! ```
+
+use std::collections::VecDeque;
+
+use crate::components::{
+    color::Color,
+    material::Material,
+    shape::Shape,
+    transform::{GlobalTransform, Transform},
+};
+use crate::entity::EntityManager;
+use crate::entity::component_storage::{ComponentStorage, StorageSnapshot};
+use crate::entity::hierarchy::{Children, Parent};
+use crate::entity::name::Name;
+
+pub type Result<T> = std::result::Result<T, SnapshotError>;
+
+#[derive(thiserror::Error, Debug)]
+pub enum SnapshotError {
+    #[error("Component {0} is not registered as cloneable")]
+    NotCloneable(&'static str),
+}
+
+/// Entities and components of an [`EntityManager`] at one point in time.
+/// Resources and events are not part of it.
+#[derive(Clone)]
+pub struct WorldSnapshot {
+    next_id: usize,
+    free_ids: Vec<usize>,
+    generations: Vec<u32>,
+    components: StorageSnapshot,
+}
+
+/// Ring buffer keeping the last `capacity` snapshots, the oldest being
+/// dropped when a new one is pushed into a full buffer.
+pub struct SnapshotBuffer {
+    snapshots: VecDeque<WorldSnapshot>,
+    capacity: usize,
+}
+
+impl EntityManager {
+    /// Lets the components of `T` be part of snapshots.
+    pub fn register_cloneable<T: Clone + Send + Sync + 'static>(&mut self) {
+        self.components.register_clone::<T>();
+    }
+
+    /// Captures all entities and components. Reserved entities are flushed first.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`SnapshotError::NotCloneable`] if a stored component type was
+    /// not registered with [`EntityManager::register_cloneable`].
+    pub fn snapshot(&mut self) -> Result<WorldSnapshot> {
+        self.flush_reserved();
+        let components = self
+            .components
+            .snapshot()
+            .map_err(SnapshotError::NotCloneable)?;
+        Ok(WorldSnapshot {
+            next_id: self.next_id,
+            free_ids: self.free_ids.clone(),
+            generations: self.generations.clone(),
+            components,
+        })
+    }
+
+    /// Brings entities and components back to the state of `snapshot`, which
+    /// can be restored again later. Handles of entities created since then no
+    /// longer resolve: indices used since the snapshot are reused with a newer
+    /// generation, so old handles never alias restored or later entities. No
+    /// hooks run, and restored components do not count as added or changed.
+    pub fn restore(&mut self, snapshot: &WorldSnapshot) {
+        self.flush_reserved();
+        // Lowest generation each index can be handed out with again without
+        // reviving a handle that exists now.
+        let unused_generations: Vec<u32> = (0..self.next_id)
+            .map(|index| {
+                let generation = self.generations[index].wrapping_add(u32::from(self.alive[index]));
+                self.generation_floors
+                    .get(&index)
+                    .map_or(generation, |&floor| floor.max(generation))
+            })
+            .collect();
+
+        // Indices created since the snapshot stay allocated, but free.
+        let next_id = self.next_id.max(snapshot.next_id);
+        self.free_ids = (snapshot.next_id..next_id).rev().collect();
+        self.free_ids.extend_from_slice(&snapshot.free_ids);
+        self.generations.clone_from(&snapshot.generations);
+        self.generations.resize(next_id, 0);
+        self.alive = vec![true; next_id];
+        for &free_id in &self.free_ids {
+            self.alive[free_id] = false;
+        }
+        self.next_id = next_id;
+
+        self.generation_floors.clear();
+        for (index, unused) in unused_generations.into_iter().enumerate() {
+            if !self.alive[index] {
+                self.generations[index] = self.generations[index].max(unused);
+            } else if unused > self.generations[index].wrapping_add(1) {
+                self.generation_floors.insert(index, unused);
+            }
+        }
+        self.sync_free_cursor();
+        self.components.restore(&snapshot.components);
+    }
+}
+
+impl WorldSnapshot {
+    /// Number of entities alive when the snapshot was taken.
+    #[must_use]
+    pub fn entity_count(&self) -> usize {
+        self.next_id - self.free_ids.len()
+    }
+}
+
+impl SnapshotBuffer {
+    /// # Panics
+    ///
+    /// Panics if `capacity` is zero.
+    #[must_use]
+    pub fn new(capacity: usize) -> Self {
+        assert!(capacity > 0, "SnapshotBuffer needs room for one snapshot");
+        Self {
+            snapshots: VecDeque::with_capacity(capacity),
+            capacity,
+        }
+    }
+
+    pub fn push(&mut self, snapshot: WorldSnapshot) {
+        if self.snapshots.len() == self.capacity {
+            self.snapshots.pop_front();
+        }
+        self.snapshots.push_back(snapshot);
+    }
+
+    /// The snapshot pushed `age` pushes ago, `0` being the latest.
+    #[must_use]
+    pub fn get(&self, age: usize) -> Option<&WorldSnapshot> {
+        let index = self.snapshots.len().checked_sub(age + 1)?;
+        self.snapshots.get(index)
+    }
+
+    #[must_use]
+    pub fn latest(&self) -> Option<&WorldSnapshot> {
+        self.snapshots.back()
+    }
+
+    /// Drops the `age` latest snapshots, which describe a future that is
+    /// being rolled back, and returns the one that is now the latest.
+    pub fn rewind(&mut self, age: usize) -> Option<&WorldSnapshot> {
+        let len = self.snapshots.len().checked_sub(age)?;
+        self.snapshots.truncate(len);
+        self.snapshots.back()
+    }
+
+    pub fn clear(&mut self) {
+        self.snapshots.clear();
+    }
+
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.snapshots.len()
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.snapshots.is_empty()
+    }
+
+    #[must_use]
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+}
+
+/// Registers the built-in components as cloneable.
+pub(super) fn register_builtins(storage: &mut ComponentStorage) {
+    storage.register_clone::<Transform>();
+    storage.register_clone::<GlobalTransform>();
+    storage.register_clone::<Shape>();
+    storage.register_clone::<Color>();
+    storage.register_clone::<Material>();
+    storage.register_snapshot_clone::<Parent>();
+    storage.register_snapshot_clone::<Children>();
+    storage.register_clone::<Name>();
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::Vec3;
+
+    use super::{SnapshotBuffer, SnapshotError};
+    use crate::components::transform::Transform;
+    use crate::entity::{Entity, EntityManager};
+
+    struct NotCloneable;
+
+    fn translation(entity_manager: &EntityManager, entity: Entity) -> Vec3 {
+        entity_manager
+            .get_component::<Transform>(entity)
+            .unwrap()
+            .matrix()
+            .w_axis
+            .truncate()
+    }
+
+    #[test]
+    fn test_snapshot_restore_round_trip() {
+        let mut entity_manager = EntityManager::new(10);
+        entity_manager.register_component::<Transform>();
+        entity_manager.register_cloneable::<u32>();
+        let first = entity_manager.create_entity((Transform::identity(), 1_u32));
+        let second = entity_manager.create_entity((Transform::identity(),));
+        let snapshot = entity_manager.snapshot().unwrap();
+
+        entity_manager.remove_entity(first);
+        let third = entity_manager.create_entity((Transform::identity(), 3_u32));
+        *entity_manager.get_component_mut::<u32>(third).unwrap() = 30;
+        for transform in entity_manager.query::<&mut Transform>() {
+            *transform = Transform::from_translation(Vec3::X);
+        }
+
+        for _ in 0..2 {
+            entity_manager.restore(&snapshot);
+            assert!(entity_manager.entity_exists(first));
+            assert!(!entity_manager.entity_exists(third));
+            assert_eq!(entity_manager.entities().count(), 2);
+            assert_eq!(entity_manager.get_component::<u32>(first), Some(&1));
+            assert_eq!(translation(&entity_manager, second), Vec3::ZERO);
+            assert_eq!(entity_manager.query::<&u32>().count(), 1);
+
+            entity_manager.add_component(second, 2_u32);
+        }
+
+        let fourth = entity_manager.create_entity((4_u32,));
+        assert_eq!(third.index(), first.index());
+        assert_eq!(fourth.index(), 2);
+        assert_eq!(entity_manager.get_component::<u32>(fourth), Some(&4));
+        assert!(entity_manager.get_component::<Transform>(fourth).is_none());
+    }
+
+    #[test]
+    fn test_restore_does_not_revive_stale_handles() {
+        let mut entity_manager = EntityManager::new(10);
+        entity_manager.register_cloneable::<u32>();
+        let restored = entity_manager.create_entity((1_u32,));
+        let snapshot = entity_manager.snapshot().unwrap();
+
+        entity_manager.remove_entity(restored);
+        let reused = entity_manager.create_entity((2_u32,));
+        let created = entity_manager.create_entity((3_u32,));
+        entity_manager.restore(&snapshot);
+        assert!(entity_manager.entity_exists(restored));
+        assert!(!entity_manager.entity_exists(reused));
+        assert!(!entity_manager.entity_exists(created));
+        assert_eq!(entity_manager.get_entity_count(), 1);
+
+        entity_manager.remove_entity(restored);
+        let first = entity_manager.create_entity((4_u32,));
+        let second = entity_manager.create_entity((5_u32,));
+        assert_eq!(first.index(), reused.index());
+        assert_eq!(second.index(), created.index());
+        assert!(first.generation() > reused.generation());
+        assert!(second.generation() > created.generation());
+        assert!(!entity_manager.entity_exists(reused));
+        assert!(!entity_manager.entity_exists(created));
+        assert_eq!(entity_manager.get_component::<u32>(first), Some(&4));
+    }
+
+    #[test]
+    fn test_snapshot_requires_cloneable_components() {
+        let mut entity_manager = EntityManager::new(10);
+        let entity = entity_manager.create_entity((Transform::identity(), NotCloneable));
+
+        assert!(matches!(
+            entity_manager.snapshot(),
+            Err(SnapshotError::NotCloneable(name)) if name.ends_with("NotCloneable")
+        ));
+
+        entity_manager.remove_component::<NotCloneable>(entity);
+        assert!(entity_manager.snapshot().is_ok());
+    }
+
+    #[test]
+    fn test_snapshot_buffer_drops_oldest() {
+        let mut entity_manager = EntityManager::new(10);
+        let mut buffer = SnapshotBuffer::new(2);
+        for _ in 0..3 {
+            entity_manager.create_entity((Transform::identity(),));
+            buffer.push(entity_manager.snapshot().unwrap());
+        }
+
+        assert_eq!(buffer.len(), 2);
+        assert_eq!(buffer.latest().unwrap().entity_count(), 3);
+        assert_eq!(buffer.get(1).unwrap().entity_count(), 2);
+        assert!(buffer.get(2).is_none());
+
+        entity_manager.restore(buffer.rewind(1).unwrap());
+        assert_eq!(entity_manager.entities().count(), 2);
+        assert_eq!(buffer.len(), 1);
+        assert!(buffer.rewind(2).is_none());
+    }
+}
@@ -0,0 +1,151 @@
+//! Introspection of the entity manager: memory statistics per component type,
+//! text dumps of entities and compaction of the storages after mass
+//! despawns.
+//!
+//! ```ignore
+//! for stats in entity_manager.component_stats() {
+//!     println!("{}: {} components, {} bytes", stats.type_name, stats.dense_len, stats.bytes_used);
+//! }
+//! println!("{}", entity_manager.dump_entity(player).unwrap());
+//! entity_manager.compact();
+//! ```
+
+use std::fmt::Write;
+
+pub use crate::entity::component_storage::ComponentStats;
+use crate::entity::{Entity, EntityManager};
+use crate::scene::{EntityMap, Value};
+
+impl EntityManager {
+    /// Memory statistics of every component type that has a storage,
+    /// dynamic components included, sorted by type name.
+    #[must_use]
+    pub fn component_stats(&self) -> Vec<ComponentStats> {
+        self.components.stats()
+    }
+
+    /// Releases the memory the stored components do not need anymore, such
+    /// as sparse pages emptied by mass despawns, which storages otherwise
+    /// keep. Entity handles, components and snapshots are unaffected.
+    pub fn compact(&mut self) {
+        self.components.shrink_to_fit();
+        self.free_ids.shrink_to_fit();
+    }
+
+    /// The entity and its components as text, one component per line sorted
+    /// by name, for debugging. Components are written as in scene files, with
+    /// entity references as `@index`; registered reflected types show their
+    /// fields, and other types only their name. `None` if the entity does
+    /// not exist.
+    #[must_use]
+    pub fn dump_entity(&self, entity: Entity) -> Option<String> {
+        if !self.entity_exists(entity) {
+            return None;
+        }
+        let mut entities = EntityMap::default();
+        for entity in self.entities() {
+            entities.insert(entity.index() as u64, entity);
+        }
+
+        let mut dump = format!("Entity {}v{}", entity.index(), entity.generation());
+        if let Some(name) = self.name(entity) {
+            let _ = write!(dump, " {}", Value::String(name.to_string()));
+        }
+        for component in self.components_of(entity) {
+            let _ = write!(dump, "\n  {}", component.name());
+            let (Some(info), Some(value)) = (
+                component.info(),
+                self.get_component_dyn(entity, component.type_id()),
+            ) else {
+                continue;
+            };
+            let text = match info.serialize(value, &entities) {
+                Some(Ok(value)) => value.to_string(),
+                Some(Err(error)) => format!("<{error}>"),
+                None => Value::map(info.fields().iter().filter_map(|field| {
+                    Some((field.name(), info.field(value, field.name()).ok()?))
+                }))
+                .to_string(),
+            };
+            let _ = write!(dump, ": {text}");
+        }
+        for id in self.dynamic_components_of(entity) {
+            let Some(component) = self.get_dynamic_component(entity, id) else {
+                continue;
+            };
+            let layout = component.layout();
+            let fields = layout
+                .fields()
+                .iter()
+                .filter_map(|field| Some((field.name(), component.field(field.name())?)));
+            let _ = write!(dump, "\n  {}: {}", layout.name(), Value::map(fields));
+        }
+        Some(dump)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::Vec3;
+
+    use crate::components::transform::Transform;
+    use crate::entity::EntityManager;
+    use crate::entity::dynamic::{DynamicLayout, FieldKind};
+    use crate::entity::hierarchy::ReparentMode;
+    use crate::entity::name::Name;
+
+    #[test]
+    fn test_dump_entity() {
+        let mut entity_manager = EntityManager::new(10);
+        let parent = entity_manager.create_entity(());
+        let player = entity_manager.create_entity((
+            Name::new("player"),
+            Transform::from_translation(Vec3::X),
+            3_u8,
+        ));
+        entity_manager.set_parent(player, parent, ReparentMode::KeepLocal);
+        let health = entity_manager.register_dynamic_component(
+            DynamicLayout::new("Health").with_field("current", FieldKind::U32),
+        );
+        entity_manager.add_dynamic_component(player, health, &9_u32.to_ne_bytes());
+
+        let dump = entity_manager.dump_entity(player).unwrap();
+        let lines: Vec<&str> = dump.lines().collect();
+        assert_eq!(lines[0], "Entity 1v0 \"player\"");
+        assert!(lines.contains(&"  Name: \"player\""));
+        assert!(lines.contains(&"  Parent: @0"));
+        assert!(lines.contains(&"  u8"));
+        assert!(
+            lines
+                .iter()
+                .any(|line| line.starts_with("  Transform: [[1, 0, 0, 0]"))
+        );
+        assert_eq!(lines.last(), Some(&"  Health: { current: 9 }"));
+
+        entity_manager.remove_entity(player);
+        assert!(entity_manager.dump_entity(player).is_none());
+    }
+
+    #[test]
+    fn test_compact_after_mass_despawn() {
+        let mut entity_manager = EntityManager::new(10);
+        let entities = entity_manager.spawn_batch((0..2000_u32).map(|index| (index,)));
+        entity_manager.remove_batch(&entities[10..]);
+        let stats = |entity_manager: &EntityManager| {
+            entity_manager
+                .component_stats()
+                .into_iter()
+                .find(|stats| stats.type_name == "u32")
+                .unwrap()
+        };
+        let before = stats(&entity_manager);
+
+        entity_manager.compact();
+        let after = stats(&entity_manager);
+        assert_eq!(after.dense_len, 10);
+        assert!(after.sparse_capacity < before.sparse_capacity);
+        assert!(after.bytes_used < before.bytes_used);
+        assert!(after.wasted_sparse_ratio < before.wasted_sparse_ratio);
+        assert_eq!(entity_manager.get_component::<u32>(entities[9]), Some(&9));
+    }
+}
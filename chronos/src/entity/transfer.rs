@@ -0,0 +1,178 @@
+use std::collections::HashMap;
+
+use crate::entity::hierarchy::{Children, Parent};
+use crate::entity::{Entity, EntityManager};
+
+impl EntityManager {
+    /// Moves the entity, its descendants and all their components into
+    /// `target`, where they get new handles. Returns the new handles keyed by
+    /// the old ones, or `None` if the entity does not exist or one of the moved
+    /// dynamic components is registered in `target` with another layout. In
+    /// that case nothing is moved.
+    ///
+    /// The entity is detached from its parent first. `Parent` and `Children`
+    /// are rewritten to the new handles; other components holding entity
+    /// handles can be fixed up with the returned map. The `on_remove` hooks
+    /// run in this manager and the `on_add` and `on_insert` hooks in `target`,
+    /// as if the components were removed here and inserted there. Dynamic
+    /// components are registered in `target` under the same name if needed.
+    pub fn move_entity_to(
+        &mut self,
+        entity: Entity,
+        target: &mut EntityManager,
+    ) -> Option<HashMap<Entity, Entity>> {
+        let tree: Vec<Entity> = self
+            .descendants(entity)
+            .into_iter()
+            .chain([entity])
+            .collect();
+        if self
+            .components
+            .dynamic()
+            .conflicts_with(target.components.dynamic(), &tree)
+        {
+            return None;
+        }
+
+        let mut taken = Vec::new();
+        let mut queue = self.remove_trees(&[entity], |components, removed| {
+            for &removed in removed {
+                let dynamic = components.dynamic_mut().take_entity(removed);
+                taken.push((removed, components.take_all_components(removed), dynamic));
+            }
+        })?;
+        // The root comes last out of `remove_trees`; give it the first new handle.
+        taken.rotate_right(1);
+
+        let map: HashMap<Entity, Entity> = taken
+            .iter()
+            .map(|&(old, ..)| (old, target.create_entity_id()))
+            .collect();
+        let tick = target.components.change_tick();
+        for (old, components, dynamic) in taken {
+            for (layout, bytes) in dynamic {
+                let target_dynamic = target.components.dynamic_mut();
+                let id = target_dynamic.register((*layout).clone());
+                target_dynamic.insert(id, map[&old], &bytes, tick);
+            }
+            for mut component in components {
+                if let Some(parent) = component.downcast_mut::<Parent>() {
+                    parent.remap(&map);
+                } else if let Some(children) = component.downcast_mut::<Children>() {
+                    children.remap(&map);
+                }
+                component.insert_into(&mut target.components, map[&old]);
+            }
+        }
+
+        target.run_triggered_hooks();
+        queue.apply(self);
+        Some(map)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+
+    use glam::Vec3;
+
+    use crate::components::{shape::Shape, transform::Transform};
+    use crate::entity::dynamic::{DynamicLayout, FieldKind};
+    use crate::entity::hierarchy::ReparentMode;
+    use crate::entity::{ComponentHooks, EntityManager};
+
+    fn triangle() -> Shape {
+        Shape::new_triangle(Vec3::ZERO, Vec3::X, Vec3::Y)
+    }
+
+    #[test]
+    fn test_move_entity_to_remaps_hierarchy() {
+        let mut source = EntityManager::new(10);
+        let mut target = EntityManager::new(10);
+        target.register_component::<Transform>();
+        target.create_entity((Transform::identity(),));
+
+        let parent = source.create_entity((Transform::identity(),));
+        let root = source.create_entity((Transform::identity(), triangle()));
+        let child = source.create_entity((triangle(), 5_u32));
+        source.set_parent(root, parent, ReparentMode::KeepLocal);
+        source.set_parent(child, root, ReparentMode::KeepLocal);
+
+        let map = source.move_entity_to(root, &mut target).unwrap();
+
+        assert_eq!(map.len(), 2);
+        assert!(!source.entity_exists(root) && !source.entity_exists(child));
+        assert!(source.children(parent).is_empty());
+        assert_eq!(source.query::<&Shape>().count(), 0);
+
+        let (new_root, new_child) = (map[&root], map[&child]);
+        assert_eq!(new_root.index(), 1);
+        assert_eq!(target.parent(new_root), None);
+        assert_eq!(target.children(new_root), &[new_child]);
+        assert_eq!(target.parent(new_child), Some(new_root));
+        assert_eq!(target.get_component::<u32>(new_child), Some(&5));
+        assert_eq!(target.get_component::<Shape>(new_root), Some(&triangle()));
+        assert_eq!(target.query::<&Transform>().count(), 2);
+        assert!(source.move_entity_to(root, &mut target).is_none());
+    }
+
+    #[test]
+    fn test_move_entity_to_runs_hooks_in_both_worlds() {
+        let mut source = EntityManager::new(10);
+        let mut target = EntityManager::new(10);
+        source.register_component_hooks::<u32>(ComponentHooks::new().on_remove(
+            |_, _, commands| {
+                commands.create_entity(('r',));
+            },
+        ));
+        target.register_component_hooks::<u32>(
+            ComponentHooks::new().on_add(|_, entity, commands| commands.add_component(entity, 'a')),
+        );
+        let entity = source.create_entity((1_u32,));
+
+        let moved = source.move_entity_to(entity, &mut target).unwrap()[&entity];
+
+        assert_eq!(source.query::<&char>().collect::<Vec<_>>(), vec![&'r']);
+        assert_eq!(target.get_component::<char>(moved), Some(&'a'));
+    }
+
+    #[test]
+    fn test_move_entity_from_background_world() {
+        let loader = thread::spawn(|| {
+            let mut loading = EntityManager::new(10);
+            let entity = loading.create_entity((Transform::identity(), triangle()));
+            (loading, entity)
+        });
+        let (mut loading, entity) = loader.join().unwrap();
+        let mut game = EntityManager::new(10);
+
+        let moved = loading.move_entity_to(entity, &mut game).unwrap()[&entity];
+
+        assert_eq!(loading.entities().count(), 0);
+        assert_eq!(game.get_component::<Shape>(moved), Some(&triangle()));
+    }
+
+    #[test]
+    fn test_move_entity_to_refuses_conflicting_dynamic_layouts() {
+        let mut source = EntityManager::new(10);
+        let mut target = EntityManager::new(10);
+        let health = source.register_dynamic_component(
+            DynamicLayout::new("Health").with_field("current", FieldKind::F32),
+        );
+        target.register_dynamic_component(
+            DynamicLayout::new("Health").with_field("current", FieldKind::U32),
+        );
+        let root = source.create_entity((1_u32,));
+        let child = source.create_entity(());
+        source.set_parent(child, root, ReparentMode::KeepLocal);
+        source.add_dynamic_component(child, health, &1.5_f32.to_ne_bytes());
+
+        assert!(source.move_entity_to(root, &mut target).is_none());
+
+        assert!(source.entity_exists(root) && source.entity_exists(child));
+        assert_eq!(source.children(root), &[child]);
+        assert!(source.get_dynamic_component(child, health).is_some());
+        assert_eq!(target.entities().count(), 0);
+    }
+}
@@ -1,6 +1,14 @@
+use winit::application::ApplicationHandler;
+use winit::event::WindowEvent;
+use winit::event_loop::{ActiveEventLoop, ControlFlow, EventLoop};
+use winit::window::WindowId;
+
+use crate::components::{color::Color, shape::Shape, transform::Transform};
+use crate::entity::EntityManager;
 use crate::renderer::shader_source::{ShaderManager, ShaderSource};
 use crate::renderer::{Renderer, RendererError, init_render};
-use crate::window::{ChronosWindow, WinError, WindowConfig};
+use crate::schedule::{Schedule, ScheduleError, Stage, SystemConfig};
+use crate::window::{ChronosWindow, WinError, WindowConfig, WindowMode};
 
 pub type Result<T> = std::result::Result<T, EngineError>;
 
@@ -10,48 +18,262 @@ pub enum EngineError {
     WindowError(#[from] WinError),
     #[error("Renderer error: {0}")]
     RendererError(#[from] RendererError),
+    #[error("Schedule error: {0}")]
+    ScheduleError(#[from] ScheduleError),
 }
 
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub enum RendererType {
     OpenGL,
     Vulkan,
 }
 
 pub struct ChronosEngine {
-    #[allow(dead_code)]
     window: ChronosWindow,
-    renderer: Box<dyn Renderer>,
+    renderer_type: RendererType,
+    /// Created once the event loop has created the window.
+    renderer: Option<Box<dyn Renderer>>,
     shader_manager: ShaderManager,
+    entity_manager: EntityManager,
+    schedule: Schedule,
+    /// First error raised inside the event loop, returned by [`ChronosEngine::run`].
+    error: Option<EngineError>,
 }
 
 impl ChronosEngine {
-    /// Starts the Chronos engine with the given window configuration and renderer type.
+    /// Creates the engine with its world and schedule, without opening the
+    /// window yet, so systems can be added before [`ChronosEngine::run`].
+    #[must_use]
+    pub fn new(window_config: WindowConfig, renderer_type: RendererType) -> Self {
+        let mut entity_manager = EntityManager::default();
+        entity_manager.register_component::<Transform>();
+        entity_manager.register_component::<Shape>();
+        entity_manager.register_component::<Color>();
+        ChronosWindow::add_events(&mut entity_manager);
+        let mut schedule = Schedule::new();
+        schedule.add_system(
+            Stage::PostUpdate,
+            SystemConfig::new("propagate_transforms", EntityManager::propagate_transforms),
+        );
+        ChronosEngine {
+            window: ChronosWindow::new(window_config),
+            renderer_type,
+            renderer: None,
+            shader_manager: ShaderManager::default(),
+            entity_manager,
+            schedule,
+            error: None,
+        }
+    }
+
+    /// Starts the Chronos engine with the given window configuration and renderer type,
+    /// running frames until the window is closed, or returning once the window
+    /// is open in [`WindowMode::Test`].
     ///
     /// # Errors
     ///
     /// Returns an error if window creation or renderer initialization fails.
     pub fn start(window_config: WindowConfig, renderer_type: &RendererType) -> Result<Self> {
-        let mut window = ChronosWindow::new(window_config);
-        window.run()?;
-        let renderer = init_render(&window, renderer_type)?;
-        let engine = ChronosEngine {
-            window,
-            renderer,
-            shader_manager: ShaderManager::default(),
-        };
+        let mut engine = ChronosEngine::new(window_config, renderer_type.clone());
+        engine.run()?;
         Ok(engine)
     }
 
+    /// Opens the window and runs the event loop, calling
+    /// [`ChronosEngine::update`] once per frame until the window is closed.
+    /// In [`WindowMode::Test`], opens a hidden window on an event loop that
+    /// may live on any thread and returns once the renderer is created, so
+    /// tests can drive frames with [`ChronosEngine::update`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the event loop, the renderer or a frame fails.
+    pub fn run(&mut self) -> Result<()> {
+        if *self.window.window_mode() == WindowMode::Test {
+            self.window.run()?;
+            return self.init_renderer();
+        }
+        let event_loop = EventLoop::new().map_err(WinError::from)?;
+        event_loop.set_control_flow(ControlFlow::Poll);
+        event_loop.run_app(self).map_err(WinError::from)?;
+        self.error.take().map_or(Ok(()), Err)
+    }
+
     /// Loads a shader into the engine.
     ///
     /// # Errors
     ///
-    /// Returns an error if shader compilation fails or if the renderer encounters an error.
+    /// Returns an error if shader compilation fails, if the renderer encounters an error
+    /// or if the engine is not running yet.
     pub fn load_shader(&mut self, name: &str, shader_source: &ShaderSource) -> Result<()> {
+        let renderer = self.renderer.as_mut().ok_or_else(|| {
+            RendererError::Initialization("the engine is not running".to_string())
+        })?;
         self.shader_manager
             .register_from_source(name, shader_source);
-        self.renderer.compile_shader(shader_source)?;
+        renderer.compile_shader(shader_source)?;
         // TODO: Store the compiled shader ID associated with the name.
         Ok(())
     }
+
+    pub fn add_system(&mut self, stage: Stage, system: SystemConfig) {
+        self.schedule.add_system(stage, system);
+    }
+
+    #[must_use]
+    pub fn entity_manager(&self) -> &EntityManager {
+        &self.entity_manager
+    }
+
+    pub fn entity_manager_mut(&mut self) -> &mut EntityManager {
+        &mut self.entity_manager
+    }
+
+    /// Runs one frame: forwards pending window events, runs the schedule,
+    /// then rotates the event queues and advances the world tick.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the system ordering cannot be resolved.
+    pub fn update(&mut self) -> Result<()> {
+        self.window.send_events(&mut self.entity_manager);
+        self.schedule.run(&mut self.entity_manager)?;
+        self.entity_manager.update_events();
+        self.entity_manager.advance_tick();
+        Ok(())
+    }
+
+    /// Creates the renderer once the window exists.
+    fn init_renderer(&mut self) -> Result<()> {
+        if self.renderer.is_none() && self.window.get_window().is_some() {
+            self.renderer = Some(init_render(&self.window, &self.renderer_type)?);
+        }
+        Ok(())
+    }
+}
+
+impl ApplicationHandler for ChronosEngine {
+    fn resumed(&mut self, event_loop: &ActiveEventLoop) {
+        self.window.resumed(event_loop);
+        if let Err(error) = self.init_renderer() {
+            self.error = Some(error);
+            event_loop.exit();
+        }
+    }
+
+    fn window_event(&mut self, event_loop: &ActiveEventLoop, id: WindowId, event: WindowEvent) {
+        self.window.window_event(event_loop, id, event);
+    }
+
+    fn about_to_wait(&mut self, event_loop: &ActiveEventLoop) {
+        if let Err(error) = self.update() {
+            self.error = Some(error);
+            event_loop.exit();
+        } else if self.window.close_requested() {
+            event_loop.exit();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use serial_test::serial;
+    use winit::dpi::PhysicalSize;
+    use winit::event::WindowEvent;
+
+    use super::{ChronosEngine, RendererType};
+    use crate::entity::EntityManager;
+    use crate::entity::events::EventReader;
+    use crate::renderer::shader_source::ShaderSource;
+    use crate::schedule::{Stage, SystemConfig};
+    use crate::window::{WindowCloseRequested, WindowConfig, WindowMode, WindowResized};
+
+    fn test_config() -> WindowConfig {
+        WindowConfig {
+            window_mode: WindowMode::Test,
+            ..Default::default()
+        }
+    }
+
+    fn test_engine() -> ChronosEngine {
+        ChronosEngine::new(test_config(), RendererType::OpenGL)
+    }
+
+    #[test]
+    fn test_engine_update_runs_schedule() {
+        let mut engine = test_engine();
+        let frames = Arc::new(Mutex::new(0));
+        let frames_in_system = Arc::clone(&frames);
+        engine.add_system(
+            Stage::Update,
+            SystemConfig::new("count", move |_: &mut EntityManager| {
+                *frames_in_system.lock().unwrap() += 1;
+            }),
+        );
+
+        engine.update().unwrap();
+        engine.update().unwrap();
+
+        assert_eq!(*frames.lock().unwrap(), 2);
+        assert!(
+            engine
+                .load_shader("none", &ShaderSource::default())
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_engine_frame_forwards_window_events() {
+        let mut engine = test_engine();
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let received_in_system = Arc::clone(&received);
+        engine.add_system(
+            Stage::Update,
+            SystemConfig::new(
+                "window_events",
+                move |mut resized: EventReader<WindowResized>,
+                      mut close: EventReader<WindowCloseRequested>| {
+                    received_in_system.lock().unwrap().push((
+                        resized.read().copied().collect::<Vec<_>>(),
+                        close.read().count(),
+                    ));
+                },
+            ),
+        );
+
+        engine
+            .window
+            .handle_event(&WindowEvent::Resized(PhysicalSize::new(800, 600)));
+        engine.update().unwrap();
+        engine.window.handle_event(&WindowEvent::CloseRequested);
+        assert!(engine.window.close_requested());
+        engine.update().unwrap();
+
+        assert_eq!(
+            *received.lock().unwrap(),
+            vec![
+                (
+                    vec![WindowResized {
+                        width: 800,
+                        height: 600
+                    }],
+                    0
+                ),
+                (vec![], 1)
+            ]
+        );
+    }
+
+    #[test]
+    #[serial]
+    #[ignore = "needs a display, and winit allows one event loop per process"]
+    fn test_engine_start_in_test_mode() {
+        let mut engine = ChronosEngine::start(test_config(), &RendererType::OpenGL).unwrap();
+
+        assert!(engine.window.get_window().is_some());
+        assert!(engine.renderer.is_some());
+        engine.update().unwrap();
+    }
 }
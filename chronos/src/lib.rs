@@ -1,7 +1,12 @@
+// Lets the derive macros refer to `::chronos` from inside this crate too.
+extern crate self as chronos;
+
 pub mod components;
 pub mod entity;
 pub mod game_engine;
 pub(crate) mod renderer;
+pub mod scene;
+pub mod schedule;
 #[cfg(test)]
 pub mod test_utils;
 pub mod window;
@@ -0,0 +1,581 @@
+mod builtins;
+mod prefab;
+mod text;
+mod value;
+
+use std::any::TypeId;
+use std::collections::HashMap;
+use std::path::Path;
+
+use crate::entity::{Entity, EntityManager};
+pub use prefab::{PREFAB_COMPONENT, Prefab, PrefabOverrides};
+pub use value::Value;
+
+pub type Result<T> = std::result::Result<T, SceneError>;
+
+#[derive(thiserror::Error, Debug)]
+pub enum SceneError {
+    #[error("Syntax error at line {line}: {message}")]
+    Syntax { line: usize, message: String },
+    #[error("Unknown component '{0}'")]
+    UnknownComponent(String),
+    #[error("Invalid value: {0}")]
+    InvalidValue(String),
+    #[error("Component '{component}' of entity {entity}: {source}")]
+    Component {
+        entity: u64,
+        component: String,
+        #[source]
+        source: Box<SceneError>,
+    },
+    #[error("Entity {0} is defined more than once")]
+    DuplicateEntity(u64),
+    #[error("Reference to entity {0}, which is not part of the scene")]
+    UnknownEntity(u64),
+    #[error("Reference to {0:?}, which is not part of the saved entities")]
+    UnsavedEntity(Entity),
+    #[error("Unknown prefab '{0}'")]
+    UnknownPrefab(String),
+    #[error("Prefab '{0}' contains itself")]
+    RecursivePrefab(String),
+    #[error("Prefab has no entities")]
+    EmptyPrefab,
+    #[error("Scene file error: {0}")]
+    Io(#[from] std::io::Error),
+}
+
+/// Component that can be written to and read from scene files. Register it
+/// with [`EntityManager::register_scene_component`].
+pub trait SceneComponent: Send + Sync + Sized + 'static {
+    /// Name of the component in scene files. Must be a valid identifier.
+    const NAME: &'static str;
+
+    /// # Errors
+    ///
+    /// Returns an error if the component references an entity that is not saved.
+    fn to_value(&self, entities: &EntityMap) -> Result<Value>;
+
+    /// # Errors
+    ///
+    /// Returns an error if `value` does not describe a valid component.
+    fn from_value(value: &Value, entities: &EntityMap) -> Result<Self>;
+
+    /// Adds the loaded component to `entity`. Components maintained by the
+    /// entity manager, such as `Parent`, override this to go through its API.
+    fn insert(self, entity_manager: &mut EntityManager, entity: Entity) {
+        entity_manager.add_component(entity, self);
+    }
+}
+
+/// Mapping between entities and their IDs in one scene file.
+#[derive(Debug, Default)]
+pub struct EntityMap {
+    scene_ids: HashMap<Entity, u64>,
+    entities: HashMap<u64, Entity>,
+}
+
+/// Scene components known to an entity manager, in registration order, which
+/// is also the order they are written and loaded in, and the prefabs that
+/// scenes can instantiate by name.
+pub struct SceneRegistry {
+    components: Vec<SceneComponentInfo>,
+    prefabs: HashMap<String, Prefab>,
+}
+
+struct SceneComponentInfo {
+    name: &'static str,
+    type_id: TypeId,
+    save: fn(&EntityManager, Entity, &EntityMap) -> Option<Result<Value>>,
+    load: fn(&mut EntityManager, Entity, &Value, &EntityMap) -> Result<()>,
+}
+
+impl EntityMap {
+    /// Scene ID of `entity`, to write references to it.
+    #[must_use]
+    pub fn scene_id(&self, entity: Entity) -> Option<u64> {
+        self.scene_ids.get(&entity).copied()
+    }
+
+    /// Entity created for the scene ID `scene_id` while loading.
+    #[must_use]
+    pub fn entity(&self, scene_id: u64) -> Option<Entity> {
+        self.entities.get(&scene_id).copied()
+    }
+
+    /// Reference to `entity` as written in the scene.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `entity` is not part of the saved entities.
+    pub fn to_value(&self, entity: Entity) -> Result<Value> {
+        self.scene_id(entity)
+            .map(Value::Entity)
+            .ok_or(SceneError::UnsavedEntity(entity))
+    }
+
+    pub fn insert(&mut self, scene_id: u64, entity: Entity) {
+        self.scene_ids.insert(entity, scene_id);
+        self.entities.insert(scene_id, entity);
+    }
+}
+
+impl SceneRegistry {
+    /// Registers `T`, replacing any component registered under the same name.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `T::NAME` is not a valid identifier or is [`PREFAB_COMPONENT`].
+    pub fn register<T: SceneComponent>(&mut self) {
+        assert!(
+            is_identifier(T::NAME),
+            "Scene component name '{}' is not a valid identifier",
+            T::NAME
+        );
+        assert!(
+            T::NAME != PREFAB_COMPONENT,
+            "Scene component name '{PREFAB_COMPONENT}' is reserved for prefabs"
+        );
+        self.components
+            .retain(|info| info.name != T::NAME && info.type_id != TypeId::of::<T>());
+        self.components.push(SceneComponentInfo {
+            name: T::NAME,
+            type_id: TypeId::of::<T>(),
+            save: |entity_manager, entity, entities| {
+                entity_manager
+                    .get_component::<T>(entity)
+                    .map(|component| component.to_value(entities))
+            },
+            load: |entity_manager, entity, value, entities| {
+                T::from_value(value, entities)?.insert(entity_manager, entity);
+                Ok(())
+            },
+        });
+    }
+
+    #[must_use]
+    pub fn contains(&self, name: &str) -> bool {
+        self.components.iter().any(|info| info.name == name)
+    }
+
+    /// Names of the registered components in registration order.
+    pub fn names(&self) -> impl Iterator<Item = &'static str> + '_ {
+        self.components.iter().map(|info| info.name)
+    }
+
+    /// Registers `prefab` under `name`, replacing any prefab of the same name.
+    pub fn register_prefab(&mut self, name: impl Into<String>, prefab: Prefab) {
+        self.prefabs.insert(name.into(), prefab);
+    }
+
+    #[must_use]
+    pub fn prefab(&self, name: &str) -> Option<&Prefab> {
+        self.prefabs.get(name)
+    }
+
+    fn get(&self, name: &str) -> Option<&SceneComponentInfo> {
+        self.components.iter().find(|info| info.name == name)
+    }
+}
+
+impl Default for SceneRegistry {
+    /// Registry with the built-in components: `Transform`, `Shape`, `Color`,
+    /// `RGBA`, `Material`, `Parent` and `Name`.
+    fn default() -> Self {
+        let mut registry = Self {
+            components: Vec::new(),
+            prefabs: HashMap::new(),
+        };
+        builtins::register(&mut registry);
+        registry
+    }
+}
+
+impl EntityManager {
+    /// Makes `T` part of saved and loaded scenes, and serializable through
+    /// the [type registry](EntityManager::type_registry).
+    pub fn register_scene_component<T: SceneComponent>(&mut self) {
+        self.scene_registry.register::<T>();
+        self.type_registry.register_serializable::<T>();
+    }
+
+    /// Makes `prefab` available to scenes and prefabs as `Prefab: "name"`.
+    pub fn register_prefab(&mut self, name: impl Into<String>, prefab: Prefab) {
+        self.scene_registry.register_prefab(name, prefab);
+    }
+
+    #[must_use]
+    pub fn scene_registry(&self) -> &SceneRegistry {
+        &self.scene_registry
+    }
+
+    /// Writes every entity with its registered components. Components whose
+    /// type is not registered are skipped.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a component references an entity that no longer exists.
+    pub fn save_scene(&self) -> Result<String> {
+        self.save_entities(&self.entities().collect::<Vec<_>>())
+    }
+
+    /// Writes `entities` with their registered components. References to
+    /// entities outside of `entities` are errors.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a component references an entity that is not saved.
+    pub fn save_entities(&self, entities: &[Entity]) -> Result<String> {
+        self.scene_document(entities)
+            .map(|document| text::write_scene(&document))
+    }
+
+    /// Creates the entities of a scene and returns them in file order,
+    /// followed by the entities of the prefabs it instantiates. Entity
+    /// references are remapped to the new entities. Nothing is created if
+    /// loading fails.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the text is malformed, names an unknown component
+    /// or prefab, or references an entity missing from the scene.
+    pub fn load_scene(&mut self, scene: &str) -> Result<Vec<Entity>> {
+        let document = text::parse_scene(scene)?;
+        let document = prefab::expand(&document, &self.scene_registry.prefabs)?;
+        self.load_document(&document)
+    }
+
+    /// # Errors
+    ///
+    /// Returns an error if the scene cannot be written or the file cannot be created.
+    pub fn save_scene_to_file(&self, path: impl AsRef<Path>) -> Result<()> {
+        std::fs::write(path, self.save_scene()?)?;
+        Ok(())
+    }
+
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be read or the scene cannot be loaded,
+    /// see [`EntityManager::load_scene`].
+    pub fn load_scene_from_file(&mut self, path: impl AsRef<Path>) -> Result<Vec<Entity>> {
+        let scene = std::fs::read_to_string(path)?;
+        self.load_scene(&scene)
+    }
+
+    /// Describes `entities` with their registered components, numbered in order.
+    fn scene_document(&self, entities: &[Entity]) -> Result<text::SceneDocument> {
+        let mut entity_map = EntityMap::default();
+        for (scene_id, &entity) in (0_u64..).zip(entities) {
+            entity_map.insert(scene_id, entity);
+        }
+
+        let mut document = text::SceneDocument::default();
+        for (scene_id, &entity) in (0_u64..).zip(entities) {
+            let mut components = Vec::new();
+            for info in &self.scene_registry.components {
+                if let Some(value) = (info.save)(self, entity, &entity_map) {
+                    components.push((info.name.to_string(), value?));
+                }
+            }
+            document.entities.push(text::SceneEntity {
+                id: scene_id,
+                components,
+            });
+        }
+        Ok(document)
+    }
+
+    /// Creates the entities of an expanded document, whose IDs are unique, in
+    /// document order, or nothing if loading fails.
+    fn load_document(&mut self, document: &text::SceneDocument) -> Result<Vec<Entity>> {
+        let mut entity_map = EntityMap::default();
+        let mut created = Vec::with_capacity(document.entities.len());
+        for scene_entity in &document.entities {
+            let entity = self.create_entity(());
+            entity_map.insert(scene_entity.id, entity);
+            created.push(entity);
+        }
+
+        if let Err(error) = self.load_components(document, &entity_map) {
+            self.remove_entities(&created);
+            return Err(error);
+        }
+        Ok(created)
+    }
+
+    /// Loads components type by type in registration order, so components
+    /// that depend on others, like `Parent`, see them in place.
+    fn load_components(
+        &mut self,
+        document: &text::SceneDocument,
+        entity_map: &EntityMap,
+    ) -> Result<()> {
+        for scene_entity in &document.entities {
+            for (name, _) in &scene_entity.components {
+                if !self.scene_registry.contains(name) {
+                    return Err(component_error(
+                        scene_entity.id,
+                        name,
+                        SceneError::UnknownComponent(name.clone()),
+                    ));
+                }
+            }
+        }
+
+        let names: Vec<&'static str> = self.scene_registry.names().collect();
+        for name in names {
+            let load = self.scene_registry.get(name).map(|info| info.load);
+            let Some(load) = load else {
+                continue;
+            };
+            for scene_entity in &document.entities {
+                let Some(entity) = entity_map.entity(scene_entity.id) else {
+                    continue;
+                };
+                for (_, value) in scene_entity
+                    .components
+                    .iter()
+                    .filter(|(component, _)| component == name)
+                {
+                    load(self, entity, value, entity_map)
+                        .map_err(|source| component_error(scene_entity.id, name, source))?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn remove_entities(&mut self, entities: &[Entity]) {
+        for &entity in entities {
+            self.remove_entity(entity);
+        }
+    }
+}
+
+fn component_error(entity: u64, component: &str, source: SceneError) -> SceneError {
+    SceneError::Component {
+        entity,
+        component: component.to_string(),
+        source: Box::new(source),
+    }
+}
+
+fn is_identifier(name: &str) -> bool {
+    let mut characters = name.chars();
+    characters
+        .next()
+        .is_some_and(|first| first.is_ascii_alphabetic() || first == '_')
+        && characters.all(|character| character.is_ascii_alphanumeric() || character == '_')
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::{Vec3, vec3};
+
+    use super::{EntityMap, Result, SceneComponent, SceneError, Value};
+    use crate::components::color::{Color, RGBA};
+    use crate::components::material::Material;
+    use crate::components::shape::Shape;
+    use crate::components::transform::Transform;
+    use crate::entity::hierarchy::ReparentMode;
+    use crate::entity::{Entity, EntityManager};
+
+    #[derive(Debug, PartialEq)]
+    struct Health(u32);
+
+    #[derive(Debug, PartialEq)]
+    struct Target(Entity);
+
+    impl SceneComponent for Health {
+        const NAME: &'static str = "Health";
+
+        fn to_value(&self, _entities: &EntityMap) -> Result<Value> {
+            Ok(Value::number(self.0))
+        }
+
+        fn from_value(value: &Value, _entities: &EntityMap) -> Result<Self> {
+            value.as_number().map(Health)
+        }
+    }
+
+    impl SceneComponent for Target {
+        const NAME: &'static str = "Target";
+
+        fn to_value(&self, entities: &EntityMap) -> Result<Value> {
+            entities.to_value(self.0)
+        }
+
+        fn from_value(value: &Value, entities: &EntityMap) -> Result<Self> {
+            value.as_entity(entities).map(Target)
+        }
+    }
+
+    fn game_entity_manager() -> EntityManager {
+        let mut entity_manager = EntityManager::default();
+        entity_manager.register_scene_component::<Health>();
+        entity_manager.register_scene_component::<Target>();
+        entity_manager
+    }
+
+    #[test]
+    fn test_scene_builtins_round_trip_exactly() {
+        let transform = Transform::new(
+            vec3(0.1, -2.5, 1e-7),
+            vec3(33.3, 12.0, -170.0),
+            vec3(1.0, 3.0, 0.3),
+        );
+        let shape = Shape::new_circle(Vec3::new(0.1, 0.2, 0.3), 0.7, 5);
+        let rgba = RGBA::new(12, 34, 56, 0.123_456_79);
+        let material = Material {
+            shader_id: 3,
+            color: Color::PerVertex(vec![0.1, 0.2, 1.0 / 3.0]),
+        };
+        let mut entity_manager = EntityManager::default();
+        let entity = entity_manager.create_entity((
+            transform,
+            shape.clone(),
+            Color::Uniform(rgba.clone()),
+            rgba.clone(),
+        ));
+        entity_manager.add_component(entity, material.clone());
+
+        let scene = entity_manager.save_scene().unwrap();
+        let mut loaded = EntityManager::default();
+        let entities = loaded.load_scene(&scene).unwrap();
+
+        let entity = entities[0];
+        assert_eq!(loaded.get_component::<Transform>(entity), Some(&transform));
+        assert_eq!(loaded.get_component::<Shape>(entity), Some(&shape));
+        assert_eq!(
+            loaded.get_component::<Color>(entity),
+            Some(&Color::Uniform(rgba.clone()))
+        );
+        assert_eq!(loaded.get_component::<RGBA>(entity), Some(&rgba));
+        assert_eq!(loaded.get_component::<Material>(entity), Some(&material));
+        assert_eq!(loaded.save_scene().unwrap(), scene);
+    }
+
+    #[test]
+    fn test_scene_is_human_readable() {
+        let mut entity_manager = game_entity_manager();
+        let player = entity_manager
+            .create_entity((Transform::from_translation(vec3(1.0, 2.0, 3.0)), Health(10)));
+        entity_manager.create_entity((Color::Uniform(RGBA::new(255, 128, 0, 1.0)), Target(player)));
+
+        assert_eq!(
+            entity_manager.save_scene().unwrap(),
+            "entity 0 {\n    \
+             Transform: [[1, 0, 0, 0], [0, 1, 0, 0], [0, 0, 1, 0], [1, 2, 3, 1]]\n    \
+             Health: 10\n\
+             }\n\
+             \n\
+             entity 1 {\n    \
+             Color: Uniform({ r: 255, g: 128, b: 0, alpha: 1 })\n    \
+             Target: @0\n\
+             }\n"
+        );
+    }
+
+    #[test]
+    fn test_scene_remaps_entity_references() {
+        let scene = "
+            entity 10 { Target: @20, Health: 5 }
+            entity 20 { Target: @10 }
+            entity 30 { Parent: @20 }
+        ";
+        let mut entity_manager = game_entity_manager();
+        let existing = entity_manager.create_entity((Health(1),));
+
+        let entities = entity_manager.load_scene(scene).unwrap();
+
+        let [first, second, third] = entities[..] else {
+            panic!("expected three entities");
+        };
+        assert!(!entities.contains(&existing));
+        assert_eq!(
+            entity_manager.get_component::<Target>(first),
+            Some(&Target(second))
+        );
+        assert_eq!(
+            entity_manager.get_component::<Target>(second),
+            Some(&Target(first))
+        );
+        assert_eq!(
+            entity_manager.get_component::<Health>(first),
+            Some(&Health(5))
+        );
+        assert_eq!(entity_manager.parent(third), Some(second));
+        assert_eq!(entity_manager.children(second), &[third]);
+    }
+
+    #[test]
+    fn test_scene_hierarchy_round_trip() {
+        let mut entity_manager = EntityManager::default();
+        let root = entity_manager.create_entity((Transform::identity(),));
+        let child = entity_manager.create_entity((Transform::identity(),));
+        entity_manager.set_parent(child, root, ReparentMode::KeepLocal);
+
+        let scene = entity_manager.save_scene().unwrap();
+        let mut loaded = EntityManager::default();
+        let entities = loaded.load_scene(&scene).unwrap();
+
+        assert_eq!(loaded.children(entities[0]), &[entities[1]]);
+        assert_eq!(loaded.parent(entities[1]), Some(entities[0]));
+    }
+
+    #[test]
+    fn test_scene_errors_leave_entity_manager_unchanged() {
+        let mut entity_manager = game_entity_manager();
+
+        let unknown = entity_manager.load_scene("entity 0 { Health: 1 }\nentity 1 { Mana: 3 }");
+        let invalid = entity_manager.load_scene("entity 0 { Health: -1 }");
+        let dangling = entity_manager.load_scene("entity 0 { Target: @1 }");
+        let duplicate = entity_manager.load_scene("entity 0 {}\nentity 0 {}");
+
+        assert!(matches!(
+            unknown,
+            Err(SceneError::Component { entity: 1, ref component, .. }) if component == "Mana"
+        ));
+        assert!(matches!(
+            invalid,
+            Err(SceneError::Component { entity: 0, .. })
+        ));
+        assert!(
+            matches!(dangling, Err(SceneError::Component { ref source, .. })
+            if matches!(**source, SceneError::UnknownEntity(1)))
+        );
+        assert!(matches!(duplicate, Err(SceneError::DuplicateEntity(0))));
+        assert_eq!(entity_manager.get_entity_count(), 0);
+    }
+
+    #[test]
+    fn test_scene_save_entities_rejects_outside_references() {
+        let mut entity_manager = game_entity_manager();
+        let outside = entity_manager.create_entity(());
+        let inside = entity_manager.create_entity((Target(outside),));
+
+        let result = entity_manager.save_entities(&[inside]);
+
+        assert!(matches!(result, Err(SceneError::UnsavedEntity(entity)) if entity == outside));
+    }
+
+    #[test]
+    fn test_scene_file_round_trip() {
+        let path = std::env::temp_dir().join(format!("chronos_scene_{}.scene", std::process::id()));
+        let mut entity_manager = game_entity_manager();
+        entity_manager.create_entity((Health(42),));
+
+        entity_manager.save_scene_to_file(&path).unwrap();
+        let mut loaded = game_entity_manager();
+        let entities = loaded.load_scene_from_file(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(
+            loaded.get_component::<Health>(entities[0]),
+            Some(&Health(42))
+        );
+        assert!(matches!(
+            loaded.load_scene_from_file(&path),
+            Err(SceneError::Io(_))
+        ));
+    }
+}
@@ -0,0 +1,174 @@
+use glam::{Mat4, Vec3};
+
+use crate::components::color::{Color, RGBA};
+use crate::components::material::Material;
+use crate::components::shape::Shape;
+use crate::components::transform::Transform;
+use crate::entity::hierarchy::{Parent, ReparentMode};
+use crate::entity::name::Name;
+use crate::entity::{Entity, EntityManager};
+use crate::scene::{EntityMap, Result, SceneComponent, SceneError, SceneRegistry, Value};
+
+pub(super) fn register(registry: &mut SceneRegistry) {
+    registry.register::<Transform>();
+    registry.register::<Shape>();
+    registry.register::<Color>();
+    registry.register::<RGBA>();
+    registry.register::<Material>();
+    registry.register::<Parent>();
+    registry.register::<Name>();
+}
+
+impl SceneComponent for Transform {
+    const NAME: &'static str = "Transform";
+
+    /// Written as the four matrix columns.
+    fn to_value(&self, _entities: &EntityMap) -> Result<Value> {
+        Ok(Value::List(
+            self.matrix()
+                .to_cols_array_2d()
+                .iter()
+                .map(|column| numbers(column))
+                .collect(),
+        ))
+    }
+
+    fn from_value(value: &Value, _entities: &EntityMap) -> Result<Self> {
+        let mut columns = [[0.0; 4]; 4];
+        for (column, column_value) in columns.iter_mut().zip(value.as_array::<4>()?) {
+            *column = parse_numbers(column_value)?;
+        }
+        Ok(Transform::from_matrix(Mat4::from_cols_array_2d(&columns)))
+    }
+}
+
+impl SceneComponent for Shape {
+    const NAME: &'static str = "Shape";
+
+    fn to_value(&self, _entities: &EntityMap) -> Result<Value> {
+        Ok(Value::List(
+            self.get_vertices()
+                .iter()
+                .map(|vertex| numbers(&vertex.to_array()))
+                .collect(),
+        ))
+    }
+
+    fn from_value(value: &Value, _entities: &EntityMap) -> Result<Self> {
+        let vertices = value
+            .as_list()?
+            .iter()
+            .map(|vertex| parse_numbers::<3>(vertex).map(Vec3::from_array))
+            .collect::<Result<_>>()?;
+        Ok(Shape::new(vertices))
+    }
+}
+
+impl SceneComponent for RGBA {
+    const NAME: &'static str = "RGBA";
+
+    fn to_value(&self, _entities: &EntityMap) -> Result<Value> {
+        let (r, g, b, alpha) = self.get();
+        Ok(Value::map([
+            ("r", Value::number(r)),
+            ("g", Value::number(g)),
+            ("b", Value::number(b)),
+            ("alpha", Value::number(alpha)),
+        ]))
+    }
+
+    fn from_value(value: &Value, _entities: &EntityMap) -> Result<Self> {
+        Ok(RGBA::new(
+            value.field("r")?.as_number()?,
+            value.field("g")?.as_number()?,
+            value.field("b")?.as_number()?,
+            value.field("alpha")?.as_number()?,
+        ))
+    }
+}
+
+impl SceneComponent for Color {
+    const NAME: &'static str = "Color";
+
+    fn to_value(&self, entities: &EntityMap) -> Result<Value> {
+        Ok(match self {
+            Color::Uniform(rgba) => Value::variant("Uniform", vec![rgba.to_value(entities)?]),
+            Color::PerVertex(colors) => Value::variant("PerVertex", vec![numbers(colors)]),
+        })
+    }
+
+    fn from_value(value: &Value, entities: &EntityMap) -> Result<Self> {
+        match value.as_variant()? {
+            ("Uniform", [rgba]) => Ok(Color::Uniform(RGBA::from_value(rgba, entities)?)),
+            ("PerVertex", [colors]) => Ok(Color::PerVertex(
+                colors
+                    .as_list()?
+                    .iter()
+                    .map(Value::as_number)
+                    .collect::<Result<_>>()?,
+            )),
+            (name, _) => Err(SceneError::InvalidValue(format!(
+                "expected Uniform(rgba) or PerVertex([...]), found '{name}'"
+            ))),
+        }
+    }
+}
+
+impl SceneComponent for Material {
+    const NAME: &'static str = "Material";
+
+    fn to_value(&self, entities: &EntityMap) -> Result<Value> {
+        Ok(Value::map([
+            ("shader_id", Value::number(self.shader_id)),
+            ("color", self.color.to_value(entities)?),
+        ]))
+    }
+
+    fn from_value(value: &Value, entities: &EntityMap) -> Result<Self> {
+        Ok(Material {
+            shader_id: value.field("shader_id")?.as_number()?,
+            color: Color::from_value(value.field("color")?, entities)?,
+        })
+    }
+}
+
+impl SceneComponent for Parent {
+    const NAME: &'static str = "Parent";
+
+    fn to_value(&self, entities: &EntityMap) -> Result<Value> {
+        entities.to_value(self.get())
+    }
+
+    fn from_value(value: &Value, entities: &EntityMap) -> Result<Self> {
+        value.as_entity(entities).map(Parent::new)
+    }
+
+    /// Goes through [`EntityManager::set_parent`] so `Children` is rebuilt.
+    fn insert(self, entity_manager: &mut EntityManager, entity: Entity) {
+        entity_manager.set_parent(entity, self.get(), ReparentMode::KeepLocal);
+    }
+}
+
+impl SceneComponent for Name {
+    const NAME: &'static str = "Name";
+
+    fn to_value(&self, _entities: &EntityMap) -> Result<Value> {
+        Ok(Value::String(self.as_str().to_string()))
+    }
+
+    fn from_value(value: &Value, _entities: &EntityMap) -> Result<Self> {
+        value.as_str().map(Name::new)
+    }
+}
+
+fn numbers(values: &[f32]) -> Value {
+    Value::List(values.iter().map(|&value| Value::number(value)).collect())
+}
+
+fn parse_numbers<const N: usize>(value: &Value) -> Result<[f32; N]> {
+    let mut numbers = [0.0; N];
+    for (number, value) in numbers.iter_mut().zip(value.as_array::<N>()?) {
+        *number = value.as_number()?;
+    }
+    Ok(numbers)
+}
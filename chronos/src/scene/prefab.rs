@@ -0,0 +1,607 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt::{self, Display};
+use std::path::Path;
+use std::str::FromStr;
+
+use crate::entity::hierarchy::Parent;
+use crate::entity::{Entity, EntityManager};
+use crate::scene::text::{self, SceneDocument, SceneEntity};
+use crate::scene::{EntityMap, Result, SceneComponent, SceneError, Value, component_error};
+
+/// Pseudo-component that replaces an entity of a scene or prefab with an
+/// instance of the registered prefab it names, as in `Prefab: "tree"`. The
+/// other components of the entity override those of the prefab root.
+pub const PREFAB_COMPONENT: &str = "Prefab";
+
+type Override = Box<dyn FnOnce(&mut EntityManager, Entity) + Send>;
+
+/// Reusable set of entities and their components, written in the scene
+/// format. The first entity is the root, which
+/// [`EntityManager::spawn_prefab`] returns. Entity references must point to
+/// entities of the prefab.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Prefab {
+    document: SceneDocument,
+}
+
+/// Components replacing those of a prefab for a single instance.
+#[derive(Default)]
+pub struct PrefabOverrides {
+    overrides: Vec<(Option<u64>, Override)>,
+}
+
+impl Prefab {
+    /// Prefab with a root entity and no components.
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            document: SceneDocument {
+                entities: vec![SceneEntity {
+                    id: 0,
+                    components: Vec::new(),
+                }],
+            },
+        }
+    }
+
+    /// Captures `entities` with their registered components. The first one
+    /// becomes the root.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `entities` is empty or a component references an
+    /// entity outside of `entities`.
+    pub fn from_entities(entity_manager: &EntityManager, entities: &[Entity]) -> Result<Self> {
+        Self::from_document(entity_manager.scene_document(entities)?)
+    }
+
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be read, is malformed, has no
+    /// entities or references an entity it does not contain.
+    pub fn load_from_file(path: impl AsRef<Path>) -> Result<Self> {
+        std::fs::read_to_string(path)?.parse()
+    }
+
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be written.
+    pub fn save_to_file(&self, path: impl AsRef<Path>) -> Result<()> {
+        std::fs::write(path, self.to_string())?;
+        Ok(())
+    }
+
+    /// Sets a component of the root, replacing the one with the same name.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the component references an entity, which only prefab text
+    /// can express.
+    #[must_use]
+    pub fn with<T: SceneComponent>(mut self, component: &T) -> Self {
+        let value = component
+            .to_value(&EntityMap::default())
+            .unwrap_or_else(|error| panic!("Cannot add {} to a prefab: {error}", T::NAME));
+        set_component(&mut self.document.entities[0].components, T::NAME, value);
+        self
+    }
+
+    /// Copies the entities of `child` into this prefab, with the root of
+    /// `child` parented to the root.
+    #[must_use]
+    pub fn with_child(mut self, child: &Prefab) -> Self {
+        let root = self.root_id();
+        let mut next_id = self.next_id();
+        let ids: HashMap<u64, u64> = child
+            .document
+            .entities
+            .iter()
+            .map(|entity| {
+                next_id += 1;
+                (entity.id, next_id - 1)
+            })
+            .collect();
+
+        for (index, entity) in child.document.entities.iter().enumerate() {
+            let mut components = entity.components.clone();
+            for (_, value) in &mut components {
+                remap_entities(value, &ids);
+            }
+            if index == 0 {
+                set_component(&mut components, Parent::NAME, Value::Entity(root));
+            }
+            self.document.entities.push(SceneEntity {
+                id: ids[&entity.id],
+                components,
+            });
+        }
+        self
+    }
+
+    /// Adds a child of the root that instantiates the prefab registered as
+    /// `name` when spawned.
+    #[must_use]
+    pub fn with_child_prefab(mut self, name: &str) -> Self {
+        let root = self.root_id();
+        let id = self.next_id();
+        self.document.entities.push(SceneEntity {
+            id,
+            components: vec![
+                (
+                    PREFAB_COMPONENT.to_string(),
+                    Value::String(name.to_string()),
+                ),
+                (Parent::NAME.to_string(), Value::Entity(root)),
+            ],
+        });
+        self
+    }
+
+    /// ID of the root entity in the prefab text.
+    #[must_use]
+    pub fn root_id(&self) -> u64 {
+        self.document.entities[0].id
+    }
+
+    /// Prefabs only reference their own entities, so copies of them can be
+    /// renumbered.
+    fn from_document(document: SceneDocument) -> Result<Self> {
+        if document.entities.is_empty() {
+            return Err(SceneError::EmptyPrefab);
+        }
+        let ids: HashSet<u64> = document.entities.iter().map(|entity| entity.id).collect();
+        for entity in &document.entities {
+            for (component, value) in &entity.components {
+                check_references(value, &ids)
+                    .map_err(|source| component_error(entity.id, component, source))?;
+            }
+        }
+        Ok(Self { document })
+    }
+
+    fn next_id(&self) -> u64 {
+        self.document
+            .entities
+            .iter()
+            .map(|entity| entity.id + 1)
+            .max()
+            .unwrap_or(0)
+    }
+}
+
+impl Default for Prefab {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Writes the prefab in scene file syntax.
+impl Display for Prefab {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        formatter.write_str(&text::write_scene(&self.document))
+    }
+}
+
+/// Parses a prefab in scene file syntax.
+impl FromStr for Prefab {
+    type Err = SceneError;
+
+    fn from_str(text: &str) -> Result<Self> {
+        Self::from_document(text::parse_scene(text)?)
+    }
+}
+
+impl PrefabOverrides {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Replaces a component of the root.
+    #[must_use]
+    pub fn with<T: SceneComponent>(self, component: T) -> Self {
+        self.push(None, component)
+    }
+
+    /// Replaces a component of the entity with ID `id` in the prefab text.
+    #[must_use]
+    pub fn with_for<T: SceneComponent>(self, id: u64, component: T) -> Self {
+        self.push(Some(id), component)
+    }
+
+    fn push<T: SceneComponent>(mut self, id: Option<u64>, component: T) -> Self {
+        self.overrides.push((
+            id,
+            Box::new(move |entity_manager, entity| component.insert(entity_manager, entity)),
+        ));
+        self
+    }
+}
+
+impl EntityManager {
+    /// Creates the entities of `prefab`, applies `overrides` and returns the
+    /// root. Nothing is created if spawning fails.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the prefab names an unknown component or prefab,
+    /// contains itself, or `overrides` target an entity the prefab does not
+    /// have.
+    pub fn spawn_prefab(&mut self, prefab: &Prefab, overrides: PrefabOverrides) -> Result<Entity> {
+        let overrides = overrides
+            .overrides
+            .into_iter()
+            .map(|(id, apply)| {
+                let id = id.unwrap_or(prefab.root_id());
+                prefab
+                    .document
+                    .entities
+                    .iter()
+                    .position(|entity| entity.id == id)
+                    .map(|index| (index, apply))
+                    .ok_or(SceneError::UnknownEntity(id))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let document = expand(&prefab.document, &self.scene_registry.prefabs)?;
+        let created = self.load_document(&document)?;
+        for (index, apply) in overrides {
+            apply(self, created[index]);
+        }
+        Ok(created[0])
+    }
+
+    /// Spawns the prefab registered as `name`, see [`EntityManager::spawn_prefab`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if no prefab is registered as `name` or spawning fails.
+    pub fn spawn_registered_prefab(
+        &mut self,
+        name: &str,
+        overrides: PrefabOverrides,
+    ) -> Result<Entity> {
+        let prefab = self
+            .scene_registry
+            .prefab(name)
+            .cloned()
+            .ok_or_else(|| SceneError::UnknownPrefab(name.to_string()))?;
+        self.spawn_prefab(&prefab, overrides)
+    }
+}
+
+/// Replaces every entity with a [`PREFAB_COMPONENT`] by the entities of the
+/// named prefab, recursively. The entities of `document` keep their IDs and
+/// order, and the other instantiated entities follow with new IDs.
+pub(super) fn expand(
+    document: &SceneDocument,
+    prefabs: &HashMap<String, Prefab>,
+) -> Result<SceneDocument> {
+    expand_nested(document, prefabs, &mut Vec::new())
+}
+
+fn expand_nested(
+    document: &SceneDocument,
+    prefabs: &HashMap<String, Prefab>,
+    expanding: &mut Vec<String>,
+) -> Result<SceneDocument> {
+    let mut ids = HashSet::new();
+    for entity in &document.entities {
+        if !ids.insert(entity.id) {
+            return Err(SceneError::DuplicateEntity(entity.id));
+        }
+    }
+
+    let mut next_id = document
+        .entities
+        .iter()
+        .map(|entity| entity.id + 1)
+        .max()
+        .unwrap_or(0);
+    let mut expanded = SceneDocument::default();
+    let mut instantiated = Vec::new();
+    for entity in &document.entities {
+        let Some((_, name)) = entity
+            .components
+            .iter()
+            .find(|(component, _)| component == PREFAB_COMPONENT)
+        else {
+            expanded.entities.push(entity.clone());
+            continue;
+        };
+        let name = name
+            .as_str()
+            .map_err(|source| component_error(entity.id, PREFAB_COMPONENT, source))?;
+        let prefab = prefabs.get(name).ok_or_else(|| {
+            component_error(
+                entity.id,
+                PREFAB_COMPONENT,
+                SceneError::UnknownPrefab(name.to_string()),
+            )
+        })?;
+        if expanding.iter().any(|expanding| expanding == name) {
+            return Err(SceneError::RecursivePrefab(name.to_string()));
+        }
+
+        expanding.push(name.to_string());
+        let nested = expand_nested(&prefab.document, prefabs, expanding)?;
+        expanding.pop();
+
+        let nested_ids: HashMap<u64, u64> = nested
+            .entities
+            .iter()
+            .enumerate()
+            .map(|(index, nested_entity)| {
+                if index == 0 {
+                    (nested_entity.id, entity.id)
+                } else {
+                    next_id += 1;
+                    (nested_entity.id, next_id - 1)
+                }
+            })
+            .collect();
+        let mut nested_entities = nested.entities.into_iter().map(|mut nested_entity| {
+            nested_entity.id = nested_ids[&nested_entity.id];
+            for (_, value) in &mut nested_entity.components {
+                remap_entities(value, &nested_ids);
+            }
+            nested_entity
+        });
+
+        let mut root = nested_entities
+            .next()
+            .expect("Prefabs have at least one entity");
+        for (component, value) in &entity.components {
+            if component != PREFAB_COMPONENT {
+                set_component(&mut root.components, component, value.clone());
+            }
+        }
+        expanded.entities.push(root);
+        instantiated.extend(nested_entities);
+    }
+    expanded.entities.extend(instantiated);
+    Ok(expanded)
+}
+
+fn set_component(components: &mut Vec<(String, Value)>, name: &str, value: Value) {
+    match components
+        .iter_mut()
+        .find(|(component, _)| component == name)
+    {
+        Some((_, existing)) => *existing = value,
+        None => components.push((name.to_string(), value)),
+    }
+}
+
+fn check_references(value: &Value, ids: &HashSet<u64>) -> Result<()> {
+    match value {
+        Value::Entity(id) if !ids.contains(id) => Err(SceneError::UnknownEntity(*id)),
+        Value::List(values) | Value::Variant(_, values) => values
+            .iter()
+            .try_for_each(|value| check_references(value, ids)),
+        Value::Map(fields) => fields
+            .iter()
+            .try_for_each(|(_, value)| check_references(value, ids)),
+        _ => Ok(()),
+    }
+}
+
+/// Rewrites references to the entities of a copied prefab.
+fn remap_entities(value: &mut Value, ids: &HashMap<u64, u64>) {
+    match value {
+        Value::Entity(id) => {
+            if let Some(&new_id) = ids.get(id) {
+                *id = new_id;
+            }
+        }
+        Value::List(values) | Value::Variant(_, values) => {
+            for value in values {
+                remap_entities(value, ids);
+            }
+        }
+        Value::Map(fields) => {
+            for (_, value) in fields {
+                remap_entities(value, ids);
+            }
+        }
+        Value::Bool(_) | Value::Number(_) | Value::String(_) => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::{Vec3, vec3};
+
+    use super::{Prefab, PrefabOverrides};
+    use crate::components::color::{Color, RGBA};
+    use crate::components::shape::Shape;
+    use crate::components::transform::Transform;
+    use crate::entity::EntityManager;
+    use crate::scene::SceneError;
+
+    fn tree() -> Prefab {
+        Prefab::new()
+            .with(&Shape::new_circle(Vec3::ZERO, 1.0, 6))
+            .with(&Color::Uniform(RGBA::new(0, 128, 0, 1.0)))
+            .with(&Transform::identity())
+    }
+
+    #[test]
+    fn test_prefab_spawn_with_overrides() {
+        let mut entity_manager = EntityManager::default();
+        let prefab = tree();
+        let moved = Transform::from_translation(vec3(5.0, 0.0, 0.0));
+
+        let first = entity_manager
+            .spawn_prefab(&prefab, PrefabOverrides::new())
+            .unwrap();
+        let second = entity_manager
+            .spawn_prefab(&prefab, PrefabOverrides::new().with(moved))
+            .unwrap();
+
+        assert_ne!(first, second);
+        assert_eq!(
+            entity_manager.get_component::<Transform>(first),
+            Some(&Transform::identity())
+        );
+        assert_eq!(
+            entity_manager.get_component::<Transform>(second),
+            Some(&moved)
+        );
+        assert_eq!(
+            entity_manager.get_component::<Shape>(first),
+            entity_manager.get_component::<Shape>(second)
+        );
+    }
+
+    #[test]
+    fn test_prefab_nested_children() {
+        let mut entity_manager = EntityManager::default();
+        let leaf = Prefab::new().with(&Color::Uniform(RGBA::new(0, 255, 0, 1.0)));
+        entity_manager.register_prefab("leaf", leaf.clone());
+        let prefab = tree().with_child(&leaf).with_child_prefab("leaf");
+
+        let root = entity_manager
+            .spawn_prefab(&prefab, PrefabOverrides::new())
+            .unwrap();
+
+        let children = entity_manager.children(root).to_vec();
+        assert_eq!(children.len(), 2);
+        for child in children {
+            assert_eq!(entity_manager.parent(child), Some(root));
+            assert_eq!(
+                entity_manager.get_component::<Color>(child),
+                Some(&Color::Uniform(RGBA::new(0, 255, 0, 1.0)))
+            );
+        }
+        assert_eq!(entity_manager.get_entity_count(), 3);
+    }
+
+    #[test]
+    fn test_prefab_from_text() {
+        let mut entity_manager = EntityManager::default();
+        let wheel: Prefab = "entity 0 { Color: Uniform({ r: 1, g: 1, b: 1, alpha: 1 }) }"
+            .parse()
+            .unwrap();
+        entity_manager.register_prefab("wheel", wheel);
+        let car: Prefab = "
+            entity 0 { Transform: [[1, 0, 0, 0], [0, 1, 0, 0], [0, 0, 1, 0], [0, 0, 0, 1]] }
+            // The second wheel overrides the prefab color.
+            entity 1 { Prefab: \"wheel\", Parent: @0 }
+            entity 2 {
+                Prefab: \"wheel\"
+                Parent: @0
+                Color: Uniform({ r: 9, g: 9, b: 9, alpha: 1 })
+            }
+        "
+        .parse()
+        .unwrap();
+
+        let root = entity_manager
+            .spawn_prefab(
+                &car,
+                PrefabOverrides::new().with_for(1, Color::Uniform(RGBA::new(2, 2, 2, 1.0))),
+            )
+            .unwrap();
+
+        let colors: Vec<Color> = entity_manager
+            .children(root)
+            .iter()
+            .map(|&child| {
+                entity_manager
+                    .get_component::<Color>(child)
+                    .unwrap()
+                    .clone()
+            })
+            .collect();
+        assert_eq!(
+            colors,
+            vec![
+                Color::Uniform(RGBA::new(2, 2, 2, 1.0)),
+                Color::Uniform(RGBA::new(9, 9, 9, 1.0)),
+            ]
+        );
+        assert_eq!(car.to_string().parse::<Prefab>().unwrap(), car);
+    }
+
+    #[test]
+    fn test_scene_instantiates_prefabs() {
+        let mut entity_manager = EntityManager::default();
+        entity_manager.register_prefab("tree", tree().with_child(&tree()));
+        let moved = Transform::from_translation(vec3(1.0, 2.0, 3.0));
+        let scene = format!(
+            "entity 7 {{ Prefab: \"tree\", Transform: {} }}\nentity 8 {{ Parent: @7 }}",
+            crate::scene::SceneComponent::to_value(&moved, &crate::scene::EntityMap::default())
+                .unwrap()
+        );
+
+        let entities = entity_manager.load_scene(&scene).unwrap();
+
+        assert_eq!(entities.len(), 3);
+        assert_eq!(
+            entity_manager.get_component::<Transform>(entities[0]),
+            Some(&moved)
+        );
+        assert!(entity_manager.has_component::<Shape>(entities[0]));
+        assert_eq!(
+            entity_manager.children(entities[0]),
+            &[entities[1], entities[2]]
+        );
+    }
+
+    #[test]
+    fn test_prefab_errors_create_nothing() {
+        let mut entity_manager = EntityManager::default();
+        entity_manager.register_prefab("loop", Prefab::new().with_child_prefab("loop"));
+
+        let recursive = entity_manager.spawn_registered_prefab("loop", PrefabOverrides::new());
+        let unknown = entity_manager.spawn_prefab(
+            &Prefab::new().with_child_prefab("missing"),
+            PrefabOverrides::new(),
+        );
+        let unregistered =
+            entity_manager.spawn_registered_prefab("missing", PrefabOverrides::new());
+        let bad_override = entity_manager.spawn_prefab(
+            &tree(),
+            PrefabOverrides::new().with_for(3, Transform::identity()),
+        );
+
+        assert!(matches!(recursive, Err(SceneError::RecursivePrefab(name)) if name == "loop"));
+        assert!(
+            matches!(unknown, Err(SceneError::Component { ref source, .. })
+            if matches!(**source, SceneError::UnknownPrefab(_)))
+        );
+        assert!(matches!(unregistered, Err(SceneError::UnknownPrefab(_))));
+        assert!(matches!(bad_override, Err(SceneError::UnknownEntity(3))));
+        assert!(matches!("".parse::<Prefab>(), Err(SceneError::EmptyPrefab)));
+        assert!(matches!(
+            "entity 0 { Parent: @1 }".parse::<Prefab>(),
+            Err(SceneError::Component { .. })
+        ));
+        assert_eq!(entity_manager.get_entity_count(), 0);
+    }
+
+    #[test]
+    fn test_prefab_from_entities() {
+        let mut entity_manager = EntityManager::default();
+        let root = entity_manager.create_entity((Transform::identity(),));
+        let child = entity_manager.create_entity((Color::Uniform(RGBA::new(3, 2, 1, 1.0)),));
+        entity_manager.set_parent(
+            child,
+            root,
+            crate::entity::hierarchy::ReparentMode::KeepLocal,
+        );
+
+        let prefab = Prefab::from_entities(&entity_manager, &[root, child]).unwrap();
+        let copy = entity_manager
+            .spawn_prefab(&prefab, PrefabOverrides::new())
+            .unwrap();
+
+        let copy_child = entity_manager.children(copy)[0];
+        assert_eq!(
+            entity_manager.get_component::<Color>(copy_child),
+            entity_manager.get_component::<Color>(child)
+        );
+        assert!(Prefab::from_entities(&entity_manager, &[child]).is_err());
+    }
+}
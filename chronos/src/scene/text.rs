@@ -0,0 +1,442 @@
+//! Text form of scenes:
+//!
+//! ```text
+//! // Comments run to the end of the line.
+//! entity 0 {
+//!     Transform: [[1, 0, 0, 0], [0, 1, 0, 0], [0, 0, 1, 0], [0, 0, 0, 1]]
+//!     Color: Uniform({ r: 255, g: 128, b: 0, alpha: 1 })
+//! }
+//! entity 1 {
+//!     Parent: @0
+//! }
+//! // Instance of a registered prefab, with its root's `Parent` overridden.
+//! entity 2 { Prefab: "tree", Parent: @0 }
+//! ```
+
+use std::fmt::Write;
+
+use crate::scene::{Result, SceneError, Value};
+
+/// Parsed scene file: entities in file order with their components.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub(crate) struct SceneDocument {
+    pub(crate) entities: Vec<SceneEntity>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct SceneEntity {
+    pub(crate) id: u64,
+    pub(crate) components: Vec<(String, Value)>,
+}
+
+struct Parser<'a> {
+    text: &'a str,
+    position: usize,
+    line: usize,
+}
+
+pub(crate) fn write_scene(document: &SceneDocument) -> String {
+    let mut text = String::new();
+    for (index, entity) in document.entities.iter().enumerate() {
+        if index > 0 {
+            text.push('\n');
+        }
+        let _ = writeln!(text, "entity {} {{", entity.id);
+        for (name, value) in &entity.components {
+            let _ = write!(text, "    {name}: ");
+            write_value(&mut text, value);
+            text.push('\n');
+        }
+        text.push_str("}\n");
+    }
+    text
+}
+
+pub(crate) fn write_value(text: &mut String, value: &Value) {
+    match value {
+        Value::Bool(value) => {
+            let _ = write!(text, "{value}");
+        }
+        Value::Number(number) => text.push_str(number),
+        Value::String(string) => write_string(text, string),
+        Value::Entity(id) => {
+            let _ = write!(text, "@{id}");
+        }
+        Value::List(values) => {
+            text.push('[');
+            write_separated(text, values, write_value);
+            text.push(']');
+        }
+        Value::Map(fields) => {
+            if fields.is_empty() {
+                text.push_str("{}");
+                return;
+            }
+            text.push_str("{ ");
+            write_separated(text, fields, |text, (name, value)| {
+                let _ = write!(text, "{name}: ");
+                write_value(text, value);
+            });
+            text.push_str(" }");
+        }
+        Value::Variant(name, fields) => {
+            text.push_str(name);
+            if !fields.is_empty() {
+                text.push('(');
+                write_separated(text, fields, write_value);
+                text.push(')');
+            }
+        }
+    }
+}
+
+fn write_separated<T>(text: &mut String, items: &[T], write_item: impl Fn(&mut String, &T)) {
+    for (index, item) in items.iter().enumerate() {
+        if index > 0 {
+            text.push_str(", ");
+        }
+        write_item(text, item);
+    }
+}
+
+fn write_string(text: &mut String, string: &str) {
+    text.push('"');
+    for character in string.chars() {
+        match character {
+            '"' => text.push_str("\\\""),
+            '\\' => text.push_str("\\\\"),
+            '\n' => text.push_str("\\n"),
+            '\r' => text.push_str("\\r"),
+            '\t' => text.push_str("\\t"),
+            other => text.push(other),
+        }
+    }
+    text.push('"');
+}
+
+/// # Errors
+///
+/// Returns a syntax error with the line it occurred on.
+pub(crate) fn parse_scene(text: &str) -> Result<SceneDocument> {
+    let mut parser = Parser::new(text);
+    let mut document = SceneDocument::default();
+    loop {
+        parser.skip_whitespace();
+        if parser.peek().is_none() {
+            return Ok(document);
+        }
+        let keyword = parser.identifier()?;
+        if keyword != "entity" {
+            return Err(parser.error(format!("expected 'entity', found '{keyword}'")));
+        }
+        parser.skip_whitespace();
+        let id = parser.number()?;
+        let id = id
+            .parse()
+            .map_err(|_| parser.error(format!("invalid entity id '{id}'")))?;
+        let components = parser.components()?;
+        document.entities.push(SceneEntity { id, components });
+    }
+}
+
+/// # Errors
+///
+/// Returns a syntax error if `text` is not exactly one value.
+pub(crate) fn parse_value(text: &str) -> Result<Value> {
+    let mut parser = Parser::new(text);
+    let value = parser.value()?;
+    parser.skip_whitespace();
+    match parser.peek() {
+        None => Ok(value),
+        Some(character) => Err(parser.error(format!("unexpected '{character}' after value"))),
+    }
+}
+
+impl<'a> Parser<'a> {
+    fn new(text: &'a str) -> Self {
+        Self {
+            text,
+            position: 0,
+            line: 1,
+        }
+    }
+
+    /// Parses `{ Name: value ... }`, with optional commas between entries.
+    fn components(&mut self) -> Result<Vec<(String, Value)>> {
+        self.skip_whitespace();
+        self.expect('{')?;
+        let mut components = Vec::new();
+        loop {
+            self.skip_whitespace();
+            if self.eat('}') {
+                return Ok(components);
+            }
+            let name = self.identifier()?;
+            self.skip_whitespace();
+            self.expect(':')?;
+            let value = self.value()?;
+            components.push((name, value));
+            self.skip_whitespace();
+            self.eat(',');
+        }
+    }
+
+    fn value(&mut self) -> Result<Value> {
+        self.skip_whitespace();
+        match self.peek() {
+            Some('"') => self.string().map(Value::String),
+            Some('@') => {
+                self.advance();
+                let id = self.number()?;
+                id.parse()
+                    .map(Value::Entity)
+                    .map_err(|_| self.error(format!("invalid entity reference '@{id}'")))
+            }
+            Some('[') => {
+                self.advance();
+                self.separated(']', Self::value).map(Value::List)
+            }
+            Some('{') => {
+                self.advance();
+                self.separated('}', |parser| {
+                    parser.skip_whitespace();
+                    let name = parser.identifier()?;
+                    parser.skip_whitespace();
+                    parser.expect(':')?;
+                    Ok((name, parser.value()?))
+                })
+                .map(Value::Map)
+            }
+            Some(character) if character.is_ascii_digit() || "-+.".contains(character) => {
+                self.number().map(Value::Number)
+            }
+            Some(character) if is_identifier_start(character) => {
+                let name = self.identifier()?;
+                match name.as_str() {
+                    "true" => return Ok(Value::Bool(true)),
+                    "false" => return Ok(Value::Bool(false)),
+                    "inf" | "NaN" => return Ok(Value::Number(name)),
+                    _ => {}
+                }
+                self.skip_whitespace();
+                let fields = if self.eat('(') {
+                    self.separated(')', Self::value)?
+                } else {
+                    Vec::new()
+                };
+                Ok(Value::Variant(name, fields))
+            }
+            Some(character) => Err(self.error(format!("unexpected '{character}'"))),
+            None => Err(self.error("unexpected end of input".to_string())),
+        }
+    }
+
+    /// Parses items up to `close`, separated by commas with an optional trailing one.
+    fn separated<T>(
+        &mut self,
+        close: char,
+        mut item: impl FnMut(&mut Self) -> Result<T>,
+    ) -> Result<Vec<T>> {
+        let mut items = Vec::new();
+        loop {
+            self.skip_whitespace();
+            if self.eat(close) {
+                return Ok(items);
+            }
+            items.push(item(self)?);
+            self.skip_whitespace();
+            if !self.eat(',') {
+                self.skip_whitespace();
+                self.expect(close)?;
+                return Ok(items);
+            }
+        }
+    }
+
+    fn string(&mut self) -> Result<String> {
+        self.expect('"')?;
+        let mut string = String::new();
+        loop {
+            match self.advance() {
+                Some('"') => return Ok(string),
+                Some('\\') => match self.advance() {
+                    Some('"') => string.push('"'),
+                    Some('\\') => string.push('\\'),
+                    Some('n') => string.push('\n'),
+                    Some('r') => string.push('\r'),
+                    Some('t') => string.push('\t'),
+                    other => {
+                        return Err(self.error(format!("invalid escape sequence '\\{other:?}'")));
+                    }
+                },
+                Some(character) => string.push(character),
+                None => return Err(self.error("unterminated string".to_string())),
+            }
+        }
+    }
+
+    fn number(&mut self) -> Result<String> {
+        let number = self.take_while(|character| {
+            character.is_ascii_alphanumeric() || "-+._".contains(character)
+        });
+        if number.is_empty() {
+            return Err(self.error("expected a number".to_string()));
+        }
+        Ok(number.to_string())
+    }
+
+    fn identifier(&mut self) -> Result<String> {
+        match self.peek() {
+            Some(character) if is_identifier_start(character) => Ok(self
+                .take_while(|character| character.is_ascii_alphanumeric() || character == '_')
+                .to_string()),
+            Some(character) => Err(self.error(format!("expected a name, found '{character}'"))),
+            None => Err(self.error("expected a name, found end of input".to_string())),
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        loop {
+            self.take_while(char::is_whitespace);
+            if self.text[self.position..].starts_with("//") {
+                self.take_while(|character| character != '\n');
+            } else {
+                return;
+            }
+        }
+    }
+
+    fn take_while(&mut self, predicate: impl Fn(char) -> bool) -> &'a str {
+        let start = self.position;
+        while let Some(character) = self.peek() {
+            if !predicate(character) {
+                break;
+            }
+            self.advance();
+        }
+        &self.text[start..self.position]
+    }
+
+    fn expect(&mut self, expected: char) -> Result<()> {
+        if self.eat(expected) {
+            return Ok(());
+        }
+        match self.peek() {
+            Some(character) => {
+                Err(self.error(format!("expected '{expected}', found '{character}'")))
+            }
+            None => Err(self.error(format!("expected '{expected}', found end of input"))),
+        }
+    }
+
+    fn eat(&mut self, expected: char) -> bool {
+        if self.peek() == Some(expected) {
+            self.advance();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.text[self.position..].chars().next()
+    }
+
+    fn advance(&mut self) -> Option<char> {
+        let character = self.peek()?;
+        self.position += character.len_utf8();
+        if character == '\n' {
+            self.line += 1;
+        }
+        Some(character)
+    }
+
+    fn error(&self, message: String) -> SceneError {
+        SceneError::Syntax {
+            line: self.line,
+            message,
+        }
+    }
+}
+
+fn is_identifier_start(character: char) -> bool {
+    character.is_ascii_alphabetic() || character == '_'
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{SceneDocument, SceneEntity, parse_scene, parse_value, write_scene, write_value};
+    use crate::scene::{SceneError, Value};
+
+    fn round_trip(value: &Value) -> Value {
+        let mut text = String::new();
+        write_value(&mut text, value);
+        parse_value(&text).unwrap()
+    }
+
+    #[test]
+    fn test_value_text_round_trip() {
+        let value = Value::map([
+            ("flag", Value::Bool(false)),
+            ("name", Value::String("a \"quoted\"\nline\\".to_string())),
+            ("target", Value::Entity(7)),
+            (
+                "items",
+                Value::List(vec![Value::number(-1.5_f32), Value::number(f32::INFINITY)]),
+            ),
+            ("empty", Value::Map(Vec::new())),
+            (
+                "variant",
+                Value::variant(
+                    "Uniform",
+                    vec![Value::number(3), Value::variant("None", vec![])],
+                ),
+            ),
+        ]);
+
+        assert_eq!(round_trip(&value), value);
+    }
+
+    #[test]
+    fn test_parse_scene() {
+        let text = "
+            // two entities
+            entity 4 {
+                Health: 10,
+                Target: @5
+            }
+            entity 5 {}
+        ";
+
+        let document = parse_scene(text).unwrap();
+
+        assert_eq!(
+            document,
+            SceneDocument {
+                entities: vec![
+                    SceneEntity {
+                        id: 4,
+                        components: vec![
+                            ("Health".to_string(), Value::number(10)),
+                            ("Target".to_string(), Value::Entity(5)),
+                        ],
+                    },
+                    SceneEntity {
+                        id: 5,
+                        components: Vec::new(),
+                    },
+                ],
+            }
+        );
+        assert_eq!(parse_scene(&write_scene(&document)).unwrap(), document);
+    }
+
+    #[test]
+    fn test_parse_scene_reports_line() {
+        let result = parse_scene("entity 0 {\n    Health: [1, 2\n}\n");
+
+        assert!(matches!(result, Err(SceneError::Syntax { line: 3, .. })));
+        assert!(parse_scene("thing 0 {}").is_err());
+        assert!(parse_value("[1, 2] 3").is_err());
+    }
+}
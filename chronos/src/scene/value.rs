@@ -0,0 +1,215 @@
+use std::fmt::{self, Display};
+use std::str::FromStr;
+
+use crate::entity::Entity;
+use crate::scene::text::{parse_value, write_value};
+use crate::scene::{EntityMap, Result, SceneError};
+
+/// Component data as written in a scene file.
+///
+/// Numbers keep their text, so floats are parsed straight into the target
+/// type and round-trip exactly.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    Bool(bool),
+    Number(String),
+    String(String),
+    /// Reference to another entity of the scene, written `@id`.
+    Entity(u64),
+    List(Vec<Value>),
+    /// Named fields in declaration order, written `{ name: value }`.
+    Map(Vec<(String, Value)>),
+    /// Enum variant with its fields, written `Name(value, ...)` or `Name`.
+    Variant(String, Vec<Value>),
+}
+
+impl Value {
+    pub fn number(number: impl Display) -> Self {
+        Self::Number(number.to_string())
+    }
+
+    pub fn map<'a>(fields: impl IntoIterator<Item = (&'a str, Value)>) -> Self {
+        Self::Map(
+            fields
+                .into_iter()
+                .map(|(name, value)| (name.to_string(), value))
+                .collect(),
+        )
+    }
+
+    #[must_use]
+    pub fn variant(name: &str, fields: Vec<Value>) -> Self {
+        Self::Variant(name.to_string(), fields)
+    }
+
+    /// # Errors
+    ///
+    /// Returns an error if the value is not a number that fits `T`.
+    pub fn as_number<T: FromStr>(&self) -> Result<T> {
+        match self {
+            Self::Number(text) => text.parse().map_err(|_| {
+                SceneError::InvalidValue(format!(
+                    "'{text}' is not a valid {}",
+                    std::any::type_name::<T>()
+                ))
+            }),
+            other => Err(other.unexpected("a number")),
+        }
+    }
+
+    /// # Errors
+    ///
+    /// Returns an error if the value is not a boolean.
+    pub fn as_bool(&self) -> Result<bool> {
+        match self {
+            Self::Bool(value) => Ok(*value),
+            other => Err(other.unexpected("a boolean")),
+        }
+    }
+
+    /// # Errors
+    ///
+    /// Returns an error if the value is not a string.
+    pub fn as_str(&self) -> Result<&str> {
+        match self {
+            Self::String(value) => Ok(value),
+            other => Err(other.unexpected("a string")),
+        }
+    }
+
+    /// # Errors
+    ///
+    /// Returns an error if the value is not a list.
+    pub fn as_list(&self) -> Result<&[Value]> {
+        match self {
+            Self::List(values) => Ok(values),
+            other => Err(other.unexpected("a list")),
+        }
+    }
+
+    /// Returns the list items if there are exactly `N` of them.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the value is not a list of `N` items.
+    pub fn as_array<const N: usize>(&self) -> Result<&[Value; N]> {
+        let values = self.as_list()?;
+        values.try_into().map_err(|_| {
+            SceneError::InvalidValue(format!(
+                "expected a list of {N} values, found {}",
+                values.len()
+            ))
+        })
+    }
+
+    /// # Errors
+    ///
+    /// Returns an error if the value is not a map containing `name`.
+    pub fn field(&self, name: &str) -> Result<&Value> {
+        match self {
+            Self::Map(fields) => fields
+                .iter()
+                .find(|(field, _)| field == name)
+                .map(|(_, value)| value)
+                .ok_or_else(|| SceneError::InvalidValue(format!("missing field '{name}'"))),
+            other => Err(other.unexpected("a map")),
+        }
+    }
+
+    /// # Errors
+    ///
+    /// Returns an error if the value is not an enum variant.
+    pub fn as_variant(&self) -> Result<(&str, &[Value])> {
+        match self {
+            Self::Variant(name, fields) => Ok((name, fields)),
+            other => Err(other.unexpected("an enum variant")),
+        }
+    }
+
+    /// Resolves an entity reference through the IDs of the scene being loaded.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the value is not a reference to an entity of the scene.
+    pub fn as_entity(&self, entities: &EntityMap) -> Result<Entity> {
+        match self {
+            Self::Entity(scene_id) => entities
+                .entity(*scene_id)
+                .ok_or(SceneError::UnknownEntity(*scene_id)),
+            other => Err(other.unexpected("an entity reference")),
+        }
+    }
+
+    fn kind(&self) -> &'static str {
+        match self {
+            Self::Bool(_) => "a boolean",
+            Self::Number(_) => "a number",
+            Self::String(_) => "a string",
+            Self::Entity(_) => "an entity reference",
+            Self::List(_) => "a list",
+            Self::Map(_) => "a map",
+            Self::Variant(..) => "an enum variant",
+        }
+    }
+
+    fn unexpected(&self, expected: &str) -> SceneError {
+        SceneError::InvalidValue(format!("expected {expected}, found {}", self.kind()))
+    }
+}
+
+/// Writes the value in scene file syntax.
+impl Display for Value {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut text = String::new();
+        write_value(&mut text, self);
+        formatter.write_str(&text)
+    }
+}
+
+/// Parses one value in scene file syntax.
+impl FromStr for Value {
+    type Err = SceneError;
+
+    fn from_str(text: &str) -> Result<Self> {
+        parse_value(text)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Value;
+
+    #[test]
+    fn test_value_number_round_trip() {
+        for number in [0.1_f32, -3.5, 1e-8, f32::MAX, f32::MIN_POSITIVE] {
+            assert_eq!(Value::number(number).as_number::<f32>().unwrap(), number);
+        }
+        assert!(Value::number(300).as_number::<u8>().is_err());
+        assert!(Value::Bool(true).as_number::<u8>().is_err());
+    }
+
+    #[test]
+    fn test_value_accessors() {
+        let value = Value::map([
+            ("name", Value::String("player".to_string())),
+            (
+                "position",
+                Value::List(vec![Value::number(1), Value::number(2)]),
+            ),
+        ]);
+
+        assert_eq!(value.field("name").unwrap().as_str().unwrap(), "player");
+        let [x, y] = value.field("position").unwrap().as_array::<2>().unwrap();
+        assert_eq!(x.as_number::<i32>().unwrap(), 1);
+        assert_eq!(y.as_number::<i32>().unwrap(), 2);
+        assert!(value.field("missing").is_err());
+        assert!(value.field("position").unwrap().as_array::<3>().is_err());
+    }
+
+    #[test]
+    fn test_value_display_from_str() {
+        let value: Value = "Uniform({ r: 1, g: 2 }, [@3, \"text\"])".parse().unwrap();
+
+        assert_eq!(value.to_string(), "Uniform({ r: 1, g: 2 }, [@3, \"text\"])");
+    }
+}
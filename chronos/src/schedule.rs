@@ -0,0 +1,820 @@
+mod executor;
+pub mod system;
+
+use std::collections::{BTreeSet, HashMap};
+
+use crate::entity::EntityManager;
+use executor::Batch;
+pub use system::{IntoSystem, System, SystemAccess};
+
+pub type Result<T> = std::result::Result<T, ScheduleError>;
+
+#[derive(thiserror::Error, Debug, PartialEq, Eq)]
+pub enum ScheduleError {
+    #[error("System ordering cycle in stage {stage:?} between: {systems:?}")]
+    Cycle { stage: Stage, systems: Vec<String> },
+    #[error(
+        "System '{system}' is ordered against unknown system '{dependency}' in stage {stage:?}"
+    )]
+    UnknownDependency {
+        stage: Stage,
+        system: String,
+        dependency: String,
+    },
+    #[error("System '{0}' is registered more than once")]
+    DuplicateSystem(String),
+}
+
+/// Stages run in declaration order every frame, except `Startup`, which only
+/// runs on the first frame.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum Stage {
+    Startup,
+    PreUpdate,
+    Update,
+    PostUpdate,
+    Render,
+}
+
+/// A named system together with its ordering constraints inside its stage.
+pub struct SystemConfig {
+    name: String,
+    system: Box<dyn System>,
+    before: Vec<String>,
+    after: Vec<String>,
+    on_main_thread: bool,
+}
+
+/// Systems of a stage run in batches: systems inside a batch have compatible
+/// [`SystemAccess`] and run concurrently on up to `worker_count` threads.
+/// Exclusive systems, systems marked [`SystemConfig::on_main_thread`] and
+/// every system of [`Stage::Render`] run alone on the calling thread.
+/// Deferred changes such as [`Commands`](crate::entity::commands::Commands)
+/// are applied at the end of each stage, in system order.
+pub struct Schedule {
+    stages: HashMap<Stage, StageSystems>,
+    startup_done: bool,
+    worker_count: usize,
+}
+
+#[derive(Default)]
+struct StageSystems {
+    systems: Vec<SystemConfig>,
+    order: Vec<usize>,
+    batches: Vec<Batch>,
+    sorted: bool,
+}
+
+impl Stage {
+    pub const ALL: [Stage; 5] = [
+        Stage::Startup,
+        Stage::PreUpdate,
+        Stage::Update,
+        Stage::PostUpdate,
+        Stage::Render,
+    ];
+}
+
+impl SystemConfig {
+    #[must_use]
+    pub fn new<Marker>(name: &str, system: impl IntoSystem<Marker>) -> Self {
+        Self {
+            name: name.to_string(),
+            system: system.into_system(),
+            before: Vec::new(),
+            after: Vec::new(),
+            on_main_thread: false,
+        }
+    }
+
+    /// Runs this system before the system called `name` in the same stage.
+    #[must_use]
+    pub fn before(mut self, name: &str) -> Self {
+        self.before.push(name.to_string());
+        self
+    }
+
+    /// Runs this system after the system called `name` in the same stage.
+    #[must_use]
+    pub fn after(mut self, name: &str) -> Self {
+        self.after.push(name.to_string());
+        self
+    }
+
+    /// Never runs this system in parallel with others, e.g. because it
+    /// touches the renderer or other thread-bound state.
+    #[must_use]
+    pub fn on_main_thread(mut self) -> Self {
+        self.on_main_thread = true;
+        self
+    }
+
+    #[must_use]
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    #[must_use]
+    pub fn access(&self) -> SystemAccess {
+        self.system.access()
+    }
+}
+
+impl Default for Schedule {
+    fn default() -> Self {
+        Self {
+            stages: HashMap::new(),
+            startup_done: false,
+            worker_count: std::thread::available_parallelism().map_or(1, usize::from),
+        }
+    }
+}
+
+impl Schedule {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Limits the number of threads a batch may use. `1` runs every system serially.
+    pub fn set_worker_count(&mut self, worker_count: usize) {
+        self.worker_count = worker_count.max(1);
+    }
+
+    #[must_use]
+    pub fn worker_count(&self) -> usize {
+        self.worker_count
+    }
+
+    pub fn add_system(&mut self, stage: Stage, system: SystemConfig) {
+        let stage_systems = self.stages.entry(stage).or_default();
+        stage_systems.systems.push(system);
+        stage_systems.sorted = false;
+    }
+
+    /// Resolves the ordering constraints of every stage.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a system name is registered twice, a constraint
+    /// names a system missing from the stage, or the constraints form a cycle.
+    pub fn build(&mut self) -> Result<()> {
+        let mut names = BTreeSet::new();
+        for stage in Stage::ALL {
+            if let Some(stage_systems) = self.stages.get(&stage) {
+                for system in &stage_systems.systems {
+                    if !names.insert(system.name.as_str()) {
+                        return Err(ScheduleError::DuplicateSystem(system.name.clone()));
+                    }
+                }
+            }
+        }
+
+        for (stage, stage_systems) in &mut self.stages {
+            if !stage_systems.sorted {
+                let (order, predecessors) = sort_systems(*stage, &stage_systems.systems)?;
+                let accesses: Vec<SystemAccess> = stage_systems
+                    .systems
+                    .iter()
+                    .map(SystemConfig::access)
+                    .collect();
+                let serial: Vec<bool> = stage_systems
+                    .systems
+                    .iter()
+                    .map(|system| system.on_main_thread || *stage == Stage::Render)
+                    .collect();
+                stage_systems.batches =
+                    executor::build_batches(&order, &predecessors, &accesses, &serial);
+                stage_systems.order = order;
+                stage_systems.sorted = true;
+            }
+        }
+        Ok(())
+    }
+
+    /// Runs one frame: the startup stage on the first call, then every other
+    /// stage in order.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the system ordering cannot be resolved, see [`Schedule::build`].
+    pub fn run(&mut self, entity_manager: &mut EntityManager) -> Result<()> {
+        self.build()?;
+
+        for stage in Stage::ALL {
+            if stage == Stage::Startup {
+                if self.startup_done {
+                    continue;
+                }
+                self.startup_done = true;
+            }
+
+            if let Some(stage_systems) = self.stages.get_mut(&stage) {
+                for batch in &stage_systems.batches {
+                    executor::run_batch(
+                        batch,
+                        &mut stage_systems.systems,
+                        entity_manager,
+                        self.worker_count,
+                    );
+                }
+                for &index in &stage_systems.order {
+                    stage_systems.systems[index]
+                        .system
+                        .apply_deferred(entity_manager);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    #[must_use]
+    pub fn system_names(&self, stage: Stage) -> Vec<&str> {
+        self.stages
+            .get(&stage)
+            .map(|stage_systems| {
+                let order: Vec<usize> = if stage_systems.sorted {
+                    stage_systems.order.clone()
+                } else {
+                    (0..stage_systems.systems.len()).collect()
+                };
+                order
+                    .into_iter()
+                    .map(|index| stage_systems.systems[index].name())
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Names of the systems of `stage`, grouped by the batches they run in.
+    /// Empty until the schedule is built.
+    #[must_use]
+    pub fn batches(&self, stage: Stage) -> Vec<Vec<&str>> {
+        self.stages
+            .get(&stage)
+            .map(|stage_systems| {
+                stage_systems
+                    .batches
+                    .iter()
+                    .map(|batch| {
+                        batch
+                            .systems
+                            .iter()
+                            .map(|&index| stage_systems.systems[index].name())
+                            .collect()
+                    })
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+}
+
+/// Topologically sorts the systems of one stage. Systems without constraints
+/// between them keep their registration order. Also returns the direct
+/// predecessors of every system.
+fn sort_systems(stage: Stage, systems: &[SystemConfig]) -> Result<(Vec<usize>, Vec<Vec<usize>>)> {
+    let index_of: HashMap<&str, usize> = systems
+        .iter()
+        .enumerate()
+        .map(|(index, system)| (system.name.as_str(), index))
+        .collect();
+
+    let mut successors = vec![Vec::new(); systems.len()];
+    let mut predecessors = vec![Vec::new(); systems.len()];
+    let mut in_degree = vec![0_usize; systems.len()];
+    for (index, system) in systems.iter().enumerate() {
+        let edges = system
+            .before
+            .iter()
+            .map(|name| (name, true))
+            .chain(system.after.iter().map(|name| (name, false)));
+
+        for (name, is_before) in edges {
+            let Some(&other) = index_of.get(name.as_str()) else {
+                return Err(ScheduleError::UnknownDependency {
+                    stage,
+                    system: system.name.clone(),
+                    dependency: name.clone(),
+                });
+            };
+            let (from, to) = if is_before {
+                (index, other)
+            } else {
+                (other, index)
+            };
+            successors[from].push(to);
+            predecessors[to].push(from);
+            in_degree[to] += 1;
+        }
+    }
+
+    let mut ready: BTreeSet<usize> = (0..systems.len())
+        .filter(|index| in_degree[*index] == 0)
+        .collect();
+    let mut order = Vec::with_capacity(systems.len());
+    while let Some(index) = ready.pop_first() {
+        order.push(index);
+        for &next in &successors[index] {
+            in_degree[next] -= 1;
+            if in_degree[next] == 0 {
+                ready.insert(next);
+            }
+        }
+    }
+
+    if order.len() < systems.len() {
+        let systems = (0..systems.len())
+            .filter(|index| in_degree[*index] > 0)
+            .map(|index| systems[index].name.clone())
+            .collect();
+        return Err(ScheduleError::Cycle { stage, systems });
+    }
+    Ok((order, predecessors))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::{
+            Arc, Mutex,
+            atomic::{AtomicUsize, Ordering},
+        },
+        time::{Duration, Instant},
+    };
+
+    use super::{Schedule, ScheduleError, Stage, SystemAccess, SystemConfig};
+    use crate::entity::{
+        Entity, EntityManager,
+        commands::Commands,
+        events::{EventReader, EventWriter},
+        query::Query,
+        resources::{Res, ResMut},
+    };
+
+    type Log = Arc<Mutex<Vec<&'static str>>>;
+
+    fn recorder(log: &Log, name: &'static str) -> SystemConfig {
+        let log = Arc::clone(log);
+        SystemConfig::new(name, move |_: &mut EntityManager| {
+            log.lock().unwrap().push(name);
+        })
+    }
+
+    struct Position(f32);
+    struct Velocity(f32);
+
+    fn read_position(_: Query<&Position>) {}
+    fn read_velocity(_: Query<&Velocity>) {}
+    fn write_position(_: Query<&mut Position>) {}
+    fn write_velocity(_: Query<(&Position, &mut Velocity)>) {}
+
+    #[test]
+    fn test_schedule_runs_stages_in_order() {
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let mut schedule = Schedule::new();
+        schedule.add_system(Stage::Render, recorder(&log, "render"));
+        schedule.add_system(Stage::Update, recorder(&log, "update"));
+        schedule.add_system(Stage::Startup, recorder(&log, "startup"));
+        schedule.add_system(Stage::PostUpdate, recorder(&log, "post_update"));
+        schedule.add_system(Stage::PreUpdate, recorder(&log, "pre_update"));
+
+        let mut entity_manager = EntityManager::default();
+        schedule.run(&mut entity_manager).unwrap();
+        schedule.run(&mut entity_manager).unwrap();
+
+        assert_eq!(
+            *log.lock().unwrap(),
+            vec![
+                "startup",
+                "pre_update",
+                "update",
+                "post_update",
+                "render",
+                "pre_update",
+                "update",
+                "post_update",
+                "render",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_schedule_before_after() {
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let mut schedule = Schedule::new();
+        schedule.add_system(Stage::Update, recorder(&log, "physics").after("input"));
+        schedule.add_system(Stage::Update, recorder(&log, "camera"));
+        schedule.add_system(Stage::Update, recorder(&log, "input").before("camera"));
+
+        schedule.run(&mut EntityManager::default()).unwrap();
+
+        assert_eq!(*log.lock().unwrap(), vec!["input", "physics", "camera"]);
+        assert_eq!(
+            schedule.system_names(Stage::Update),
+            vec!["input", "physics", "camera"]
+        );
+    }
+
+    #[test]
+    fn test_schedule_detects_cycle() {
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let mut schedule = Schedule::new();
+        schedule.add_system(Stage::Update, recorder(&log, "a").before("b"));
+        schedule.add_system(Stage::Update, recorder(&log, "b").before("c"));
+        schedule.add_system(Stage::Update, recorder(&log, "c").before("a"));
+        schedule.add_system(Stage::Update, recorder(&log, "d"));
+
+        let result = schedule.run(&mut EntityManager::default());
+
+        assert_eq!(
+            result,
+            Err(ScheduleError::Cycle {
+                stage: Stage::Update,
+                systems: vec!["a".to_string(), "b".to_string(), "c".to_string()],
+            })
+        );
+        assert!(log.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_schedule_unknown_dependency() {
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let mut schedule = Schedule::new();
+        schedule.add_system(Stage::Update, recorder(&log, "a").after("missing"));
+
+        assert_eq!(
+            schedule.build(),
+            Err(ScheduleError::UnknownDependency {
+                stage: Stage::Update,
+                system: "a".to_string(),
+                dependency: "missing".to_string(),
+            })
+        );
+    }
+
+    #[test]
+    fn test_schedule_duplicate_system() {
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let mut schedule = Schedule::new();
+        schedule.add_system(Stage::Update, recorder(&log, "a"));
+        schedule.add_system(Stage::Render, recorder(&log, "a"));
+
+        assert_eq!(
+            schedule.build(),
+            Err(ScheduleError::DuplicateSystem("a".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_schedule_systems_modify_entities() {
+        let mut schedule = Schedule::new();
+        schedule.add_system(
+            Stage::Startup,
+            SystemConfig::new("spawn", |entity_manager: &mut EntityManager| {
+                entity_manager.create_entity((0_u32,));
+            }),
+        );
+        schedule.add_system(
+            Stage::Update,
+            SystemConfig::new("count", |entity_manager: &mut EntityManager| {
+                for counter in entity_manager.query::<&mut u32>() {
+                    *counter += 1;
+                }
+            }),
+        );
+
+        let mut entity_manager = EntityManager::default();
+        schedule.run(&mut entity_manager).unwrap();
+        schedule.run(&mut entity_manager).unwrap();
+
+        let counters: Vec<u32> = entity_manager.query::<&u32>().copied().collect();
+        assert_eq!(counters, vec![2]);
+    }
+
+    #[test]
+    fn test_function_system_queries() {
+        fn movement(mut query: Query<(&mut Position, &Velocity)>) {
+            for (position, velocity) in &mut query {
+                position.0 += velocity.0;
+            }
+        }
+
+        let mut schedule = Schedule::new();
+        schedule.add_system(Stage::Update, SystemConfig::new("movement", movement));
+
+        let mut entity_manager = EntityManager::default();
+        let entity = entity_manager.create_entity((Position(1.0), Velocity(2.0)));
+        schedule.run(&mut entity_manager).unwrap();
+        schedule.run(&mut entity_manager).unwrap();
+
+        assert_eq!(
+            entity_manager.get_component::<Position>(entity).unwrap().0,
+            5.0
+        );
+    }
+
+    #[test]
+    fn test_function_system_access_is_inferred() {
+        let access = SystemConfig::new("movement", write_velocity).access();
+
+        assert!(!access.is_exclusive());
+        assert_eq!(
+            access.reads().collect::<Vec<_>>(),
+            vec![std::any::TypeId::of::<Position>()]
+        );
+        assert_eq!(
+            access.writes().collect::<Vec<_>>(),
+            vec![std::any::TypeId::of::<Velocity>()]
+        );
+        assert!(
+            SystemConfig::new("exclusive", |_: &mut EntityManager| {})
+                .access()
+                .is_exclusive()
+        );
+    }
+
+    #[test]
+    fn test_system_access_compatibility() {
+        let read = SystemAccess::default().read::<Position>();
+        let write = SystemAccess::default().write::<Position>();
+        let other = SystemAccess::default().write::<Velocity>();
+
+        assert!(read.is_compatible(&read.clone()));
+        assert!(!read.is_compatible(&write));
+        assert!(!write.is_compatible(&read));
+        assert!(write.is_compatible(&other));
+        assert!(!read.is_compatible(&SystemAccess::exclusive()));
+    }
+
+    #[test]
+    fn test_schedule_batches() {
+        let mut schedule = Schedule::new();
+        schedule.add_system(Stage::Update, SystemConfig::new("read_a", read_position));
+        schedule.add_system(Stage::Update, SystemConfig::new("read_b", read_position));
+        schedule.add_system(Stage::Update, SystemConfig::new("velocity", read_velocity));
+        schedule.add_system(Stage::Update, SystemConfig::new("write", write_position));
+        schedule.add_system(
+            Stage::Update,
+            SystemConfig::new("after_write", read_velocity).after("write"),
+        );
+        schedule.add_system(
+            Stage::Update,
+            SystemConfig::new("exclusive", |_: &mut EntityManager| {}),
+        );
+        schedule.add_system(
+            Stage::Update,
+            SystemConfig::new("main_thread", read_velocity).on_main_thread(),
+        );
+        schedule.add_system(Stage::Render, SystemConfig::new("render_a", read_position));
+        schedule.add_system(Stage::Render, SystemConfig::new("render_b", read_velocity));
+        schedule.build().unwrap();
+
+        assert_eq!(
+            schedule.batches(Stage::Update),
+            vec![
+                vec!["read_a", "read_b", "velocity"],
+                vec!["write"],
+                vec!["after_write"],
+                vec!["exclusive"],
+                vec!["main_thread"],
+            ]
+        );
+        assert_eq!(
+            schedule.batches(Stage::Render),
+            vec![vec!["render_a"], vec!["render_b"]]
+        );
+    }
+
+    #[test]
+    fn test_schedule_batches_filter_access() {
+        use crate::entity::query::{Changed, With, Without};
+
+        fn changed_position(_: Query<Entity, Changed<Position>>) {}
+        fn with_position(_: Query<&Velocity, With<Position>>) {}
+        fn without_position(_: Query<&Velocity, Without<Position>>) {}
+
+        let mut schedule = Schedule::new();
+        schedule.add_system(
+            Stage::Update,
+            SystemConfig::new("changed", changed_position),
+        );
+        schedule.add_system(Stage::Update, SystemConfig::new("write", write_position));
+        schedule.add_system(Stage::Update, SystemConfig::new("with", with_position));
+        schedule.add_system(
+            Stage::Update,
+            SystemConfig::new("without", without_position),
+        );
+        schedule.build().unwrap();
+
+        assert_eq!(
+            schedule.batches(Stage::Update),
+            vec![vec!["changed"], vec!["write"], vec!["with", "without"]]
+        );
+    }
+
+    #[test]
+    fn test_schedule_runs_batch_in_parallel() {
+        // Each system waits for the other, so this only finishes if both run at once.
+        fn rendezvous(counter: &Arc<AtomicUsize>) -> impl FnMut(Query<&Position>) + Send + 'static {
+            let counter = Arc::clone(counter);
+            move |_| {
+                counter.fetch_add(1, Ordering::SeqCst);
+                let start = Instant::now();
+                while counter.load(Ordering::SeqCst) < 2 {
+                    assert!(
+                        start.elapsed() < Duration::from_secs(10),
+                        "systems ran serially"
+                    );
+                    std::thread::yield_now();
+                }
+            }
+        }
+
+        let counter = Arc::new(AtomicUsize::new(0));
+        let mut schedule = Schedule::new();
+        schedule.set_worker_count(2);
+        schedule.add_system(Stage::Update, SystemConfig::new("a", rendezvous(&counter)));
+        schedule.add_system(Stage::Update, SystemConfig::new("b", rendezvous(&counter)));
+
+        schedule.run(&mut EntityManager::default()).unwrap();
+
+        assert_eq!(counter.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn test_schedule_changed_since_last_run() {
+        use crate::entity::query::Changed;
+
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let seen_in_system = Arc::clone(&seen);
+        let mut schedule = Schedule::new();
+        schedule.add_system(
+            Stage::Update,
+            SystemConfig::new(
+                "changed",
+                move |query: Query<&Position, Changed<Position>>| {
+                    seen_in_system.lock().unwrap().push(query.iter().count());
+                },
+            ),
+        );
+
+        let mut entity_manager = EntityManager::default();
+        let entity = entity_manager.create_entity((Position(0.0),));
+        schedule.run(&mut entity_manager).unwrap();
+        entity_manager.advance_tick();
+        schedule.run(&mut entity_manager).unwrap();
+        entity_manager.advance_tick();
+        entity_manager
+            .get_component_mut::<Position>(entity)
+            .unwrap()
+            .0 = 1.0;
+        schedule.run(&mut entity_manager).unwrap();
+
+        assert_eq!(*seen.lock().unwrap(), vec![1, 0, 1]);
+    }
+
+    #[test]
+    fn test_function_system_resources() {
+        struct DeltaTime(f32);
+        struct Elapsed(f32);
+
+        fn tick(delta_time: Res<DeltaTime>, mut elapsed: ResMut<Elapsed>) {
+            elapsed.0 += delta_time.0;
+        }
+
+        let mut schedule = Schedule::new();
+        schedule.add_system(Stage::Update, SystemConfig::new("tick", tick));
+        let mut entity_manager = EntityManager::default();
+        entity_manager.insert_resource(DeltaTime(0.25));
+        entity_manager.insert_resource(Elapsed(0.0));
+
+        schedule.run(&mut entity_manager).unwrap();
+        schedule.run(&mut entity_manager).unwrap();
+
+        assert_eq!(entity_manager.resource::<Elapsed>().unwrap().0, 0.5);
+    }
+
+    #[test]
+    fn test_schedule_batches_resource_access() {
+        fn read_time(_: Res<u64>) {}
+        fn write_time(_: ResMut<u64>) {}
+        fn maybe_read_time(_: Option<Res<u64>>) {}
+
+        let mut schedule = Schedule::new();
+        schedule.add_system(Stage::Update, SystemConfig::new("read_a", read_time));
+        schedule.add_system(Stage::Update, SystemConfig::new("read_b", maybe_read_time));
+        schedule.add_system(Stage::Update, SystemConfig::new("write", write_time));
+        schedule.add_system(Stage::Update, SystemConfig::new("query", write_position));
+        schedule.build().unwrap();
+
+        assert_eq!(
+            schedule.batches(Stage::Update),
+            vec![vec!["read_a", "read_b"], vec!["write", "query"]]
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "does not exist")]
+    fn test_function_system_missing_resource() {
+        let mut schedule = Schedule::new();
+        schedule.add_system(
+            Stage::Update,
+            SystemConfig::new("missing", |_: Res<u64>| {}),
+        );
+
+        schedule.run(&mut EntityManager::default()).unwrap();
+    }
+
+    #[test]
+    fn test_function_system_optional_resource() {
+        let mut schedule = Schedule::new();
+        schedule.add_system(
+            Stage::Update,
+            SystemConfig::new("optional", |value: Option<ResMut<u64>>| {
+                assert!(value.is_none());
+            }),
+        );
+
+        schedule.run(&mut EntityManager::default()).unwrap();
+    }
+
+    #[test]
+    fn test_function_system_commands_apply_at_stage_end() {
+        fn split(mut commands: Commands, query: Query<(Entity, &Position)>) {
+            for (entity, position) in &query {
+                commands.remove_entity(entity);
+                let child = commands.create_entity((Position(position.0 / 2.0),));
+                commands.add_component(child, Velocity(1.0));
+            }
+        }
+
+        fn count(counts: &Arc<Mutex<Vec<usize>>>) -> impl FnMut(Query<&Position>) + Send + 'static {
+            let counts = Arc::clone(counts);
+            move |query| counts.lock().unwrap().push(query.iter().count())
+        }
+
+        let counts = Arc::new(Mutex::new(Vec::new()));
+        let mut schedule = Schedule::new();
+        schedule.add_system(Stage::Update, SystemConfig::new("split", split));
+        schedule.add_system(
+            Stage::Update,
+            SystemConfig::new("count_update", count(&counts)).after("split"),
+        );
+        schedule.add_system(
+            Stage::PostUpdate,
+            SystemConfig::new("count_post", count(&counts)),
+        );
+        let mut entity_manager = EntityManager::default();
+        let original = entity_manager.create_entity((Position(4.0),));
+
+        schedule.run(&mut entity_manager).unwrap();
+
+        assert_eq!(*counts.lock().unwrap(), vec![1, 1]);
+        assert!(!entity_manager.entity_exists(original));
+        let halves: Vec<f32> = entity_manager
+            .query::<(&Position, &Velocity)>()
+            .map(|(position, _)| position.0)
+            .collect();
+        assert_eq!(halves, vec![2.0]);
+    }
+
+    #[test]
+    fn test_function_system_events() {
+        #[derive(Clone, Copy, Debug, PartialEq)]
+        struct Collision(u32);
+
+        fn collide(mut writer: EventWriter<Collision>, mut frame: ResMut<u32>) {
+            *frame += 1;
+            writer.send(Collision(*frame));
+        }
+
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let received_in_system = Arc::clone(&received);
+        let mut schedule = Schedule::new();
+        // The reader runs first, so it only sees the previous frame's events.
+        schedule.add_system(
+            Stage::Update,
+            SystemConfig::new("read", move |mut reader: EventReader<Collision>| {
+                received_in_system
+                    .lock()
+                    .unwrap()
+                    .push(reader.read().copied().collect::<Vec<_>>());
+            })
+            .before("collide"),
+        );
+        schedule.add_system(Stage::Update, SystemConfig::new("collide", collide));
+        let mut entity_manager = EntityManager::default();
+        entity_manager.add_event::<Collision>();
+        entity_manager.insert_resource(0_u32);
+
+        for _ in 0..3 {
+            schedule.run(&mut entity_manager).unwrap();
+            entity_manager.update_events();
+        }
+
+        assert_eq!(
+            *received.lock().unwrap(),
+            vec![vec![], vec![Collision(1)], vec![Collision(2)]]
+        );
+    }
+}
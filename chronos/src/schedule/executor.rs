@@ -0,0 +1,88 @@
+use std::sync::Mutex;
+
+use super::{
+    SystemConfig,
+    system::{System, SystemAccess, WorldCell},
+};
+use crate::entity::EntityManager;
+
+/// Systems that can run together. A serial batch always holds a single system
+/// that runs on the thread calling [`Schedule::run`](super::Schedule::run).
+#[derive(Debug, PartialEq, Eq)]
+pub(super) struct Batch {
+    pub(super) systems: Vec<usize>,
+    pub(super) parallel: bool,
+}
+
+/// Groups the sorted systems of a stage into consecutive batches. A system
+/// joins the current batch if its access is compatible with every system in
+/// it and none of its ordering predecessors is part of it.
+pub(super) fn build_batches(
+    order: &[usize],
+    predecessors: &[Vec<usize>],
+    accesses: &[SystemAccess],
+    serial: &[bool],
+) -> Vec<Batch> {
+    let mut batches: Vec<Batch> = Vec::new();
+    for &index in order {
+        let parallel = !serial[index] && !accesses[index].is_exclusive();
+        if let Some(batch) = batches.last_mut() {
+            let fits = parallel
+                && batch.parallel
+                && batch.systems.iter().all(|&other| {
+                    accesses[index].is_compatible(&accesses[other])
+                        && !predecessors[index].contains(&other)
+                });
+            if fits {
+                batch.systems.push(index);
+                continue;
+            }
+        }
+        batches.push(Batch {
+            systems: vec![index],
+            parallel,
+        });
+    }
+    batches
+}
+
+/// Runs one batch, spreading parallel batches over up to `worker_count` threads.
+pub(super) fn run_batch(
+    batch: &Batch,
+    systems: &mut [SystemConfig],
+    entity_manager: &mut EntityManager,
+    worker_count: usize,
+) {
+    if !batch.parallel || batch.systems.len() == 1 || worker_count <= 1 {
+        for &index in &batch.systems {
+            systems[index].system.run(entity_manager);
+        }
+        return;
+    }
+
+    let queue: Vec<&mut Box<dyn System>> = systems
+        .iter_mut()
+        .enumerate()
+        .filter(|(index, _)| batch.systems.contains(index))
+        .map(|(_, config)| &mut config.system)
+        .rev()
+        .collect();
+    let worker_count = worker_count.min(queue.len());
+    let queue = Mutex::new(queue);
+    let world = WorldCell::new(entity_manager);
+
+    std::thread::scope(|scope| {
+        for _ in 0..worker_count {
+            scope.spawn(|| {
+                loop {
+                    let next = queue.lock().unwrap().pop();
+                    let Some(system) = next else {
+                        break;
+                    };
+                    // SAFETY: the systems of a batch have pairwise compatible access.
+                    unsafe { system.run_unsafe(world) };
+                }
+            });
+        }
+    });
+}
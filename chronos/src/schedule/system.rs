@@ -0,0 +1,509 @@
+use std::{
+    any::{TypeId, type_name},
+    marker::PhantomData,
+};
+
+use crate::entity::{
+    EntityManager, Tick,
+    commands::{CommandQueue, Commands},
+    events::{EventCursor, EventReader, EventWriter, Events},
+    query::{ComponentAccess, Query, QueryData, QueryFilter, TickRange},
+    resources::{Res, ResMut, missing_resource},
+};
+
+/// Unit of work run by the [`Schedule`](super::Schedule).
+///
+/// Plain closures taking `&mut EntityManager` are systems with exclusive
+/// access. Functions whose parameters are all [`SystemParam`]s become systems
+/// through [`IntoSystem`] and have their access inferred from those parameters.
+pub trait System: Send + 'static {
+    /// Data this system touches, used to decide which systems may run in parallel.
+    fn access(&self) -> SystemAccess;
+
+    /// # Safety
+    ///
+    /// No system with conflicting [`SystemAccess`] may run at the same time,
+    /// and nothing else may touch `world` while an exclusive system runs.
+    unsafe fn run_unsafe(&mut self, world: WorldCell<'_>);
+
+    fn run(&mut self, entity_manager: &mut EntityManager) {
+        // SAFETY: `&mut EntityManager` guarantees exclusive access.
+        unsafe { self.run_unsafe(WorldCell::new(entity_manager)) }
+    }
+
+    /// Applies changes deferred by earlier runs, such as recorded [`Commands`].
+    /// Called by the schedule at the end of the system's stage.
+    fn apply_deferred(&mut self, _entity_manager: &mut EntityManager) {}
+}
+
+/// Component and resource types a system reads and writes, or `exclusive`
+/// if it needs the whole world.
+#[derive(Default, Debug, Clone)]
+pub struct SystemAccess {
+    components: ComponentAccess,
+    resources: ComponentAccess,
+    exclusive: bool,
+}
+
+/// Pointer to the world shared by the systems of one batch. Systems only
+/// dereference the parts their [`SystemAccess`] declares.
+#[derive(Clone, Copy)]
+pub struct WorldCell<'w> {
+    entity_manager: *mut EntityManager,
+    marker: PhantomData<&'w mut EntityManager>,
+}
+
+// SAFETY: access through the cell is coordinated by the executor, which only
+// runs systems with compatible `SystemAccess` together.
+unsafe impl Send for WorldCell<'_> {}
+unsafe impl Sync for WorldCell<'_> {}
+
+/// A value a system function can take as a parameter, e.g. [`Query`].
+///
+/// # Safety
+///
+/// `access` must register everything `get_param` touches.
+pub unsafe trait SystemParam: Sized {
+    /// Data kept by the system between runs.
+    type State: Default + Send + 'static;
+    type Item<'w>: SystemParam<State = Self::State>;
+
+    fn access(access: &mut SystemAccess);
+
+    /// # Safety
+    ///
+    /// The caller must hold the access registered by [`SystemParam::access`] for `'w`.
+    unsafe fn get_param<'w>(
+        state: &'w mut Self::State,
+        world: WorldCell<'w>,
+        ticks: TickRange,
+    ) -> Self::Item<'w>;
+
+    /// Applies the changes recorded in `state`, see [`System::apply_deferred`].
+    fn apply(_state: &mut Self::State, _entity_manager: &mut EntityManager) {}
+}
+
+pub type SystemParamItem<'w, P> = <P as SystemParam>::Item<'w>;
+
+/// Function whose parameters are all [`SystemParam`]s.
+pub trait SystemParamFunction<Marker>: Send + 'static {
+    type Param: SystemParam;
+
+    fn run(&mut self, param: SystemParamItem<'_, Self::Param>);
+}
+
+/// Conversion into a boxed [`System`], implemented for systems and for
+/// functions of [`SystemParam`]s.
+pub trait IntoSystem<Marker> {
+    fn into_system(self) -> Box<dyn System>;
+}
+
+#[doc(hidden)]
+pub struct FunctionMarker;
+
+/// [`System`] built from a [`SystemParamFunction`].
+pub struct FunctionSystem<F: SystemParamFunction<Marker>, Marker> {
+    func: F,
+    state: <F::Param as SystemParam>::State,
+    access: SystemAccess,
+    last_run: Tick,
+    marker: PhantomData<fn() -> Marker>,
+}
+
+impl SystemAccess {
+    /// Access to the whole world. Exclusive systems never share a batch.
+    #[must_use]
+    pub fn exclusive() -> Self {
+        Self {
+            exclusive: true,
+            ..Self::default()
+        }
+    }
+
+    #[must_use]
+    pub fn read<T: 'static>(mut self) -> Self {
+        self.components.add_read::<T>();
+        self
+    }
+
+    #[must_use]
+    pub fn write<T: 'static>(mut self) -> Self {
+        self.components.add_write::<T>();
+        self
+    }
+
+    #[must_use]
+    pub fn read_resource<T: 'static>(mut self) -> Self {
+        self.add_resource_read::<T>();
+        self
+    }
+
+    #[must_use]
+    pub fn write_resource<T: 'static>(mut self) -> Self {
+        self.add_resource_write::<T>();
+        self
+    }
+
+    /// # Panics
+    ///
+    /// Panics if the resource `T` is already written by the same system.
+    pub fn add_resource_read<T: 'static>(&mut self) {
+        assert!(
+            !self.resources.writes().contains(&TypeId::of::<T>()),
+            "System reads resource {} while also writing it",
+            type_name::<T>()
+        );
+        self.resources.add_read::<T>();
+    }
+
+    /// # Panics
+    ///
+    /// Panics if the resource `T` is already accessed by the same system.
+    pub fn add_resource_write<T: 'static>(&mut self) {
+        let type_id = TypeId::of::<T>();
+        assert!(
+            !self.resources.reads().contains(&type_id)
+                && !self.resources.writes().contains(&type_id),
+            "System writes resource {} while also accessing it elsewhere",
+            type_name::<T>()
+        );
+        self.resources.add_write::<T>();
+    }
+
+    /// Adds the accesses of one system parameter.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `components` conflicts with the access already registered.
+    pub fn add_components(&mut self, components: &ComponentAccess) {
+        self.components.extend(components);
+    }
+
+    #[must_use]
+    pub fn is_exclusive(&self) -> bool {
+        self.exclusive
+    }
+
+    pub fn reads(&self) -> impl Iterator<Item = TypeId> + '_ {
+        self.components.reads().iter().copied()
+    }
+
+    pub fn writes(&self) -> impl Iterator<Item = TypeId> + '_ {
+        self.components.writes().iter().copied()
+    }
+
+    pub fn resource_reads(&self) -> impl Iterator<Item = TypeId> + '_ {
+        self.resources.reads().iter().copied()
+    }
+
+    pub fn resource_writes(&self) -> impl Iterator<Item = TypeId> + '_ {
+        self.resources.writes().iter().copied()
+    }
+
+    /// Whether systems with these accesses may run at the same time.
+    #[must_use]
+    pub fn is_compatible(&self, other: &SystemAccess) -> bool {
+        !self.exclusive
+            && !other.exclusive
+            && self.components.is_compatible(&other.components)
+            && self.resources.is_compatible(&other.resources)
+    }
+}
+
+impl<'w> WorldCell<'w> {
+    pub fn new(entity_manager: &'w mut EntityManager) -> Self {
+        Self {
+            entity_manager,
+            marker: PhantomData,
+        }
+    }
+
+    /// # Safety
+    ///
+    /// Only the parts covered by the caller's [`SystemAccess`] may be accessed.
+    #[must_use]
+    pub unsafe fn entity_manager(self) -> &'w EntityManager {
+        // SAFETY: the pointer comes from a `&'w mut EntityManager`.
+        unsafe { &*self.entity_manager }
+    }
+
+    /// # Safety
+    ///
+    /// The caller must have exclusive access to the world.
+    #[must_use]
+    pub unsafe fn entity_manager_mut(self) -> &'w mut EntityManager {
+        // SAFETY: the pointer comes from a `&'w mut EntityManager`.
+        unsafe { &mut *self.entity_manager }
+    }
+}
+
+impl<F: FnMut(&mut EntityManager) + Send + 'static> System for F {
+    fn access(&self) -> SystemAccess {
+        SystemAccess::exclusive()
+    }
+
+    unsafe fn run_unsafe(&mut self, world: WorldCell<'_>) {
+        // SAFETY: the system is exclusive, so nothing else runs.
+        let entity_manager = unsafe { world.entity_manager_mut() };
+        entity_manager.increment_change_tick();
+        self(entity_manager);
+    }
+}
+
+impl<S: System> IntoSystem<()> for S {
+    fn into_system(self) -> Box<dyn System> {
+        Box::new(self)
+    }
+}
+
+impl<F: SystemParamFunction<Marker>, Marker: 'static> IntoSystem<(FunctionMarker, Marker)> for F {
+    fn into_system(self) -> Box<dyn System> {
+        let mut access = SystemAccess::default();
+        F::Param::access(&mut access);
+        Box::new(FunctionSystem {
+            func: self,
+            state: Default::default(),
+            access,
+            last_run: Tick::default(),
+            marker: PhantomData::<fn() -> Marker>,
+        })
+    }
+}
+
+impl<F: SystemParamFunction<Marker>, Marker: 'static> System for FunctionSystem<F, Marker> {
+    fn access(&self) -> SystemAccess {
+        self.access.clone()
+    }
+
+    unsafe fn run_unsafe(&mut self, world: WorldCell<'_>) {
+        // SAFETY: only the tick counter is touched, which is atomic.
+        let this_run = unsafe { world.entity_manager() }.increment_change_tick();
+        let ticks = TickRange::new(self.last_run, this_run);
+        // SAFETY: the caller guarantees the access registered for `F::Param`.
+        let param = unsafe { F::Param::get_param(&mut self.state, world, ticks) };
+        self.func.run(param);
+        self.last_run = this_run;
+    }
+
+    fn apply_deferred(&mut self, entity_manager: &mut EntityManager) {
+        F::Param::apply(&mut self.state, entity_manager);
+    }
+}
+
+unsafe impl SystemParam for Commands<'_> {
+    type State = CommandQueue;
+    type Item<'w> = Commands<'w>;
+
+    // Commands only reserve entity IDs, which is atomic, until they are applied.
+    fn access(_access: &mut SystemAccess) {}
+
+    unsafe fn get_param<'w>(
+        state: &'w mut Self::State,
+        world: WorldCell<'w>,
+        _ticks: TickRange,
+    ) -> Self::Item<'w> {
+        // SAFETY: `Commands` only calls `reserve_entity` through the shared reference.
+        Commands::new(state, unsafe { world.entity_manager() })
+    }
+
+    fn apply(state: &mut Self::State, entity_manager: &mut EntityManager) {
+        state.apply(entity_manager);
+    }
+}
+
+unsafe impl<Q: QueryData + 'static, F: QueryFilter + 'static> SystemParam for Query<'_, Q, F> {
+    type State = ();
+    type Item<'w> = Query<'w, Q, F>;
+
+    fn access(access: &mut SystemAccess) {
+        access.add_components(&Query::<Q, F>::component_access());
+    }
+
+    unsafe fn get_param<'w>(
+        _state: &'w mut Self::State,
+        world: WorldCell<'w>,
+        ticks: TickRange,
+    ) -> Self::Item<'w> {
+        // SAFETY: the caller holds the access of `Q` and `F`.
+        unsafe { Query::new(world.entity_manager(), ticks) }
+    }
+}
+
+unsafe impl<T: Send + Sync + 'static> SystemParam for Res<'_, T> {
+    type State = ();
+    type Item<'w> = Res<'w, T>;
+
+    fn access(access: &mut SystemAccess) {
+        access.add_resource_read::<T>();
+    }
+
+    unsafe fn get_param<'w>(
+        state: &'w mut Self::State,
+        world: WorldCell<'w>,
+        ticks: TickRange,
+    ) -> Self::Item<'w> {
+        // SAFETY: forwarded from the caller.
+        unsafe { Option::<Self>::get_param(state, world, ticks) }
+            .unwrap_or_else(|| missing_resource::<T>())
+    }
+}
+
+unsafe impl<T: Send + Sync + 'static> SystemParam for Option<Res<'_, T>> {
+    type State = ();
+    type Item<'w> = Option<Res<'w, T>>;
+
+    fn access(access: &mut SystemAccess) {
+        access.add_resource_read::<T>();
+    }
+
+    unsafe fn get_param<'w>(
+        _state: &'w mut Self::State,
+        world: WorldCell<'w>,
+        _ticks: TickRange,
+    ) -> Self::Item<'w> {
+        // SAFETY: the caller holds read access to `T`.
+        unsafe { world.entity_manager() }
+            .resources()
+            .get::<T>()
+            .map(Res::new)
+    }
+}
+
+unsafe impl<T: Send + Sync + 'static> SystemParam for ResMut<'_, T> {
+    type State = ();
+    type Item<'w> = ResMut<'w, T>;
+
+    fn access(access: &mut SystemAccess) {
+        access.add_resource_write::<T>();
+    }
+
+    unsafe fn get_param<'w>(
+        state: &'w mut Self::State,
+        world: WorldCell<'w>,
+        ticks: TickRange,
+    ) -> Self::Item<'w> {
+        // SAFETY: forwarded from the caller.
+        unsafe { Option::<Self>::get_param(state, world, ticks) }
+            .unwrap_or_else(|| missing_resource::<T>())
+    }
+}
+
+unsafe impl<T: Send + Sync + 'static> SystemParam for Option<ResMut<'_, T>> {
+    type State = ();
+    type Item<'w> = Option<ResMut<'w, T>>;
+
+    fn access(access: &mut SystemAccess) {
+        access.add_resource_write::<T>();
+    }
+
+    unsafe fn get_param<'w>(
+        _state: &'w mut Self::State,
+        world: WorldCell<'w>,
+        _ticks: TickRange,
+    ) -> Self::Item<'w> {
+        // SAFETY: the caller holds write access to `T`.
+        unsafe {
+            world
+                .entity_manager()
+                .resources()
+                .get_ptr::<T>()
+                .map(|value| ResMut::new(&mut *value))
+        }
+    }
+}
+
+unsafe impl<T: Send + Sync + 'static> SystemParam for EventWriter<'_, T> {
+    type State = ();
+    type Item<'w> = EventWriter<'w, T>;
+
+    fn access(access: &mut SystemAccess) {
+        access.add_resource_write::<Events<T>>();
+    }
+
+    unsafe fn get_param<'w>(
+        state: &'w mut Self::State,
+        world: WorldCell<'w>,
+        ticks: TickRange,
+    ) -> Self::Item<'w> {
+        // SAFETY: the caller holds write access to `Events<T>`.
+        let events = unsafe { ResMut::<Events<T>>::get_param(state, world, ticks) };
+        EventWriter::new(events.into_inner())
+    }
+}
+
+unsafe impl<T: Send + Sync + 'static> SystemParam for EventReader<'_, T> {
+    type State = EventCursor<T>;
+    type Item<'w> = EventReader<'w, T>;
+
+    fn access(access: &mut SystemAccess) {
+        access.add_resource_read::<Events<T>>();
+    }
+
+    unsafe fn get_param<'w>(
+        state: &'w mut Self::State,
+        world: WorldCell<'w>,
+        _ticks: TickRange,
+    ) -> Self::Item<'w> {
+        // SAFETY: the caller holds read access to `Events<T>`.
+        let events = unsafe { world.entity_manager() }
+            .resources()
+            .get::<Events<T>>()
+            .unwrap_or_else(|| missing_resource::<Events<T>>());
+        EventReader::new(state, events)
+    }
+}
+
+macro_rules! impl_system_param_for_tuple {
+    ($($P:ident),*) => {
+        #[allow(non_snake_case, unused_variables, clippy::unused_unit)]
+        unsafe impl<$($P: SystemParam),*> SystemParam for ($($P,)*) {
+            type State = ($($P::State,)*);
+            type Item<'w> = ($($P::Item<'w>,)*);
+
+            fn access(access: &mut SystemAccess) {
+                $($P::access(access);)*
+            }
+
+            unsafe fn get_param<'w>(
+                state: &'w mut Self::State,
+                world: WorldCell<'w>,
+                ticks: TickRange,
+            ) -> Self::Item<'w> {
+                let ($($P,)*) = state;
+                ($(unsafe { $P::get_param($P, world, ticks) },)*)
+            }
+
+            fn apply(state: &mut Self::State, entity_manager: &mut EntityManager) {
+                let ($($P,)*) = state;
+                $($P::apply($P, entity_manager);)*
+            }
+        }
+
+        #[allow(non_snake_case, clippy::too_many_arguments)]
+        impl<Func, $($P: SystemParam),*> SystemParamFunction<fn($($P,)*)> for Func
+        where
+            Func: Send + 'static,
+            for<'a> &'a mut Func: FnMut($($P),*) + FnMut($(SystemParamItem<'_, $P>),*),
+        {
+            type Param = ($($P,)*);
+
+            fn run(&mut self, param: SystemParamItem<'_, Self::Param>) {
+                fn call_inner<$($P),*>(mut func: impl FnMut($($P),*), $($P: $P),*) {
+                    func($($P),*);
+                }
+                let ($($P,)*) = param;
+                call_inner(self, $($P),*);
+            }
+        }
+    };
+}
+
+impl_system_param_for_tuple!();
+impl_system_param_for_tuple!(P1);
+impl_system_param_for_tuple!(P1, P2);
+impl_system_param_for_tuple!(P1, P2, P3);
+impl_system_param_for_tuple!(P1, P2, P3, P4);
+impl_system_param_for_tuple!(P1, P2, P3, P4, P5);
+impl_system_param_for_tuple!(P1, P2, P3, P4, P5, P6);
+impl_system_param_for_tuple!(P1, P2, P3, P4, P5, P6, P7);
+impl_system_param_for_tuple!(P1, P2, P3, P4, P5, P6, P7, P8);
@@ -23,7 +23,6 @@ fn get_window() -> &'static ChronosWindow {
 pub fn get_opengl_api() -> &'static OpenGL {
     OPENGL_API_INSTANCE.get_or_init(|| {
         let window = get_window();
-        let opengl = init_opengl(window).unwrap();
-        opengl
+        init_opengl(window).unwrap()
     })
 }
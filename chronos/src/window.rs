@@ -5,6 +5,8 @@ use winit::event::WindowEvent;
 use winit::event_loop::{ActiveEventLoop, ControlFlow, EventLoop};
 use winit::window::{Window, WindowId};
 
+use crate::entity::EntityManager;
+
 pub type Result<T> = std::result::Result<T, WinError>;
 
 #[derive(thiserror::Error, Debug)]
@@ -36,6 +38,32 @@ pub struct WindowConfig {
 pub struct ChronosWindow {
     window: Option<Window>,
     config: WindowConfig,
+    pending_events: Vec<ForwardedEvent>,
+    close_requested: bool,
+}
+
+/// Sent when the window is asked to close.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct WindowCloseRequested;
+
+/// Sent with the new inner size of the window in physical pixels.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct WindowResized {
+    pub width: u32,
+    pub height: u32,
+}
+
+/// Sent when the window gains or loses focus.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct WindowFocused {
+    pub focused: bool,
+}
+
+/// Window events waiting to be sent to the world by [`ChronosWindow::send_events`].
+enum ForwardedEvent {
+    CloseRequested(WindowCloseRequested),
+    Resized(WindowResized),
+    Focused(WindowFocused),
 }
 
 impl Default for WindowConfig {
@@ -58,9 +86,67 @@ impl ChronosWindow {
         Self {
             window: None,
             config,
+            pending_events: Vec::new(),
+            close_requested: false,
+        }
+    }
+
+    /// Registers the window event types, so systems can read them before
+    /// the first event arrives.
+    pub fn add_events(entity_manager: &mut EntityManager) {
+        entity_manager.add_event::<WindowCloseRequested>();
+        entity_manager.add_event::<WindowResized>();
+        entity_manager.add_event::<WindowFocused>();
+    }
+
+    /// Sends the window events received since the last call as typed events.
+    pub fn send_events(&mut self, entity_manager: &mut EntityManager) {
+        for event in self.pending_events.drain(..) {
+            match event {
+                ForwardedEvent::CloseRequested(event) => entity_manager.send_event(event),
+                ForwardedEvent::Resized(event) => entity_manager.send_event(event),
+                ForwardedEvent::Focused(event) => entity_manager.send_event(event),
+            }
         }
     }
 
+    /// Queues the window events forwarded to the world by the next
+    /// [`ChronosWindow::send_events`].
+    pub fn handle_event(&mut self, event: &WindowEvent) {
+        match *event {
+            WindowEvent::CloseRequested => {
+                self.close_requested = true;
+                self.pending_events
+                    .push(ForwardedEvent::CloseRequested(WindowCloseRequested));
+            }
+            WindowEvent::Resized(new_size) => {
+                println!("Window resized to: {}x{}", new_size.width, new_size.height);
+                self.pending_events
+                    .push(ForwardedEvent::Resized(WindowResized {
+                        width: new_size.width,
+                        height: new_size.height,
+                    }));
+            }
+            WindowEvent::Focused(focused) => {
+                self.pending_events
+                    .push(ForwardedEvent::Focused(WindowFocused { focused }));
+            }
+            _ => {}
+        }
+    }
+
+    /// Whether the window was asked to close. The event loop exits at the end
+    /// of the frame, once the close event has been sent to the world.
+    #[must_use]
+    pub fn close_requested(&self) -> bool {
+        self.close_requested
+    }
+
+    #[must_use]
+    pub fn window_mode(&self) -> &WindowMode {
+        &self.config.window_mode
+    }
+
     /// Runs the window event loop.
     ///
     /// # Errors
@@ -138,18 +224,50 @@ impl ApplicationHandler for ChronosWindow {
         }
     }
 
-    fn window_event(&mut self, event_loop: &ActiveEventLoop, _id: WindowId, event: WindowEvent) {
-        match event {
-            WindowEvent::CloseRequested => {
-                event_loop.exit();
-            }
-            WindowEvent::Resized(new_size) => {
-                println!("Window resized to: {}x{}", new_size.width, new_size.height);
-            }
-            _ => {}
+    fn window_event(&mut self, _event_loop: &ActiveEventLoop, _id: WindowId, event: WindowEvent) {
+        self.handle_event(&event);
+    }
+
+    fn about_to_wait(&mut self, event_loop: &ActiveEventLoop) {
+        if self.close_requested {
+            event_loop.exit();
         }
     }
 }
 
 #[cfg(test)]
-mod tests {}
+mod tests {
+    use winit::dpi::PhysicalSize;
+    use winit::event::WindowEvent;
+
+    use super::{ChronosWindow, WindowCloseRequested, WindowConfig, WindowFocused, WindowResized};
+    use crate::entity::{EntityManager, events::Events};
+
+    #[test]
+    fn test_window_send_events() {
+        let mut window = ChronosWindow::new(WindowConfig::default());
+        let mut entity_manager = EntityManager::default();
+        ChronosWindow::add_events(&mut entity_manager);
+        window.handle_event(&WindowEvent::Resized(PhysicalSize::new(800, 600)));
+        window.handle_event(&WindowEvent::Focused(true));
+
+        window.send_events(&mut entity_manager);
+
+        assert!(window.pending_events.is_empty());
+        let resized = entity_manager.resource::<Events<WindowResized>>().unwrap();
+        assert_eq!(
+            resized.iter().copied().collect::<Vec<_>>(),
+            vec![WindowResized {
+                width: 800,
+                height: 600
+            }]
+        );
+        let focused = entity_manager.resource::<Events<WindowFocused>>().unwrap();
+        assert_eq!(focused.len(), 1);
+        let close = entity_manager
+            .resource::<Events<WindowCloseRequested>>()
+            .unwrap();
+        assert!(close.is_empty());
+        assert!(!window.close_requested());
+    }
+}
@@ -0,0 +1,181 @@
+//! Derive macros for `chronos`. Use them through the re-exports in
+//! `chronos::entity`.
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::{format_ident, quote};
+use syn::{Data, DeriveInput, Fields, Index, LitInt, LitStr, parse_macro_input, parse_quote};
+
+/// Implements `ComponentBundle` for a struct. Every field is added as a
+/// component, except fields marked `#[bundle]`, which are bundles themselves.
+///
+/// ```ignore
+/// #[derive(Bundle)]
+/// struct Sprite {
+///     shape: Shape,
+///     color: Color,
+///     #[bundle]
+///     placement: Placement,
+/// }
+/// ```
+#[proc_macro_derive(Bundle, attributes(bundle))]
+pub fn derive_bundle(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    bundle(input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+/// Implements `Component` for a type, with its storage configuration given as
+/// `#[component(storage = "table", sparse_capacity = 4096, dense_capacity = 256)]`.
+/// `storage` is either `"sparse_set"`, the default, or `"table"`.
+#[proc_macro_derive(Component, attributes(component))]
+pub fn derive_component(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    component(input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+fn bundle(mut input: DeriveInput) -> syn::Result<TokenStream2> {
+    let Data::Struct(data) = &input.data else {
+        return Err(syn::Error::new_spanned(
+            &input.ident,
+            "Bundle can only be derived for structs",
+        ));
+    };
+    let fields = match &data.fields {
+        Fields::Named(fields) => fields.named.iter().collect(),
+        Fields::Unnamed(fields) => fields.unnamed.iter().collect(),
+        Fields::Unit => Vec::new(),
+    };
+
+    let mut bounds = Vec::new();
+    let mut adds = Vec::new();
+    let mut columns = Vec::new();
+    let mut pushes = Vec::new();
+    let mut extends = Vec::new();
+    for (index, field) in fields.into_iter().enumerate() {
+        let member = field.ident.as_ref().map_or_else(
+            || {
+                let index = Index::from(index);
+                quote!(#index)
+            },
+            |ident| quote!(#ident),
+        );
+        let field_type = &field.ty;
+        let column = format_ident!("__column_{}", index);
+        columns.push(quote!(let mut #column = ::std::vec::Vec::with_capacity(batch.len());));
+        if field
+            .attrs
+            .iter()
+            .any(|attr| attr.path().is_ident("bundle"))
+        {
+            bounds.push(quote!(#field_type: ::chronos::entity::ComponentBundle));
+            adds.push(quote! {
+                ::chronos::entity::ComponentBundle::add_to_entity(self.#member, entity, storage);
+            });
+            pushes.push(quote!(#column.push((entity, __bundle.#member));));
+            extends.push(quote! {
+                <#field_type as ::chronos::entity::ComponentBundle>::add_to_entities(#column, storage);
+            });
+        } else {
+            bounds.push(quote!(#field_type: Send + Sync + 'static));
+            adds.push(quote! {
+                storage.add_component(entity, self.#member);
+            });
+            pushes.push(quote!(#column.push(__bundle.#member);));
+            extends.push(quote!(storage.extend_components(&__entities, #column);));
+        }
+    }
+
+    let where_clause = input.generics.make_where_clause();
+    for bound in bounds {
+        where_clause.predicates.push(parse_quote!(#bound));
+    }
+    let name = &input.ident;
+    let (impl_generics, type_generics, where_clause) = input.generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics ::chronos::entity::ComponentBundle for #name #type_generics #where_clause {
+            fn add_to_entity(
+                self,
+                entity: ::chronos::entity::Entity,
+                storage: &mut ::chronos::entity::ComponentStorage,
+            ) {
+                #(#adds)*
+            }
+
+            fn add_to_entities(
+                batch: ::std::vec::Vec<(::chronos::entity::Entity, Self)>,
+                storage: &mut ::chronos::entity::ComponentStorage,
+            ) {
+                let mut __entities = ::std::vec::Vec::with_capacity(batch.len());
+                #(#columns)*
+                for (entity, __bundle) in batch {
+                    __entities.push(entity);
+                    #(#pushes)*
+                }
+                #(#extends)*
+            }
+        }
+    })
+}
+
+fn component(mut input: DeriveInput) -> syn::Result<TokenStream2> {
+    let mut settings = Vec::new();
+    for attr in input
+        .attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("component"))
+    {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("storage") {
+                let value: LitStr = meta.value()?.parse()?;
+                let kind = match value.value().as_str() {
+                    "sparse_set" => quote!(SparseSet),
+                    "table" => quote!(Table),
+                    _ => {
+                        return Err(syn::Error::new_spanned(
+                            value,
+                            "expected \"sparse_set\" or \"table\"",
+                        ));
+                    }
+                };
+                settings.push(quote!(config.kind = ::chronos::entity::StorageKind::#kind;));
+                return Ok(());
+            }
+            let value: LitInt = meta.value()?.parse()?;
+            let value: usize = value.base10_parse()?;
+            if meta.path.is_ident("sparse_capacity") {
+                settings.push(quote!(config.sparse_capacity = Some(#value);));
+            } else if meta.path.is_ident("dense_capacity") {
+                settings.push(quote!(config.dense_capacity = #value;));
+            } else {
+                return Err(meta.error("expected `storage`, `sparse_capacity` or `dense_capacity`"));
+            }
+            Ok(())
+        })?;
+    }
+
+    input
+        .generics
+        .make_where_clause()
+        .predicates
+        .push(parse_quote!(Self: Send + Sync + 'static));
+    let storage = (!settings.is_empty()).then(|| {
+        quote! {
+            const STORAGE: ::chronos::entity::StorageConfig = {
+                let mut config = ::chronos::entity::StorageConfig::DEFAULT;
+                #(#settings)*
+                config
+            };
+        }
+    });
+    let name = &input.ident;
+    let (impl_generics, type_generics, where_clause) = input.generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics ::chronos::entity::Component for #name #type_generics #where_clause {
+            #storage
+        }
+    })
+}